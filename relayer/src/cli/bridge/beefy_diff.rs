@@ -0,0 +1,345 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::ops::RangeInclusive;
+
+use bridge_common::beefy_types::ValidatorSet;
+use bridge_types::{GenericNetworkId, SubNetworkId};
+
+use crate::cli::prelude::*;
+use crate::substrate::beefy_subscription::find_mandatory_commitment;
+use crate::substrate::{BlockNumber, BlockNumberOrHash};
+
+use super::register;
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum Receiver {
+    /// BEEFY light client of parachain on SORA
+    Sora,
+    /// BEEFY light client of SORA on parachain
+    Parachain,
+}
+
+#[derive(Args, Clone, Debug)]
+pub(crate) struct Command {
+    #[clap(flatten)]
+    sub: SubstrateClient,
+    #[clap(flatten)]
+    para: ParachainClient,
+    /// Network with BEEFY light client to compare
+    #[clap(long, value_enum)]
+    receiver: Receiver,
+    /// Sender block to reinitialize light client at, finalized head by default
+    #[clap(long)]
+    block: Option<u32>,
+}
+
+impl Command {
+    pub async fn run(&self) -> AnyResult<()> {
+        let sub = self.sub.get_unsigned_substrate().await?;
+        let para = self.para.get_unsigned_substrate().await?;
+        match self.receiver {
+            Receiver::Sora => {
+                if diff(&para, &sub).await? {
+                    let block = reset_block(&para, self.block).await?;
+                    let call =
+                        register::sora::parachain::beefy::initialize_call(&para, Some(block))
+                            .await?;
+                    print_call(&call, "bridge register sora parachain beefy", block);
+                }
+            }
+            Receiver::Parachain => {
+                if diff(&sub, &para).await? {
+                    let block = reset_block(&sub, self.block).await?;
+                    let call =
+                        register::parachain::beefy::initialize_call(&sub, Some(block)).await?;
+                    print_call(&call, "bridge register parachain beefy", block);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn reset_block<S: SenderConfig>(
+    sender: &SubUnsignedClient<S>,
+    block: Option<BlockNumber<S>>,
+) -> AnyResult<BlockNumber<S>> {
+    match block {
+        Some(block) => Ok(block),
+        None => sender.block_number(BlockNumberOrHash::Finalized).await,
+    }
+}
+
+fn print_call<C: Encode + core::fmt::Debug>(call: &C, command: &str, block: u32) {
+    println!("Initialize call: {:?}", call);
+    println!("Encoded call for sudo: 0x{}", hex::encode(call.encode()));
+    println!("Submit it with `{command} --block {block}`");
+}
+
+/// Compares the light client state on the receiver with the validator sets
+/// on the sender and prints the difference. Returns true if the light client
+/// should be reinitialized.
+async fn diff<S, R>(
+    sender: &SubUnsignedClient<S>,
+    receiver: &SubUnsignedClient<R>,
+) -> AnyResult<bool>
+where
+    S: SenderConfig,
+    R: ReceiverConfig,
+{
+    let GenericNetworkId::Sub(network_id) = sender.constant_fetch_or_default(&S::network_id())?
+    else {
+        return Err(anyhow!("Sender is expected to be a Substrate network"));
+    };
+    let latest_beefy_block = receiver
+        .storage_fetch_or_default(&R::latest_beefy_block(network_id), ())
+        .await?;
+    let current = receiver
+        .storage_fetch(&R::current_validator_set(network_id), ())
+        .await?;
+    let next = receiver
+        .storage_fetch(&R::next_validator_set(network_id), ())
+        .await?;
+    let (Some(current), Some(next)) = (current, next) else {
+        println!("Light client for {:?} is not initialized", network_id);
+        return Ok(true);
+    };
+
+    let sender_current = sender
+        .storage_fetch_or_default(&S::current_validator_set(), latest_beefy_block)
+        .await?;
+    let sender_next = sender
+        .storage_fetch_or_default(&S::next_validator_set(), latest_beefy_block)
+        .await?;
+    let finalized = sender.block_number(BlockNumberOrHash::Finalized).await?;
+    let finalized_vset = sender
+        .storage_fetch_or_default(&S::current_validator_set(), finalized)
+        .await?;
+    let finalized: u64 = finalized.into();
+
+    println!("Light client for {:?}", network_id);
+    println!("  latest BEEFY block: {}", latest_beefy_block);
+    println!("  current validator set: {:?}", current);
+    println!("  next validator set: {:?}", next);
+    println!("Sender at block {}", latest_beefy_block);
+    println!("  current validator set: {:?}", sender_current);
+    println!("  next validator set: {:?}", sender_next);
+    println!(
+        "Sender at finalized block {}: validator set {}",
+        finalized, finalized_vset.id
+    );
+
+    let comparison = compare(
+        &current,
+        &next,
+        &sender_current,
+        &sender_next,
+        finalized_vset.id,
+    );
+    for mismatch in &comparison.mismatches {
+        report_divergence(
+            sender,
+            network_id,
+            mismatch.kind,
+            mismatch.receiver_id,
+            mismatch.sender_id,
+        )
+        .await?;
+    }
+    // Light client accepts commitments signed by its current or next set only,
+    // so every set change on the sender must be relayed by a mandatory commitment.
+    // The relayer does it in order, so lag is not a reason to reinitialize.
+    if !comparison.unrelayed.is_empty() {
+        println!(
+            "Mandatory commitments for validator sets {}..={} are not relayed yet:",
+            comparison.unrelayed.start(),
+            comparison.unrelayed.end()
+        );
+        for vset_id in comparison.unrelayed.clone() {
+            print_vset_start(sender, vset_id).await?;
+        }
+    }
+    if !comparison.diverged() {
+        println!("Light client state matches sender");
+    }
+    Ok(comparison.diverged())
+}
+
+/// Validator set with a known id.
+trait VsetId {
+    fn vset_id(&self) -> u64;
+}
+
+impl VsetId for ValidatorSet {
+    fn vset_id(&self) -> u64 {
+        self.id
+    }
+}
+
+/// Validator set which differs between the light client and the sender.
+#[derive(Debug, PartialEq, Eq)]
+struct Mismatch {
+    kind: &'static str,
+    receiver_id: u64,
+    sender_id: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Comparison {
+    mismatches: Vec<Mismatch>,
+    /// Sender validator sets which light client doesn't know yet.
+    unrelayed: RangeInclusive<u64>,
+}
+
+impl Comparison {
+    fn diverged(&self) -> bool {
+        !self.mismatches.is_empty()
+    }
+}
+
+/// Compares light client sets with the sender sets at the latest BEEFY block.
+/// Sender sets newer than the light client next set are reported as lag.
+fn compare<V: VsetId + PartialEq>(
+    current: &V,
+    next: &V,
+    sender_current: &V,
+    sender_next: &V,
+    finalized_vset_id: u64,
+) -> Comparison {
+    let mut mismatches = vec![];
+    for (kind, receiver, sender) in [
+        ("current", current, sender_current),
+        ("next", next, sender_next),
+    ] {
+        if receiver != sender {
+            mismatches.push(Mismatch {
+                kind,
+                receiver_id: receiver.vset_id(),
+                sender_id: sender.vset_id(),
+            });
+        }
+    }
+    Comparison {
+        mismatches,
+        unrelayed: (next.vset_id() + 1)..=finalized_vset_id,
+    }
+}
+
+async fn report_divergence<S: SenderConfig>(
+    sender: &SubUnsignedClient<S>,
+    network_id: SubNetworkId,
+    kind: &str,
+    receiver_id: u64,
+    sender_id: u64,
+) -> AnyResult<()> {
+    println!(
+        "Diverged {kind} validator set of {:?}: receiver has set {receiver_id}, sender has set {sender_id}",
+        network_id
+    );
+    print_vset_start(sender, receiver_id).await
+}
+
+async fn print_vset_start<S: SenderConfig>(
+    sender: &SubUnsignedClient<S>,
+    vset_id: u64,
+) -> AnyResult<()> {
+    match find_mandatory_commitment(sender, vset_id).await? {
+        Some(block) => {
+            let vset = sender
+                .storage_fetch_or_default(&S::current_validator_set(), block)
+                .await?;
+            let block: u64 = block.into();
+            println!(
+                "  validator set {} starts at sender block {}: {:?}",
+                vset_id, block, vset
+            );
+        }
+        None => println!("  validator set {} is not found on sender", vset_id),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Vset(u64, u8);
+
+    impl VsetId for Vset {
+        fn vset_id(&self) -> u64 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn matching_state_is_not_diverged() {
+        let comparison = compare(&Vset(1, 1), &Vset(2, 2), &Vset(1, 1), &Vset(2, 2), 2);
+        assert!(!comparison.diverged());
+        assert!(comparison.unrelayed.is_empty());
+    }
+
+    #[test]
+    fn lag_is_not_diverged() {
+        let comparison = compare(&Vset(1, 1), &Vset(2, 2), &Vset(1, 1), &Vset(2, 2), 5);
+        assert!(!comparison.diverged());
+        assert_eq!(comparison.unrelayed, 3..=5);
+    }
+
+    #[test]
+    fn current_mismatch_is_diverged() {
+        let comparison = compare(&Vset(1, 1), &Vset(2, 2), &Vset(1, 3), &Vset(2, 2), 2);
+        assert!(comparison.diverged());
+        assert_eq!(
+            comparison.mismatches,
+            vec![Mismatch {
+                kind: "current",
+                receiver_id: 1,
+                sender_id: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn next_mismatch_is_diverged() {
+        let comparison = compare(&Vset(1, 1), &Vset(2, 2), &Vset(1, 1), &Vset(3, 3), 3);
+        assert!(comparison.diverged());
+        assert_eq!(
+            comparison.mismatches,
+            vec![Mismatch {
+                kind: "next",
+                receiver_id: 2,
+                sender_id: 3
+            }]
+        );
+        assert_eq!(comparison.unrelayed, 3..=3);
+    }
+}
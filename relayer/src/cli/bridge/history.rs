@@ -0,0 +1,353 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::cli::error::CliError;
+use crate::cli::prelude::*;
+use crate::history::{EntryKind, HistoryEntry, Outcome};
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub(crate) enum Format {
+    #[default]
+    Table,
+    Json,
+    Csv,
+}
+
+#[derive(Args, Clone, Debug)]
+pub(crate) struct Command {
+    #[clap(from_global)]
+    history_path: Option<PathBuf>,
+    /// Show only entries of this kind
+    #[clap(long, value_enum)]
+    kind: Option<EntryKind>,
+    /// Show only entries with source or target network containing this string, e.g. `EVM(1)` or `Mainnet`
+    #[clap(long)]
+    network: Option<String>,
+    /// Show only entries with this commitment nonce
+    #[clap(long)]
+    nonce: Option<u64>,
+    /// Show only entries with this outcome
+    #[clap(long, value_enum)]
+    outcome: Option<Outcome>,
+    /// Show entries recorded at or after this time (RFC 3339)
+    #[clap(long)]
+    since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Show entries recorded before this time (RFC 3339)
+    #[clap(long)]
+    until: Option<chrono::DateTime<chrono::Utc>>,
+    /// Show only the latest entries
+    #[clap(long)]
+    limit: Option<usize>,
+    #[clap(long, value_enum, default_value_t)]
+    format: Format,
+    /// Write to the file instead of stdout
+    #[clap(long)]
+    output: Option<PathBuf>,
+}
+
+impl Command {
+    pub(super) async fn run(&self) -> AnyResult<()> {
+        let path = self.history_path.as_ref().ok_or(CliError::HistoryPath)?;
+        let entries = self.select(HistoryEntry::read_all(path)?);
+        let output = match self.format {
+            Format::Table => table(&entries),
+            Format::Json => serde_json::to_string_pretty(&entries)? + "\n",
+            Format::Csv => csv(&entries),
+        };
+        match &self.output {
+            Some(path) => std::fs::write(path, output)
+                .with_context(|| format!("write history to {}", path.display()))?,
+            None => std::io::stdout().write_all(output.as_bytes())?,
+        }
+        Ok(())
+    }
+
+    /// Returns entries matching the filters, only the latest `limit` ones if it is set.
+    fn select(&self, entries: Vec<HistoryEntry>) -> Vec<HistoryEntry> {
+        let mut entries = entries
+            .into_iter()
+            .filter(|entry| self.matches(entry))
+            .collect::<Vec<_>>();
+        if let Some(limit) = self.limit {
+            entries.drain(..entries.len().saturating_sub(limit));
+        }
+        entries
+    }
+
+    fn matches(&self, entry: &HistoryEntry) -> bool {
+        let network_matches = |filter: &String| {
+            let filter = filter.to_lowercase();
+            entry.source.to_lowercase().contains(&filter)
+                || entry.target.to_lowercase().contains(&filter)
+        };
+        self.kind.map_or(true, |kind| entry.kind == kind)
+            && self.network.as_ref().map_or(true, network_matches)
+            && self.nonce.map_or(true, |nonce| entry.nonce == Some(nonce))
+            && self
+                .outcome
+                .map_or(true, |outcome| entry.outcome == outcome)
+            && self
+                .since
+                .map_or(true, |since| entry.timestamp >= since.timestamp())
+            && self
+                .until
+                .map_or(true, |until| entry.timestamp < until.timestamp())
+    }
+}
+
+const CSV_HEADER: [&str; 11] = [
+    "timestamp",
+    "kind",
+    "source",
+    "target",
+    "nonce",
+    "commitment_hash",
+    "tx_hash",
+    "block_hash",
+    "block_number",
+    "outcome",
+    "error",
+];
+
+fn format_time(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|date| date.to_rfc3339())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+fn format_option<T: core::fmt::Debug>(value: &Option<T>) -> String {
+    value
+        .as_ref()
+        .map(|value| format!("{:?}", value))
+        .unwrap_or_default()
+}
+
+fn kind_name(kind: EntryKind) -> String {
+    kind.to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+fn outcome_name(outcome: Outcome) -> String {
+    outcome
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+fn table(entries: &[HistoryEntry]) -> String {
+    let mut output = format!(
+        "{:<25}  {:<18}  {:<14}  {:<14}  {:>8}  {:<7}  {}\n",
+        "time", "kind", "source", "target", "nonce", "outcome", "tx hash"
+    );
+    for entry in entries {
+        output += &format!(
+            "{:<25}  {:<18}  {:<14}  {:<14}  {:>8}  {:<7}  {}\n",
+            format_time(entry.timestamp),
+            kind_name(entry.kind),
+            entry.source,
+            entry.target,
+            entry
+                .nonce
+                .map(|nonce| nonce.to_string())
+                .unwrap_or_default(),
+            outcome_name(entry.outcome),
+            format_option(&entry.tx_hash),
+        );
+    }
+    output
+}
+
+fn csv_field(field: String) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+fn csv(entries: &[HistoryEntry]) -> String {
+    let mut output = CSV_HEADER.join(",") + "\n";
+    for entry in entries {
+        let row = [
+            format_time(entry.timestamp),
+            kind_name(entry.kind),
+            entry.source.clone(),
+            entry.target.clone(),
+            entry
+                .nonce
+                .map(|nonce| nonce.to_string())
+                .unwrap_or_default(),
+            format_option(&entry.commitment_hash),
+            format_option(&entry.tx_hash),
+            format_option(&entry.block_hash),
+            entry
+                .block_number
+                .map(|number| number.to_string())
+                .unwrap_or_default(),
+            outcome_name(entry.outcome),
+            entry.error.clone().unwrap_or_default(),
+        ];
+        output += &row.map(csv_field).join(",");
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bridge_types::{GenericNetworkId, SubNetworkId};
+
+    fn command() -> Command {
+        Command {
+            history_path: None,
+            kind: None,
+            network: None,
+            nonce: None,
+            outcome: None,
+            since: None,
+            until: None,
+            limit: None,
+            format: Format::Table,
+            output: None,
+        }
+    }
+
+    fn entry(
+        kind: EntryKind,
+        target: GenericNetworkId,
+        nonce: u64,
+        timestamp: i64,
+    ) -> HistoryEntry {
+        let mut entry =
+            HistoryEntry::new(kind, GenericNetworkId::Sub(SubNetworkId::Mainnet), target)
+                .with_nonce(nonce);
+        entry.timestamp = timestamp;
+        entry
+    }
+
+    fn entries() -> Vec<HistoryEntry> {
+        let evm = || GenericNetworkId::EVM(1u64.into());
+        let parachain = GenericNetworkId::Sub(SubNetworkId::Kusama);
+        vec![
+            entry(EntryKind::Approval, evm(), 1, 100),
+            entry(EntryKind::EvmBatch, evm(), 1, 200),
+            entry(EntryKind::InboundCommitment, parachain, 2, 300).with_error(&anyhow!("failed")),
+            entry(EntryKind::EvmBatch, evm(), 2, 400),
+        ]
+    }
+
+    fn nonces_and_times(entries: &[HistoryEntry]) -> Vec<(Option<u64>, i64)> {
+        entries
+            .iter()
+            .map(|entry| (entry.nonce, entry.timestamp))
+            .collect()
+    }
+
+    #[test]
+    fn filters_entries() {
+        let mut command = command();
+        assert_eq!(command.select(entries()).len(), 4);
+
+        command.kind = Some(EntryKind::EvmBatch);
+        assert_eq!(
+            nonces_and_times(&command.select(entries())),
+            vec![(Some(1), 200), (Some(2), 400)]
+        );
+
+        let mut command = self::command();
+        command.network = Some("kusama".to_string());
+        assert_eq!(
+            nonces_and_times(&command.select(entries())),
+            vec![(Some(2), 300)]
+        );
+
+        let mut command = self::command();
+        command.nonce = Some(1);
+        command.outcome = Some(Outcome::Success);
+        assert_eq!(command.select(entries()).len(), 2);
+
+        let mut command = self::command();
+        command.outcome = Some(Outcome::Failed);
+        assert_eq!(
+            nonces_and_times(&command.select(entries())),
+            vec![(Some(2), 300)]
+        );
+    }
+
+    #[test]
+    fn filters_entries_by_time() {
+        let mut command = command();
+        command.since = chrono::DateTime::from_timestamp(200, 0);
+        command.until = chrono::DateTime::from_timestamp(400, 0);
+        assert_eq!(
+            nonces_and_times(&command.select(entries())),
+            vec![(Some(1), 200), (Some(2), 300)]
+        );
+    }
+
+    #[test]
+    fn limit_keeps_latest_entries() {
+        let mut command = command();
+        command.limit = Some(2);
+        assert_eq!(
+            nonces_and_times(&command.select(entries())),
+            vec![(Some(2), 300), (Some(2), 400)]
+        );
+
+        command.kind = Some(EntryKind::Approval);
+        assert_eq!(command.select(entries()).len(), 1);
+
+        command.limit = Some(0);
+        assert!(command.select(entries()).is_empty());
+    }
+
+    #[test]
+    fn escapes_csv_fields() {
+        assert_eq!(csv_field("plain".to_string()), "plain");
+        assert_eq!(csv_field("a,b".to_string()), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\"".to_string()), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("line\nbreak".to_string()), "\"line\nbreak\"");
+        assert_eq!(csv_field(String::new()), "");
+    }
+
+    #[test]
+    fn csv_has_row_per_entry() {
+        let output = csv(&entries());
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], CSV_HEADER.join(","));
+        assert!(lines[3].ends_with(",failed,failed"));
+    }
+}
@@ -0,0 +1,194 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use bridge_types::types::AuxiliaryDigestItem;
+use bridge_types::GenericNetworkId;
+use ethers::abi::AbiDecode;
+use sp_runtime::traits::Keccak256;
+
+use crate::cli::prelude::*;
+use crate::relay::messages_subscription::{leaf_proof_with_digest, load_digest};
+use crate::relay::traits::OutboundChannelReader;
+use crate::substrate::{BlockNumberOrHash, UnboundedGenericCommitment};
+
+#[derive(Args, Clone, Debug)]
+pub(crate) struct Command {
+    #[clap(flatten)]
+    sub: SubstrateClient,
+    #[clap(flatten)]
+    para: ParachainClient,
+    /// Target network of the commitment, e.g. `evm:1`, `sub:kusama` or `ton:mainnet`
+    #[clap(long, value_parser = parse_network_id)]
+    network: GenericNetworkId,
+    /// Commitment nonce
+    #[clap(long)]
+    nonce: u64,
+}
+
+impl Command {
+    pub async fn run(&self) -> AnyResult<()> {
+        let sub = self.sub.get_unsigned_substrate().await?;
+        let offchain_data = sub
+            .commitment_with_nonce(self.network, self.nonce, BlockNumberOrHash::Finalized)
+            .await?;
+        let commitment_hash = offchain_data.commitment.hash();
+        let digest = load_digest(
+            &sub,
+            self.network,
+            offchain_data.block_number,
+            commitment_hash,
+        )
+        .await?;
+        let digest_hash = Keccak256::hash_of(&digest);
+
+        println!(
+            "Commitment {} to {:?} at block {}",
+            self.nonce, self.network, offchain_data.block_number
+        );
+        println!("Commitment hash: {:?}", commitment_hash);
+        println!("Digest hash: {:?}", digest_hash);
+        println!("Digest:");
+        for AuxiliaryDigestItem::Commitment(network_id, hash) in digest.logs.iter() {
+            let marker = if *network_id == self.network && *hash == commitment_hash {
+                "*"
+            } else {
+                " "
+            };
+            println!("  {marker} {:?} {:?}", network_id, hash);
+        }
+        print_messages(&offchain_data.commitment);
+
+        let finalized = sub.block_number(BlockNumberOrHash::Finalized).await?;
+        let leaf_block = match leaf_proof_with_digest(
+            &sub,
+            digest_hash,
+            offchain_data.block_number,
+            50,
+            finalized,
+        )
+        .await
+        {
+            Ok(leaf_proof) => leaf_proof.leaf.parent_number_and_hash.0 + 1,
+            Err(err) => {
+                println!("MMR leaf: not found in finalized blocks: {err}");
+                return Ok(());
+            }
+        };
+        println!("MMR leaf: block {}", leaf_block);
+        // Only parachain light client verifies SORA MMR leaves
+        if !matches!(self.network, GenericNetworkId::Sub(_)) {
+            println!("MMR leaf proven: not applicable for {:?}", self.network);
+            return Ok(());
+        }
+        if !self.para.is_configured() {
+            println!("MMR leaf proven: unknown, provide --parachain-url to check light client");
+            return Ok(());
+        }
+        let para = self.para.get_unsigned_substrate().await?;
+        let para_network_id = OutboundChannelReader::network_id(&para)?;
+        if para_network_id != self.network {
+            println!(
+                "MMR leaf proven: not applicable, parachain is {:?}",
+                para_network_id
+            );
+            return Ok(());
+        }
+        let GenericNetworkId::Sub(sora_network_id) = OutboundChannelReader::network_id(&sub)?
+        else {
+            return Err(anyhow!("SORA is expected to be a Substrate network"));
+        };
+        let latest_beefy_block = para
+            .storage_fetch_or_default(&ParachainConfig::latest_beefy_block(sora_network_id), ())
+            .await?;
+        println!(
+            "MMR leaf proven: {} (latest BEEFY block {})",
+            leaf_block as u64 <= latest_beefy_block,
+            latest_beefy_block
+        );
+        Ok(())
+    }
+}
+
+fn print_messages(commitment: &UnboundedGenericCommitment) {
+    match commitment {
+        UnboundedGenericCommitment::EVM(bridge_types::evm::Commitment::Outbound(commitment)) => {
+            println!(
+                "Messages ({}), total max gas {}:",
+                commitment.messages.len(),
+                commitment.total_max_gas
+            );
+            for (i, message) in commitment.messages.iter().enumerate() {
+                println!(
+                    "  #{i} target {:?}, max gas {}",
+                    message.target, message.max_gas
+                );
+                match ethereum_gen::fa_app::FAAppCalls::decode(message.payload.as_slice()) {
+                    Ok(call) => println!("{}", indent(&format!("{:#?}", call))),
+                    Err(_) => println!("    0x{}", hex::encode(&message.payload)),
+                }
+            }
+        }
+        UnboundedGenericCommitment::Sub(commitment) => {
+            println!("Messages ({}):", commitment.messages.len());
+            for (i, message) in commitment.messages.iter().enumerate() {
+                println!("  #{i}");
+                match decode_substrate_call(&message.payload) {
+                    Some(call) => println!("{}", indent(&call)),
+                    None => println!("    0x{}", hex::encode(&message.payload)),
+                }
+            }
+        }
+        commitment => println!("Commitment: {:#?}", commitment),
+    }
+}
+
+/// Decodes Substrate message payload into the call dispatched on the target.
+/// Parachain app calls are decoded with the runtime types generated from the
+/// parachain metadata. Other bridge calls are not in any runtime metadata, so
+/// they are decoded with `bridge_types`.
+fn decode_substrate_call(payload: &[u8]) -> Option<String> {
+    use bridge_types::substrate::BridgeCall;
+    use parachain_runtime::runtime_types::bridge_types::substrate::ParachainAppCall;
+
+    let call = BridgeCall::decode(&mut &payload[..]).ok()?;
+    if let BridgeCall::ParachainApp(_) = call {
+        // Variant index is followed by the pallet call
+        let call = ParachainAppCall::decode(&mut &payload[1..]).ok()?;
+        return Some(format!("ParachainApp({:#?})", call));
+    }
+    Some(format!("{:#?}", call))
+}
+
+fn indent(s: &str) -> String {
+    s.lines()
+        .map(|line| format!("    {line}"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
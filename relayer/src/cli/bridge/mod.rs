@@ -28,9 +28,15 @@
 // STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
 // USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
+mod beefy_diff;
+mod history;
+mod inspect;
 mod register;
 mod relay;
+mod run;
+mod status;
 mod transfer;
+mod verify_beefy;
 
 use crate::cli::prelude::*;
 
@@ -45,6 +51,19 @@ pub(crate) enum Commands {
     /// Transfer operations for bridge
     #[clap(subcommand)]
     Transfer(transfer::Commands),
+    /// Run relays described in config file
+    Run(run::Command),
+    /// Show approvals and commitments submitted by this relayer
+    History(history::Command),
+    /// Show nonces, approvals and light client state for every bridged network
+    Status(status::Command),
+    /// Decode bridge data stored on SORA
+    #[clap(subcommand)]
+    Inspect(inspect::Commands),
+    /// Verify BEEFY justification of SORA block before relaying it
+    VerifyBeefy(verify_beefy::Command),
+    /// Compare BEEFY light client state with sender validator sets
+    BeefyDiff(beefy_diff::Command),
 }
 
 impl Commands {
@@ -53,6 +72,12 @@ impl Commands {
             Commands::Relay(cmd) => cmd.run().await,
             Commands::Register(cmd) => cmd.run().await,
             Commands::Transfer(cmd) => cmd.run().await,
+            Commands::Run(cmd) => cmd.run().await,
+            Commands::History(cmd) => cmd.run().await,
+            Commands::Status(cmd) => cmd.run().await,
+            Commands::Inspect(cmd) => cmd.run().await,
+            Commands::VerifyBeefy(cmd) => cmd.run().await,
+            Commands::BeefyDiff(cmd) => cmd.run().await,
         }
     }
 }
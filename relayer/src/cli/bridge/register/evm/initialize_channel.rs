@@ -29,6 +29,7 @@
 // USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
 use crate::cli::prelude::*;
+use crate::ethereum::gas::GasRecord;
 use bridge_types::H160;
 
 #[derive(Args, Debug)]
@@ -53,11 +54,24 @@ impl Command {
         let call = call.legacy().from(eth.address());
         debug!("Static call: {:?}", call);
         call.call().await?;
+        let estimated_gas = call.estimate_gas().await?;
+        debug!("Estimated gas: {}", estimated_gas);
+        if crate::dry_run::is_enabled() {
+            eth.dry_run(&call).await?;
+            return Ok(());
+        }
         debug!("Send transaction");
         let pending = call.send().await?;
         debug!("Pending transaction: {:?}", pending);
         let result = pending.confirmations(1).await?;
         debug!("Confirmed: {:?}", result);
+        let tx = result.ok_or(anyhow!("Transaction dropped"))?;
+        eth.save_gas_record(&GasRecord::new(
+            &call.function.name,
+            None,
+            estimated_gas,
+            &tx,
+        ));
         Ok(())
     }
 }
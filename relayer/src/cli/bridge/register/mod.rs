@@ -32,8 +32,8 @@
 #![allow(clippy::all)]
 mod evm;
 mod liberland;
-mod parachain;
-mod sora;
+pub(crate) mod parachain;
+pub(crate) mod sora;
 mod ton;
 
 use crate::cli::prelude::*;
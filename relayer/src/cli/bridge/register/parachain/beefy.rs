@@ -39,7 +39,7 @@ pub(crate) struct Command {
     #[clap(flatten)]
     para: ParachainClient,
     #[clap(long)]
-    block: Option<BlockNumber<ParachainConfig>>,
+    block: Option<BlockNumber<MainnetConfig>>,
 }
 
 impl Command {
@@ -47,49 +47,7 @@ impl Command {
         let sub = self.sub.get_unsigned_substrate().await?;
         let para = self.para.get_signed_substrate().await?;
 
-        let (block_number, block_hash) = if let Some(block) = self.block {
-            let hash = sub
-                .api()
-                .rpc()
-                .block_hash(Some(block.into()))
-                .await?
-                .ok_or(anyhow!("Block {} not found on mainnet", block))?;
-            (block, hash)
-        } else {
-            let hash = sub.api().rpc().finalized_head().await?;
-            let number = sub.block_number(hash).await?;
-            (number, hash)
-        };
-        let authorities = sub
-            .storage_fetch(
-                &mainnet_runtime::storage().mmr_leaf().beefy_authorities(),
-                block_hash,
-            )
-            .await?
-            .ok_or(anyhow!("Beefy authorities not found"))?;
-        let next_authorities = sub
-            .storage_fetch(
-                &mainnet_runtime::storage()
-                    .mmr_leaf()
-                    .beefy_next_authorities(),
-                block_hash,
-            )
-            .await?
-            .ok_or(anyhow!("Beefy authorities not found"))?;
-        let GenericNetworkId::Sub(network_id) = sub.constant_fetch_or_default(
-            &mainnet_runtime::constants()
-                .substrate_bridge_outbound_channel()
-                .this_network_id(),
-        )?
-        else {
-            return Err(anyhow!("Network ID not found"));
-        };
-
-        let call = parachain_runtime::runtime_types::sora2_parachain_runtime::RuntimeCall::BeefyLightClient(parachain_runtime::runtime_types::beefy_light_client::pallet::Call::initialize {
-                network_id,
-                latest_beefy_block: block_number.into(),
-                validator_set: authorities,
-                next_validator_set: next_authorities });
+        let call = initialize_call(&sub, self.block).await?;
         info!("Submit call: {call:?}");
         let call = parachain_runtime::tx().sudo().sudo(call);
         para.submit_extrinsic(&call).await?;
@@ -97,3 +55,59 @@ impl Command {
         Ok(())
     }
 }
+
+/// Builds the call which initializes the parachain BEEFY light client with
+/// SORA validator sets at the given block or at the finalized head.
+pub(crate) async fn initialize_call(
+    sub: &SubUnsignedClient<MainnetConfig>,
+    block: Option<BlockNumber<MainnetConfig>>,
+) -> AnyResult<parachain_runtime::runtime_types::sora2_parachain_runtime::RuntimeCall> {
+    let (block_number, block_hash) = if let Some(block) = block {
+        let hash = sub
+            .api()
+            .rpc()
+            .block_hash(Some(block.into()))
+            .await?
+            .ok_or(anyhow!("Block {} not found on mainnet", block))?;
+        (block, hash)
+    } else {
+        let hash = sub.api().rpc().finalized_head().await?;
+        let number = sub.block_number(hash).await?;
+        (number, hash)
+    };
+    let authorities = sub
+        .storage_fetch(
+            &mainnet_runtime::storage().mmr_leaf().beefy_authorities(),
+            block_hash,
+        )
+        .await?
+        .ok_or(anyhow!("Beefy authorities not found"))?;
+    let next_authorities = sub
+        .storage_fetch(
+            &mainnet_runtime::storage()
+                .mmr_leaf()
+                .beefy_next_authorities(),
+            block_hash,
+        )
+        .await?
+        .ok_or(anyhow!("Beefy authorities not found"))?;
+    let GenericNetworkId::Sub(network_id) = sub.constant_fetch_or_default(
+        &mainnet_runtime::constants()
+            .substrate_bridge_outbound_channel()
+            .this_network_id(),
+    )?
+    else {
+        return Err(anyhow!("Network ID not found"));
+    };
+
+    Ok(
+        parachain_runtime::runtime_types::sora2_parachain_runtime::RuntimeCall::BeefyLightClient(
+            parachain_runtime::runtime_types::beefy_light_client::pallet::Call::initialize {
+                network_id,
+                latest_beefy_block: block_number.into(),
+                validator_set: authorities,
+                next_validator_set: next_authorities,
+            },
+        ),
+    )
+}
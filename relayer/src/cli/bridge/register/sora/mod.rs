@@ -30,7 +30,7 @@
 
 mod evm;
 mod liberland;
-mod parachain;
+pub(crate) mod parachain;
 mod sora;
 mod ton;
 
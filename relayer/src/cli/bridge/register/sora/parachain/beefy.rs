@@ -39,7 +39,7 @@ pub(crate) struct Command {
     #[clap(flatten)]
     para: ParachainClient,
     #[clap(long)]
-    block: Option<BlockNumber<MainnetConfig>>,
+    block: Option<BlockNumber<ParachainConfig>>,
 }
 
 impl Command {
@@ -47,56 +47,67 @@ impl Command {
         let sub = self.sub.get_signed_substrate().await?;
         let para = self.para.get_unsigned_substrate().await?;
 
-        let (block_number, block_hash) = if let Some(block) = self.block {
-            let hash = para
-                .api()
-                .rpc()
-                .block_hash(Some(block.into()))
-                .await?
-                .ok_or(anyhow!("Block {} not found on mainnet", block))?;
-            (block, hash)
-        } else {
-            let hash = para.api().rpc().finalized_head().await?;
-            let number = para.block_number(hash).await?;
-            (number, hash)
-        };
-        let authorities = para
-            .storage_fetch(
-                &parachain_runtime::storage().beefy_mmr().beefy_authorities(),
-                block_hash,
-            )
-            .await?
-            .ok_or(anyhow!("Beefy authorities not found"))?;
-        let next_authorities = para
-            .storage_fetch(
-                &parachain_runtime::storage()
-                    .beefy_mmr()
-                    .beefy_next_authorities(),
-                block_hash,
-            )
+        let call = initialize_call(&para, self.block).await?;
+        info!("Submit call: {call:?}");
+        let call = mainnet_runtime::tx().sudo().sudo(call);
+        sub.submit_extrinsic(&call).await?;
+
+        Ok(())
+    }
+}
+
+/// Builds the call which initializes the SORA BEEFY light client with
+/// parachain validator sets at the given block or at the finalized head.
+pub(crate) async fn initialize_call(
+    para: &SubUnsignedClient<ParachainConfig>,
+    block: Option<BlockNumber<ParachainConfig>>,
+) -> AnyResult<mainnet_runtime::runtime_types::framenode_runtime::RuntimeCall> {
+    let (block_number, block_hash) = if let Some(block) = block {
+        let hash = para
+            .api()
+            .rpc()
+            .block_hash(Some(block.into()))
             .await?
-            .ok_or(anyhow!("Beefy authorities not found"))?;
-        let GenericNetworkId::Sub(network_id) = para.constant_fetch_or_default(
-            &parachain_runtime::constants()
-                .substrate_bridge_outbound_channel()
-                .this_network_id(),
-        )?
-        else {
-            return Err(anyhow!("Network id not found"));
-        };
+            .ok_or(anyhow!("Block {} not found on parachain", block))?;
+        (block, hash)
+    } else {
+        let hash = para.api().rpc().finalized_head().await?;
+        let number = para.block_number(hash).await?;
+        (number, hash)
+    };
+    let authorities = para
+        .storage_fetch(
+            &parachain_runtime::storage().beefy_mmr().beefy_authorities(),
+            block_hash,
+        )
+        .await?
+        .ok_or(anyhow!("Beefy authorities not found"))?;
+    let next_authorities = para
+        .storage_fetch(
+            &parachain_runtime::storage()
+                .beefy_mmr()
+                .beefy_next_authorities(),
+            block_hash,
+        )
+        .await?
+        .ok_or(anyhow!("Beefy authorities not found"))?;
+    let GenericNetworkId::Sub(network_id) = para.constant_fetch_or_default(
+        &parachain_runtime::constants()
+            .substrate_bridge_outbound_channel()
+            .this_network_id(),
+    )?
+    else {
+        return Err(anyhow!("Network id not found"));
+    };
 
-        let call = mainnet_runtime::runtime_types::framenode_runtime::RuntimeCall::BeefyLightClient(
+    Ok(
+        mainnet_runtime::runtime_types::framenode_runtime::RuntimeCall::BeefyLightClient(
             mainnet_runtime::runtime_types::beefy_light_client::pallet::Call::initialize {
                 network_id,
                 latest_beefy_block: block_number.into(),
                 validator_set: authorities,
                 next_validator_set: next_authorities,
             },
-        );
-        info!("Submit call: {call:?}");
-        let call = mainnet_runtime::tx().sudo().sudo(call);
-        sub.submit_extrinsic(&call).await?;
-
-        Ok(())
-    }
+        ),
+    )
 }
@@ -28,9 +28,12 @@
 // STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
 // USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
+use std::path::PathBuf;
 use std::time::Duration;
 
 use crate::cli::prelude::*;
+use crate::ethereum::logs::PagedClient;
+use crate::relay::checkpoint::FileCheckpointStore;
 use crate::relay::evm::evm_messages::SubstrateMessagesRelay;
 
 #[derive(Args, Clone, Debug)]
@@ -42,6 +45,8 @@ pub(crate) struct Command {
     /// Signer for bridge messages
     #[clap(long)]
     signer: String,
+    #[clap(from_global)]
+    state_dir: Option<PathBuf>,
 }
 
 impl Command {
@@ -49,29 +54,43 @@ impl Command {
         let eth = self.eth.get_unsigned_evm().await?;
         let sub = self.sub.get_unsigned_substrate().await?;
         let signer = sp_core::ecdsa::Pair::from_string(&self.signer, None)?;
-        let chain_id = eth.chainid().await?;
-        debug!("Eth chain id = {}", chain_id);
-        loop {
-            let has_channel = sub
-                .storage_fetch(
-                    &runtime::storage()
-                        .bridge_inbound_channel()
-                        .evm_channel_addresses(&chain_id),
-                    (),
-                )
-                .await?
-                .is_some();
-            if has_channel {
-                break;
-            }
-            debug!(
-                "Waiting for bridge to be available. Channel status = {}",
-                has_channel
-            );
-            tokio::time::sleep(Duration::from_secs(10)).await;
+        run_relay(sub, eth, signer, self.state_dir.clone()).await
+    }
+}
+
+/// Waits for the EVM channel to be registered on SORA and relays messages from it.
+pub(crate) async fn run_relay(
+    sub: SubUnsignedClient<MainnetConfig>,
+    eth: EthUnsignedClient,
+    signer: sp_core::ecdsa::Pair,
+    state_dir: Option<PathBuf>,
+) -> AnyResult<()> {
+    let chain_id = eth.chainid().await?;
+    debug!("Eth chain id = {}", chain_id);
+    loop {
+        let has_channel = sub
+            .storage_fetch(
+                &runtime::storage()
+                    .bridge_inbound_channel()
+                    .evm_channel_addresses(&chain_id),
+                (),
+            )
+            .await?
+            .is_some();
+        if has_channel {
+            break;
         }
-        let messages_relay = SubstrateMessagesRelay::new(sub, eth, signer).await?;
-        messages_relay.run().await?;
-        Ok(())
+        debug!(
+            "Waiting for bridge to be available. Channel status = {}",
+            has_channel
+        );
+        tokio::time::sleep(Duration::from_secs(10)).await;
+    }
+    let mut messages_relay =
+        SubstrateMessagesRelay::new(sub, PagedClient::new(eth), signer).await?;
+    if let Some(state_dir) = &state_dir {
+        messages_relay = messages_relay.with_checkpoint_store(FileCheckpointStore::new(state_dir)?);
     }
+    messages_relay.run().await?;
+    Ok(())
 }
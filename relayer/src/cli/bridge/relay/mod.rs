@@ -30,13 +30,15 @@
 
 // TODO #167: fix clippy warnings
 #![allow(clippy::all)]
-mod evm;
+pub(crate) mod evm;
 mod liberland;
 mod parachain;
-mod sora;
-mod ton;
+pub(crate) mod sora;
+pub(crate) mod ton;
 
 use crate::cli::prelude::*;
+use crate::relay::beefy_syncer::BeefySyncer;
+use crate::substrate::OtherParams;
 use clap::*;
 
 #[derive(Debug, Subcommand)]
@@ -69,3 +71,40 @@ impl Commands {
         }
     }
 }
+
+/// Runs BEEFY relay together with messages relay which waits for its commitments.
+pub(crate) async fn run_beefy_relay<S, R>(
+    sender: SubUnsignedClient<S>,
+    receiver: SubSignedClient<R>,
+    send_unneeded_commitments: bool,
+    subscribe_justifications: bool,
+    pipeline_window: usize,
+) -> AnyResult<()>
+where
+    S: SenderConfig,
+    R: ReceiverConfig,
+    OtherParams<R>: Default,
+{
+    let syncer = BeefySyncer::new();
+    let beefy_relay = crate::relay::parachain::RelayBuilder::new()
+        .with_sender_client(sender.clone())
+        .with_receiver_client(receiver.clone())
+        .with_syncer(syncer.clone())
+        .with_justification_subscription(subscribe_justifications)
+        .build()
+        .await
+        .context("build beefy relay")?;
+    let messages_relay = crate::relay::parachain_messages::RelayBuilder::new()
+        .with_sender_client(sender)
+        .with_receiver_client(receiver.unsigned())
+        .with_syncer(syncer)
+        .with_pipeline_window(pipeline_window)
+        .build()
+        .await
+        .context("build messages relay")?;
+    tokio::try_join!(
+        beefy_relay.run(!send_unneeded_commitments),
+        messages_relay.run()
+    )?;
+    Ok(())
+}
@@ -39,6 +39,8 @@ pub(crate) struct Command {
     para: ParachainClient,
     #[clap(long)]
     signer: String,
+    #[clap(flatten)]
+    pipeline: PipelineArgs,
 }
 
 impl Command {
@@ -50,6 +52,7 @@ impl Command {
             .with_sender_client(sender)
             .with_receiver_client(receiver)
             .with_signer(signer)
+            .with_pipeline_window(self.pipeline.window())
             .build()
             .await
             .context("build sora to sora relay")?;
@@ -28,8 +28,8 @@
 // STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
 // USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
+use crate::cli::bridge::relay::run_beefy_relay;
 use crate::cli::prelude::*;
-use crate::relay::parachain::RelayBuilder;
 
 #[derive(Args, Clone, Debug)]
 pub(crate) struct Command {
@@ -40,31 +40,24 @@ pub(crate) struct Command {
     /// Send all Beefy commitments
     #[clap(short, long)]
     send_unneeded_commitments: bool,
+    /// Receive BEEFY justifications from `beefy_subscribeJustifications` instead of scanning blocks
+    #[clap(long)]
+    subscribe_justifications: bool,
+    #[clap(flatten)]
+    pipeline: PipelineArgs,
 }
 
 impl Command {
     pub(super) async fn run(&self) -> AnyResult<()> {
         let sender = self.para.get_unsigned_substrate().await?;
         let receiver = self.sub.get_signed_substrate().await?;
-        let syncer = crate::relay::beefy_syncer::BeefySyncer::new();
-        let beefy_relay = RelayBuilder::new()
-            .with_sender_client(sender.clone())
-            .with_receiver_client(receiver.clone())
-            .with_syncer(syncer.clone())
-            .build()
-            .await
-            .context("build sora to sora relay")?;
-        let messages_relay = crate::relay::parachain_messages::RelayBuilder::new()
-            .with_sender_client(sender)
-            .with_receiver_client(receiver.unsigned())
-            .with_syncer(syncer)
-            .build()
-            .await
-            .context("build sora to sora relay")?;
-        tokio::try_join!(
-            beefy_relay.run(!self.send_unneeded_commitments),
-            messages_relay.run()
-        )?;
-        Ok(())
+        run_beefy_relay(
+            sender,
+            receiver,
+            self.send_unneeded_commitments,
+            self.subscribe_justifications,
+            self.pipeline.window(),
+        )
+        .await
     }
 }
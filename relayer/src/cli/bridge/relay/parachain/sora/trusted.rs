@@ -41,6 +41,8 @@ pub(crate) struct Command {
     para: ParachainClient,
     #[clap(long)]
     signer: String,
+    #[clap(flatten)]
+    pipeline: PipelineArgs,
 }
 
 impl Command {
@@ -52,6 +54,7 @@ impl Command {
             .with_sender_client(sender)
             .with_receiver_client(receiver)
             .with_signer(signer)
+            .with_pipeline_window(self.pipeline.window())
             .build()
             .await
             .context("build sora to sora relay")?;
@@ -29,6 +29,8 @@
 // USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
 use crate::cli::prelude::*;
+use crate::ethereum::fees::FeeStrategy;
+use crate::relay::evm::profitability::ProfitabilityPolicy;
 use std::time::Duration;
 
 #[derive(Args, Clone, Debug)]
@@ -40,6 +42,10 @@ pub(crate) struct Command {
     /// Signer for bridge messages
     #[clap(long)]
     signer: Option<String>,
+    #[clap(flatten)]
+    fees: EvmFeeArgs,
+    #[clap(flatten)]
+    profitability: ProfitabilityArgs,
 }
 
 impl Command {
@@ -51,30 +57,49 @@ impl Command {
         } else {
             None
         };
-        let network_id = either::for_both!(&eth, e => e.chainid().await.context("fetch chain id")?);
-        let channel_address = loop {
-            let channel_address = sub
-                .storage_fetch(
-                    &runtime::storage()
-                        .bridge_inbound_channel()
-                        .evm_channel_addresses(&network_id),
-                    (),
-                )
-                .await?;
-            if let Some(channel_address) = channel_address {
-                break channel_address;
-            }
-            debug!("Waiting for bridge to be available");
-            tokio::time::sleep(Duration::from_secs(10)).await;
-        };
-        let messages_relay = crate::relay::evm::sub_messages::RelayBuilder::new()
-            .with_channel_contract(channel_address)
-            .with_receiver_client(eth)
-            .with_sender_client(sub)
-            .with_signer(signer)
-            .build()
-            .await?;
-        messages_relay.run().await?;
-        Ok(())
+        run_relay(
+            sub,
+            eth,
+            signer,
+            self.fees.fee_strategy()?,
+            self.profitability.policy(),
+        )
+        .await
     }
 }
+
+/// Waits for the EVM channel to be registered on SORA and relays messages to it.
+pub(crate) async fn run_relay(
+    sub: SubUnsignedClient<MainnetConfig>,
+    eth: EthUnsignedOrSignedClient,
+    signer: Option<sp_core::ecdsa::Pair>,
+    fees: FeeStrategy,
+    profitability: ProfitabilityPolicy,
+) -> AnyResult<()> {
+    let network_id = either::for_both!(&eth, e => e.chainid().await.context("fetch chain id")?);
+    let channel_address = loop {
+        let channel_address = sub
+            .storage_fetch(
+                &runtime::storage()
+                    .bridge_inbound_channel()
+                    .evm_channel_addresses(&network_id),
+                (),
+            )
+            .await?;
+        if let Some(channel_address) = channel_address {
+            break channel_address;
+        }
+        debug!("Waiting for bridge to be available");
+        tokio::time::sleep(Duration::from_secs(10)).await;
+    };
+    let channel = crate::relay::evm::sub_messages::EvmChannel::new(eth, channel_address, fees);
+    let messages_relay = crate::relay::evm::sub_messages::RelayBuilder::new()
+        .with_receiver_client(channel)
+        .with_sender_client(sub)
+        .with_signer(signer)
+        .with_profitability_policy(profitability)
+        .build()
+        .await?;
+    messages_relay.run().await?;
+    Ok(())
+}
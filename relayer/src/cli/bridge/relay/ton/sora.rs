@@ -48,35 +48,44 @@ impl Command {
         let ton = self.ton.get_unsigned_ton()?;
         let sub = self.sub.get_unsigned_substrate().await?;
         let signer = sp_core::ecdsa::Pair::from_string(&self.signer, None)?;
-        let Some((network_id, _app)) = sub
-            .storage_fetch(&runtime::storage().jetton_app().app_info(), ())
-            .await?
-        else {
-            return Err(anyhow!("Bridge app not registered"));
-        };
-        let Some(channel_address) = sub
-            .storage_fetch(
-                &runtime::storage()
-                    .bridge_inbound_channel()
-                    .ton_channel_addresses(network_id),
-                (),
-            )
-            .await?
-        else {
-            return Err(anyhow!("Bridge channel not registered"));
-        };
-        let relay = crate::relay::ton::ton_messages::RelayBuilder::new()
-            .with_sub_client(sub)
-            .with_ton_client(ton)
-            .with_channel(MsgAddress {
-                workchain_id: channel_address.workchain.into(),
-                address: channel_address.address.0,
-            })
-            .with_ton_network_id(network_id)
-            .with_signer(signer)
-            .build()
-            .await?;
-        relay.run().await?;
-        Ok(())
+        run_relay(sub, ton, signer).await
     }
 }
+
+/// Looks up the TON channel registered on SORA and relays messages from it.
+pub(crate) async fn run_relay(
+    sub: SubUnsignedClient<MainnetConfig>,
+    ton: crate::ton::TonClient,
+    signer: sp_core::ecdsa::Pair,
+) -> AnyResult<()> {
+    let Some((network_id, _app)) = sub
+        .storage_fetch(&runtime::storage().jetton_app().app_info(), ())
+        .await?
+    else {
+        return Err(anyhow!("Bridge app not registered"));
+    };
+    let Some(channel_address) = sub
+        .storage_fetch(
+            &runtime::storage()
+                .bridge_inbound_channel()
+                .ton_channel_addresses(network_id),
+            (),
+        )
+        .await?
+    else {
+        return Err(anyhow!("Bridge channel not registered"));
+    };
+    let relay = crate::relay::ton::ton_messages::RelayBuilder::new()
+        .with_sub_client(sub)
+        .with_ton_client(ton)
+        .with_channel(MsgAddress {
+            workchain_id: channel_address.workchain.into(),
+            address: channel_address.address.0,
+        })
+        .with_ton_network_id(network_id)
+        .with_signer(signer)
+        .build()
+        .await?;
+    relay.run().await?;
+    Ok(())
+}
@@ -0,0 +1,643 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serde::Deserialize;
+use sp_core::ecdsa;
+
+use super::relay;
+use crate::cli::error::CliError;
+use crate::cli::prelude::*;
+use crate::substrate::traits::KeyPair;
+use crate::substrate::OtherParams;
+
+/// Delay before the first restart of a failed relay.
+const MIN_RESTART_DELAY: Duration = Duration::from_secs(5);
+/// Upper bound for the restart delay. A relay which ran longer than this
+/// before failing is restarted with `MIN_RESTART_DELAY` again.
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(300);
+
+type RelayFactory = Box<dyn Fn() -> BoxFuture<'static, AnyResult<()>> + Send + Sync>;
+
+#[derive(Args, Clone, Debug)]
+pub(crate) struct Command {
+    /// Path to relayer config file in TOML format
+    #[clap(long)]
+    config: PathBuf,
+}
+
+impl Command {
+    pub(super) async fn run(&self) -> AnyResult<()> {
+        let config = RelayerConfig::load(&self.config)?;
+        if config.relays.is_empty() {
+            return Err(anyhow!("No relays configured in {:?}", self.config));
+        }
+        if let Some(path) = &config.history_path {
+            crate::history::set_path(path);
+        }
+        let clients = Clients::connect(&config).await?;
+        let mut relays = vec![];
+        for (index, relay) in config.relays.iter().enumerate() {
+            let name = format!("{}#{}", relay.kind(), index);
+            let factory = relay
+                .factory(&clients, &config.state_dir)
+                .with_context(|| format!("configure relay {}", name))?;
+            relays.push(supervise(name, factory));
+        }
+        futures::future::join_all(relays).await;
+        Ok(())
+    }
+}
+
+/// Runs relay in a separate task and restarts it with exponential backoff
+/// when it fails or panics.
+async fn supervise(name: String, factory: RelayFactory) {
+    let mut delay = MIN_RESTART_DELAY;
+    loop {
+        info!("Starting relay {}", name);
+        let started = Instant::now();
+        match tokio::spawn(factory()).await {
+            Ok(Ok(())) => warn!("Relay {} stopped", name),
+            Ok(Err(err)) => error!("Relay {} failed: {:?}", name, err),
+            Err(err) => error!("Relay {} panicked: {}", name, err),
+        }
+        if started.elapsed() > MAX_RESTART_DELAY {
+            delay = MIN_RESTART_DELAY;
+        }
+        warn!("Restarting relay {} in {:?}", name, delay);
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RESTART_DELAY);
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+struct RelayerConfig {
+    /// Directory for relay state, same as `--state-dir`
+    state_dir: Option<PathBuf>,
+    /// Journal of submitted approvals and commitments, same as `--history-path`
+    history_path: Option<PathBuf>,
+    sora: Option<SubstrateChain>,
+    parachain: Option<SubstrateChain>,
+    liberland: Option<SubstrateChain>,
+    evm: Option<EvmChain>,
+    ton: Option<TonChain>,
+    #[serde(default, rename = "relay")]
+    relays: Vec<RelayConfig>,
+}
+
+impl RelayerConfig {
+    fn load(path: &Path) -> AnyResult<Self> {
+        let config = std::fs::read_to_string(path)
+            .with_context(|| format!("read config file {:?}", path))?;
+        let config =
+            toml::from_str(&config).with_context(|| format!("parse config file {:?}", path))?;
+        Ok(config)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+struct SubstrateChain {
+    url: Urls,
+    key: Option<String>,
+    key_file: Option<PathBuf>,
+}
+
+impl SubstrateChain {
+    async fn connect<T: ConfigExt>(
+        &self,
+    ) -> AnyResult<(SubUnsignedClient<T>, Option<SubSignedClient<T>>)> {
+        let unsigned = SubUnsignedClient::<T>::with_endpoints(self.url.to_vec()).await?;
+        let signed = match read_key(&self.key, &self.key_file)? {
+            Some(key) => Some(
+                unsigned
+                    .clone()
+                    .signed(subxt::tx::PairSigner::new(
+                        KeyPair::from_string(&key, None)
+                            .map_err(|e| anyhow!("Invalid key: {:?}", e))?,
+                    ))
+                    .await?,
+            ),
+            None => None,
+        };
+        Ok((unsigned, signed))
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+struct EvmChain {
+    url: Urls,
+    key: Option<String>,
+    key_file: Option<PathBuf>,
+    gas_metrics_path: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+struct TonChain {
+    url: Urls,
+    api_key: Option<String>,
+}
+
+/// Single endpoint or list of redundant endpoints.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+enum Urls {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Urls {
+    fn to_vec(&self) -> Vec<String> {
+        match self {
+            Self::One(url) => vec![url.clone()],
+            Self::Many(urls) => urls.clone(),
+        }
+    }
+
+    fn parse(&self) -> AnyResult<Vec<Url>> {
+        Ok(self
+            .to_vec()
+            .iter()
+            .map(|url| Url::parse(url))
+            .collect::<Result<_, _>>()?)
+    }
+}
+
+fn read_key(key: &Option<String>, key_file: &Option<PathBuf>) -> AnyResult<Option<String>> {
+    match (key, key_file) {
+        (Some(_), Some(_)) => Err(CliError::BothKeyTypesProvided.into()),
+        (None, None) => Ok(None),
+        (Some(key), _) => Ok(Some(key.clone())),
+        (_, Some(key_file)) => Ok(Some(std::fs::read_to_string(key_file)?)),
+    }
+}
+
+/// Clients for every configured chain. Relays working with the same chain
+/// share a single connection.
+#[derive(Clone, Default)]
+struct Clients {
+    sora: Option<(
+        SubUnsignedClient<MainnetConfig>,
+        Option<SubSignedClient<MainnetConfig>>,
+    )>,
+    parachain: Option<(
+        SubUnsignedClient<ParachainConfig>,
+        Option<SubSignedClient<ParachainConfig>>,
+    )>,
+    liberland: Option<(
+        SubUnsignedClient<LiberlandConfig>,
+        Option<SubSignedClient<LiberlandConfig>>,
+    )>,
+    evm: Option<(EthUnsignedClient, Option<EthSignedClient>)>,
+    ton: Option<crate::ton::TonClient>,
+}
+
+impl Clients {
+    async fn connect(config: &RelayerConfig) -> AnyResult<Self> {
+        let mut clients = Self::default();
+        if let Some(sora) = &config.sora {
+            clients.sora = Some(sora.connect().await.context("connect to sora")?);
+        }
+        if let Some(parachain) = &config.parachain {
+            clients.parachain = Some(parachain.connect().await.context("connect to parachain")?);
+        }
+        if let Some(liberland) = &config.liberland {
+            clients.liberland = Some(liberland.connect().await.context("connect to liberland")?);
+        }
+        if let Some(evm) = &config.evm {
+            let unsigned = EthUnsignedClient::with_endpoints(evm.url.parse()?)
+                .await
+                .context("connect to evm")?;
+            let signed = match read_key(&evm.key, &evm.key_file)? {
+                Some(key) => Some(
+                    unsigned
+                        .sign_with_string(&key, evm.gas_metrics_path.clone())
+                        .await?,
+                ),
+                None => None,
+            };
+            clients.evm = Some((unsigned, signed));
+        }
+        if let Some(ton) = &config.ton {
+            clients.ton = Some(crate::ton::TonClient::with_endpoints(
+                ton.url.parse()?,
+                ton.api_key.clone(),
+            )?);
+        }
+        Ok(clients)
+    }
+
+    fn sora(&self) -> AnyResult<SubUnsignedClient<MainnetConfig>> {
+        let (client, _) = self
+            .sora
+            .as_ref()
+            .ok_or(anyhow!("Missing [sora] section"))?;
+        Ok(client.clone())
+    }
+
+    fn sora_signed(&self) -> AnyResult<SubSignedClient<MainnetConfig>> {
+        let (_, client) = self
+            .sora
+            .as_ref()
+            .ok_or(anyhow!("Missing [sora] section"))?;
+        client.clone().ok_or(CliError::SubstrateKey.into())
+    }
+
+    fn parachain(&self) -> AnyResult<SubUnsignedClient<ParachainConfig>> {
+        let (client, _) = self
+            .parachain
+            .as_ref()
+            .ok_or(anyhow!("Missing [parachain] section"))?;
+        Ok(client.clone())
+    }
+
+    fn parachain_signed(&self) -> AnyResult<SubSignedClient<ParachainConfig>> {
+        let (_, client) = self
+            .parachain
+            .as_ref()
+            .ok_or(anyhow!("Missing [parachain] section"))?;
+        client.clone().ok_or(CliError::ParachainKey.into())
+    }
+
+    fn liberland(&self) -> AnyResult<SubUnsignedClient<LiberlandConfig>> {
+        let (client, _) = self
+            .liberland
+            .as_ref()
+            .ok_or(anyhow!("Missing [liberland] section"))?;
+        Ok(client.clone())
+    }
+
+    fn evm(&self) -> AnyResult<EthUnsignedClient> {
+        let (client, _) = self.evm.as_ref().ok_or(anyhow!("Missing [evm] section"))?;
+        Ok(client.clone())
+    }
+
+    fn evm_any(&self) -> AnyResult<EthUnsignedOrSignedClient> {
+        let (unsigned, signed) = self.evm.as_ref().ok_or(anyhow!("Missing [evm] section"))?;
+        match signed {
+            Some(signed) => Ok(Either::Right(signed.clone())),
+            None => Ok(Either::Left(unsigned.clone())),
+        }
+    }
+
+    fn ton(&self) -> AnyResult<crate::ton::TonClient> {
+        self.ton.clone().ok_or(anyhow!("Missing [ton] section"))
+    }
+}
+
+/// Relay entry of the config file, names follow `bridge relay` subcommands.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+enum RelayConfig {
+    EvmSora {
+        signer: String,
+    },
+    SoraEvm {
+        signer: Option<String>,
+        #[serde(default)]
+        fees: EvmFeeArgs,
+        #[serde(default)]
+        profitability: ProfitabilityArgs,
+    },
+    SoraParachainBeefy {
+        #[serde(default)]
+        send_unneeded_commitments: bool,
+        #[serde(default)]
+        subscribe_justifications: bool,
+        #[serde(default)]
+        pipeline: PipelineArgs,
+    },
+    SoraParachainTrusted {
+        signer: String,
+        #[serde(default)]
+        pipeline: PipelineArgs,
+    },
+    ParachainSoraBeefy {
+        #[serde(default)]
+        send_unneeded_commitments: bool,
+        #[serde(default)]
+        subscribe_justifications: bool,
+        #[serde(default)]
+        pipeline: PipelineArgs,
+    },
+    ParachainSoraTrusted {
+        signer: String,
+        #[serde(default)]
+        pipeline: PipelineArgs,
+    },
+    SoraLiberlandTrusted {
+        signer: String,
+        #[serde(default)]
+        pipeline: PipelineArgs,
+    },
+    LiberlandSoraTrusted {
+        signer: String,
+        #[serde(default)]
+        pipeline: PipelineArgs,
+    },
+    TonSora {
+        signer: String,
+    },
+}
+
+impl RelayConfig {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::EvmSora { .. } => "evm-sora",
+            Self::SoraEvm { .. } => "sora-evm",
+            Self::SoraParachainBeefy { .. } => "sora-parachain-beefy",
+            Self::SoraParachainTrusted { .. } => "sora-parachain-trusted",
+            Self::ParachainSoraBeefy { .. } => "parachain-sora-beefy",
+            Self::ParachainSoraTrusted { .. } => "parachain-sora-trusted",
+            Self::SoraLiberlandTrusted { .. } => "sora-liberland-trusted",
+            Self::LiberlandSoraTrusted { .. } => "liberland-sora-trusted",
+            Self::TonSora { .. } => "ton-sora",
+        }
+    }
+
+    /// Checks that all required clients are configured and returns function
+    /// which starts the relay.
+    fn factory(&self, clients: &Clients, state_dir: &Option<PathBuf>) -> AnyResult<RelayFactory> {
+        let factory: RelayFactory = match self {
+            Self::EvmSora { signer } => {
+                let sub = clients.sora()?;
+                let eth = clients.evm()?;
+                let signer = ecdsa::Pair::from_string(signer, None)?;
+                let state_dir = state_dir.clone();
+                Box::new(move || {
+                    relay::evm::sora::run_relay(
+                        sub.clone(),
+                        eth.clone(),
+                        signer.clone(),
+                        state_dir.clone(),
+                    )
+                    .boxed()
+                })
+            }
+            Self::SoraEvm {
+                signer,
+                fees,
+                profitability,
+            } => {
+                let sub = clients.sora()?;
+                let eth = clients.evm_any()?;
+                let signer = match signer {
+                    Some(signer) => Some(ecdsa::Pair::from_string(signer, None)?),
+                    None => None,
+                };
+                let fees = fees.fee_strategy()?;
+                let profitability = profitability.policy();
+                Box::new(move || {
+                    relay::sora::evm::run_relay(
+                        sub.clone(),
+                        eth.clone(),
+                        signer.clone(),
+                        fees.clone(),
+                        profitability,
+                    )
+                    .boxed()
+                })
+            }
+            Self::SoraParachainBeefy {
+                send_unneeded_commitments,
+                subscribe_justifications,
+                pipeline,
+            } => {
+                let sender = clients.sora()?;
+                let receiver = clients.parachain_signed()?;
+                let send_unneeded_commitments = *send_unneeded_commitments;
+                let subscribe_justifications = *subscribe_justifications;
+                let pipeline_window = pipeline.window();
+                Box::new(move || {
+                    relay::run_beefy_relay(
+                        sender.clone(),
+                        receiver.clone(),
+                        send_unneeded_commitments,
+                        subscribe_justifications,
+                        pipeline_window,
+                    )
+                    .boxed()
+                })
+            }
+            Self::SoraParachainTrusted { signer, pipeline } => {
+                let sender = clients.sora()?;
+                let receiver = clients.parachain()?;
+                let signer = ecdsa::Pair::from_string(signer, None)?;
+                let pipeline_window = pipeline.window();
+                Box::new(move || {
+                    run_trusted_relay(
+                        sender.clone(),
+                        receiver.clone(),
+                        signer.clone(),
+                        pipeline_window,
+                    )
+                    .boxed()
+                })
+            }
+            Self::ParachainSoraBeefy {
+                send_unneeded_commitments,
+                subscribe_justifications,
+                pipeline,
+            } => {
+                let sender = clients.parachain()?;
+                let receiver = clients.sora_signed()?;
+                let send_unneeded_commitments = *send_unneeded_commitments;
+                let subscribe_justifications = *subscribe_justifications;
+                let pipeline_window = pipeline.window();
+                Box::new(move || {
+                    relay::run_beefy_relay(
+                        sender.clone(),
+                        receiver.clone(),
+                        send_unneeded_commitments,
+                        subscribe_justifications,
+                        pipeline_window,
+                    )
+                    .boxed()
+                })
+            }
+            Self::ParachainSoraTrusted { signer, pipeline } => {
+                let sender = clients.parachain()?;
+                let receiver = clients.sora()?;
+                let signer = ecdsa::Pair::from_string(signer, None)?;
+                let pipeline_window = pipeline.window();
+                Box::new(move || {
+                    run_trusted_relay(
+                        sender.clone(),
+                        receiver.clone(),
+                        signer.clone(),
+                        pipeline_window,
+                    )
+                    .boxed()
+                })
+            }
+            Self::SoraLiberlandTrusted { signer, pipeline } => {
+                let sender = clients.sora()?;
+                let receiver = clients.liberland()?;
+                let signer = ecdsa::Pair::from_string(signer, None)?;
+                let pipeline_window = pipeline.window();
+                Box::new(move || {
+                    run_trusted_relay(
+                        sender.clone(),
+                        receiver.clone(),
+                        signer.clone(),
+                        pipeline_window,
+                    )
+                    .boxed()
+                })
+            }
+            Self::LiberlandSoraTrusted { signer, pipeline } => {
+                let sender = clients.liberland()?;
+                let receiver = clients.sora()?;
+                let signer = ecdsa::Pair::from_string(signer, None)?;
+                let pipeline_window = pipeline.window();
+                Box::new(move || {
+                    run_trusted_relay(
+                        sender.clone(),
+                        receiver.clone(),
+                        signer.clone(),
+                        pipeline_window,
+                    )
+                    .boxed()
+                })
+            }
+            Self::TonSora { signer } => {
+                let sub = clients.sora()?;
+                let ton = clients.ton()?;
+                let signer = ecdsa::Pair::from_string(signer, None)?;
+                Box::new(move || {
+                    relay::ton::sora::run_relay(sub.clone(), ton.clone(), signer.clone()).boxed()
+                })
+            }
+        };
+        Ok(factory)
+    }
+}
+
+async fn run_trusted_relay<S, R>(
+    sender: SubUnsignedClient<S>,
+    receiver: SubUnsignedClient<R>,
+    signer: ecdsa::Pair,
+    pipeline_window: usize,
+) -> AnyResult<()>
+where
+    S: SenderConfig,
+    R: ReceiverConfig,
+    OtherParams<R>: Default,
+    OtherParams<S>: Default,
+{
+    let messages_relay = crate::relay::multisig_messages::RelayBuilder::new()
+        .with_sender_client(sender)
+        .with_receiver_client(receiver)
+        .with_signer(signer)
+        .with_pipeline_window(pipeline_window)
+        .build()
+        .await
+        .context("build trusted relay")?;
+    messages_relay.run().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Config examples from the `toml` code blocks of the README.
+    fn readme_configs() -> Vec<RelayerConfig> {
+        include_str!("../../../../README.md")
+            .split("```toml\n")
+            .skip(1)
+            .map(|block| {
+                let (config, _) = block.split_once("```").expect("code block is closed");
+                toml::from_str(config).expect("README config is valid")
+            })
+            .collect()
+    }
+
+    fn kinds(config: &RelayerConfig) -> Vec<&'static str> {
+        config.relays.iter().map(RelayConfig::kind).collect()
+    }
+
+    #[test]
+    fn parses_readme_config() {
+        let configs = readme_configs();
+        let config = &configs[0];
+        assert_eq!(
+            kinds(config),
+            vec![
+                "sora-liberland-trusted",
+                "liberland-sora-trusted",
+                "evm-sora"
+            ]
+        );
+        assert_eq!(config.state_dir, Some(PathBuf::from("/var/lib/relayer")));
+        let sora = config.sora.as_ref().unwrap();
+        assert_eq!(sora.url.to_vec(), vec!["ws://localhost:9944".to_string()]);
+        assert_eq!(sora.key.as_deref(), Some("//Alice"));
+        assert!(config.liberland.is_some());
+        assert!(config.parachain.is_none());
+        let evm = config.evm.as_ref().unwrap();
+        assert_eq!(evm.key_file, Some(PathBuf::from("/etc/relayer/evm.key")));
+    }
+
+    #[test]
+    fn parses_readme_relay_examples() {
+        let configs = readme_configs();
+        assert!(configs.len() > 1);
+        let relays = configs[1..].iter().flat_map(kinds).collect::<Vec<_>>();
+        assert_eq!(relays, vec!["sora-evm", "sora-parachain-beefy"]);
+        let RelayConfig::SoraParachainBeefy { pipeline, .. } = &configs[2].relays[0] else {
+            panic!("sora-parachain-beefy relay is expected");
+        };
+        assert_eq!(pipeline.window(), 8);
+    }
+
+    #[test]
+    fn parses_endpoint_list() {
+        let config: RelayerConfig =
+            toml::from_str("[sora]\nurl = [\"ws://node1:9944\", \"ws://node2:9944\"]\n").unwrap();
+        assert_eq!(
+            config.sora.unwrap().url.to_vec(),
+            vec!["ws://node1:9944".to_string(), "ws://node2:9944".to_string()]
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_relay_type() {
+        let res = toml::from_str::<RelayerConfig>("[[relay]]\ntype = \"sora-mars\"\n");
+        assert!(res.is_err());
+    }
+}
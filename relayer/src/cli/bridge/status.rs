@@ -0,0 +1,481 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use bridge_types::{EVMChainId, GenericNetworkId};
+use sp_runtime::traits::Keccak256;
+use toner::ton::MsgAddress;
+
+use crate::cli::prelude::*;
+use crate::relay::evm::batch::message_to_sign;
+use crate::relay::traits::{
+    peer_approvals, ApprovalStore, InboundChannelReader, OutboundChannelReader,
+};
+use crate::substrate::UnboundedGenericCommitment;
+use crate::ton::types::StackEntry;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub(crate) enum Format {
+    #[default]
+    Table,
+    Json,
+}
+
+#[derive(Args, Clone, Debug)]
+pub(crate) struct Command {
+    #[clap(flatten)]
+    sub: SubstrateClient,
+    #[clap(flatten)]
+    para: ParachainClient,
+    #[clap(flatten)]
+    liberland: LiberlandClient,
+    #[clap(flatten)]
+    eth: EvmClient,
+    #[clap(flatten)]
+    ton: TonClientCli,
+    #[clap(long, value_enum, default_value_t)]
+    format: Format,
+}
+
+/// Next commitment from SORA which is not yet accepted by the network.
+#[derive(Serialize, Debug)]
+struct PendingCommitment {
+    nonce: u64,
+    approvals: usize,
+    threshold: u32,
+}
+
+/// State of the channels between SORA and the network. Nonces which can be
+/// read only from the network are known when its endpoint is provided.
+/// If the state can't be read, only the network and the error are set.
+#[derive(Serialize, Debug)]
+struct NetworkStatus {
+    network: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// Latest commitment sent from SORA to the network
+    sora_outbound_nonce: Option<u64>,
+    /// Latest commitment from SORA accepted by the network
+    network_inbound_nonce: Option<u64>,
+    /// Latest commitment sent from the network to SORA
+    network_outbound_nonce: Option<u64>,
+    /// Latest commitment from the network accepted by SORA
+    sora_inbound_nonce: Option<u64>,
+    /// Latest network channel nonce reported to SORA
+    reported_nonce: Option<u64>,
+    /// Commitments from SORA are approved by bridge peers, not proven by
+    /// BEEFY light client
+    multisig: bool,
+    /// Approvals of the next commitment, set for multisig channels only
+    pending: Option<PendingCommitment>,
+    peers: Vec<String>,
+    /// Latest SORA BEEFY block known to the network light client
+    sora_beefy_block: Option<u64>,
+    /// Latest network BEEFY block known to the SORA light client
+    network_beefy_block: Option<u64>,
+}
+
+impl NetworkStatus {
+    fn new(network: String) -> Self {
+        Self {
+            network,
+            error: None,
+            sora_outbound_nonce: None,
+            network_inbound_nonce: None,
+            network_outbound_nonce: None,
+            sora_inbound_nonce: None,
+            reported_nonce: None,
+            multisig: true,
+            pending: None,
+            peers: vec![],
+            sora_beefy_block: None,
+            network_beefy_block: None,
+        }
+    }
+
+    fn failed(network: String, err: anyhow::Error) -> Self {
+        Self {
+            error: Some(format!("{:#}", err)),
+            ..Self::new(network)
+        }
+    }
+
+    fn or_failed(network: String, status: AnyResult<NetworkStatus>) -> Self {
+        status.unwrap_or_else(|err| Self::failed(network, err))
+    }
+}
+
+/// Decodes chain id from the full storage key of `EVMChannelAddresses` map.
+/// Chain id can be read from the key only if the map uses identity or concat
+/// hasher, so the key is checked to be 32 bytes of prefix, 0, 8 or 16 bytes
+/// of hash and 32 bytes of chain id.
+fn evm_chain_id_from_key(key: &[u8]) -> AnyResult<EVMChainId> {
+    const PREFIX_LEN: usize = 32;
+    const CHAIN_ID_LEN: usize = 32;
+    let hash_len = key.len().checked_sub(PREFIX_LEN + CHAIN_ID_LEN);
+    if !matches!(hash_len, Some(0 | 8 | 16)) {
+        return Err(anyhow!(
+            "Unexpected EVM channel addresses key 0x{}, chain id is expected to be hashed with identity or concat hasher",
+            hex::encode(key)
+        ));
+    }
+    Ok(EVMChainId::decode(&mut &key[key.len() - CHAIN_ID_LEN..])?)
+}
+
+impl Command {
+    pub(super) async fn run(&self) -> AnyResult<()> {
+        let sub = self.sub.get_unsigned_substrate().await?;
+        let mut statuses = vec![];
+        match self.evm_networks(&sub).await {
+            Ok(chain_ids) => {
+                for chain_id in chain_ids {
+                    let network = format!("{:?}", GenericNetworkId::EVM(chain_id));
+                    statuses.push(NetworkStatus::or_failed(
+                        network,
+                        self.evm_status(&sub, chain_id).await,
+                    ));
+                }
+            }
+            Err(err) => statuses.push(NetworkStatus::failed("EVM".to_string(), err)),
+        }
+        match self.ton_status(&sub).await {
+            Ok(Some(status)) => statuses.push(status),
+            Ok(None) => {}
+            Err(err) => statuses.push(NetworkStatus::failed("TON".to_string(), err)),
+        }
+        if self.para.is_configured() {
+            let status = match self.para.get_unsigned_substrate().await {
+                Ok(para) => substrate_status(&sub, &para, true).await,
+                Err(err) => Err(err),
+            };
+            statuses.push(NetworkStatus::or_failed("Parachain".to_string(), status));
+        }
+        if self.liberland.is_configured() {
+            let status = match self.liberland.get_unsigned_substrate().await {
+                Ok(liberland) => substrate_status(&sub, &liberland, false).await,
+                Err(err) => Err(err),
+            };
+            statuses.push(NetworkStatus::or_failed("Liberland".to_string(), status));
+        }
+        match self.format {
+            Format::Table => print_table(&statuses),
+            Format::Json => println!("{}", serde_json::to_string_pretty(&statuses)?),
+        }
+        Ok(())
+    }
+
+    /// EVM networks with channel registered on SORA.
+    async fn evm_networks(
+        &self,
+        sub: &SubUnsignedClient<MainnetConfig>,
+    ) -> AnyResult<Vec<EVMChainId>> {
+        let keys = sub
+            .storage_keys(
+                &runtime::storage()
+                    .bridge_inbound_channel()
+                    .evm_channel_addresses_root(),
+            )
+            .await?;
+        keys.iter().map(|key| evm_chain_id_from_key(key)).collect()
+    }
+
+    async fn evm_status(
+        &self,
+        sub: &SubUnsignedClient<MainnetConfig>,
+        chain_id: EVMChainId,
+    ) -> AnyResult<NetworkStatus> {
+        let network_id = GenericNetworkId::EVM(chain_id);
+        let mut status = sora_status(sub, network_id).await?;
+        status.reported_nonce = Some(reported_nonce(sub, network_id).await?);
+        if self.eth.is_configured() {
+            let eth = self.eth.get_unsigned_evm().await?;
+            if eth.chainid().await? == chain_id {
+                let channel_address = sub
+                    .storage_fetch(
+                        &runtime::storage()
+                            .bridge_inbound_channel()
+                            .evm_channel_addresses(&chain_id),
+                        (),
+                    )
+                    .await?
+                    .ok_or(anyhow!("Channel for {:?} is not registered", network_id))?;
+                let channel = ethereum_gen::ChannelHandler::new(channel_address, eth.inner());
+                status.network_inbound_nonce = Some(channel.batch_nonce().call().await? as u64);
+                status.network_outbound_nonce = Some(channel.message_nonce().call().await? as u64);
+            }
+        }
+        status.pending = pending_commitment(sub, network_id, status.network_inbound_nonce).await?;
+        Ok(status)
+    }
+
+    async fn ton_status(
+        &self,
+        sub: &SubUnsignedClient<MainnetConfig>,
+    ) -> AnyResult<Option<NetworkStatus>> {
+        let Some((ton_network_id, _app)) = sub
+            .storage_fetch(&runtime::storage().jetton_app().app_info(), ())
+            .await?
+        else {
+            return Ok(None);
+        };
+        let Some(channel_address) = sub
+            .storage_fetch(
+                &runtime::storage()
+                    .bridge_inbound_channel()
+                    .ton_channel_addresses(ton_network_id),
+                (),
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+        let network_id = GenericNetworkId::TON(ton_network_id);
+        let mut status = sora_status(sub, network_id).await?;
+        status.reported_nonce = Some(reported_nonce(sub, network_id).await?);
+        if self.ton.is_configured() {
+            let ton = self.ton.get_unsigned_ton()?;
+            let channel = MsgAddress {
+                workchain_id: channel_address.workchain.into(),
+                address: channel_address.address.0,
+            };
+            let res = ton
+                .run_get_method(channel, "outboundNonce", vec![], None)
+                .await?;
+            if let (0, Some(StackEntry::Int(nonce))) = (res.exit_code, res.stack.first()) {
+                status.network_outbound_nonce = Some(nonce.as_u64());
+            }
+        }
+        Ok(Some(status))
+    }
+}
+
+/// Reads state of the channels which is stored on SORA.
+async fn sora_status(
+    sub: &SubUnsignedClient<MainnetConfig>,
+    network_id: GenericNetworkId,
+) -> AnyResult<NetworkStatus> {
+    let peers = sub.approval_peers(network_id).await?;
+    Ok(NetworkStatus {
+        sora_outbound_nonce: Some(sub.outbound_nonce(network_id).await?),
+        sora_inbound_nonce: Some(sub.inbound_nonce(network_id).await?),
+        peers: peers
+            .iter()
+            .map(|peer| format!("0x{}", hex::encode(peer.0)))
+            .collect(),
+        ..NetworkStatus::new(format!("{:?}", network_id))
+    })
+}
+
+async fn reported_nonce(
+    sub: &SubUnsignedClient<MainnetConfig>,
+    network_id: GenericNetworkId,
+) -> AnyResult<u64> {
+    sub.storage_fetch_or_default(
+        &runtime::storage()
+            .bridge_inbound_channel()
+            .reported_channel_nonces(&network_id),
+        (),
+    )
+    .await
+}
+
+/// Counts approvals of the first SORA commitment not accepted by the network.
+async fn pending_commitment(
+    sub: &SubUnsignedClient<MainnetConfig>,
+    network_id: GenericNetworkId,
+    inbound_nonce: Option<u64>,
+) -> AnyResult<Option<PendingCommitment>> {
+    let Some(nonce) = inbound_nonce.map(|nonce| nonce + 1) else {
+        return Ok(None);
+    };
+    if nonce > sub.outbound_nonce(network_id).await? {
+        return Ok(None);
+    }
+    let offchain_data = sub.commitment(network_id, nonce).await?;
+    let message = match (network_id, &offchain_data.commitment) {
+        (
+            GenericNetworkId::EVM(_),
+            UnboundedGenericCommitment::EVM(bridge_types::evm::Commitment::Outbound(commitment)),
+        ) => message_to_sign(
+            OutboundChannelReader::network_id(sub)?,
+            network_id,
+            commitment,
+        ),
+        (GenericNetworkId::Sub(_), commitment) => {
+            let digest = sub
+                .digest(network_id, offchain_data.block_number, commitment.hash())
+                .await?;
+            Keccak256::hash_of(&digest)
+        }
+        _ => return Ok(None),
+    };
+    let peers = sub.approval_peers(network_id).await?;
+    let approvals = peer_approvals(sub.approvals(network_id, message).await?, &peers, message)?;
+    Ok(Some(PendingCommitment {
+        nonce,
+        approvals: approvals.len(),
+        threshold: bridge_types::utils::threshold(peers.len() as u32),
+    }))
+}
+
+/// Reads state of the channels between SORA and Substrate network.
+async fn substrate_status<T>(
+    sub: &SubUnsignedClient<MainnetConfig>,
+    client: &SubUnsignedClient<T>,
+    beefy: bool,
+) -> AnyResult<NetworkStatus>
+where
+    T: SenderConfig + ReceiverConfig,
+{
+    let GenericNetworkId::Sub(sub_network_id) = OutboundChannelReader::network_id(sub)? else {
+        return Err(anyhow!("SORA is expected to be a Substrate network"));
+    };
+    let network_id = OutboundChannelReader::network_id(client)?;
+    let GenericNetworkId::Sub(client_network_id) = network_id else {
+        return Err(anyhow!(
+            "Substrate network is expected, got {:?}",
+            network_id
+        ));
+    };
+    let mut status = sora_status(sub, network_id).await?;
+    status.network_inbound_nonce = Some(
+        client
+            .storage_fetch_or_default(&T::substrate_bridge_inbound_nonce(sub_network_id), ())
+            .await?,
+    );
+    status.network_outbound_nonce = Some(client.outbound_nonce(sub_network_id.into()).await?);
+    if beefy {
+        status.sora_beefy_block = Some(
+            client
+                .storage_fetch_or_default(&T::latest_beefy_block(sub_network_id), ())
+                .await?,
+        );
+        status.network_beefy_block = Some(
+            sub.storage_fetch_or_default(&MainnetConfig::latest_beefy_block(client_network_id), ())
+                .await?,
+        );
+    }
+    // Commitments to BEEFY light client are not approved by peers
+    status.multisig = !beefy;
+    if status.multisig {
+        status.pending = pending_commitment(sub, network_id, status.network_inbound_nonce).await?;
+    }
+    Ok(status)
+}
+
+fn format_nonce(nonce: Option<u64>) -> String {
+    nonce
+        .map(|nonce| nonce.to_string())
+        .unwrap_or("-".to_string())
+}
+
+fn print_table(statuses: &[NetworkStatus]) {
+    println!(
+        "{:<16}  {:>12}  {:>12}  {:>12}  {:>12}  {:>8}  {:>14}  {:>5}  {:>12}  {:>12}",
+        "network",
+        "sora out",
+        "network in",
+        "network out",
+        "sora in",
+        "reported",
+        "approvals",
+        "peers",
+        "sora beefy",
+        "network beefy"
+    );
+    for status in statuses {
+        let approvals = match &status.pending {
+            _ if !status.multisig => "n/a".to_string(),
+            Some(pending) => format!(
+                "#{} {}/{}",
+                pending.nonce, pending.approvals, pending.threshold
+            ),
+            None => "-".to_string(),
+        };
+        println!(
+            "{:<16}  {:>12}  {:>12}  {:>12}  {:>12}  {:>8}  {:>14}  {:>5}  {:>12}  {:>12}",
+            status.network,
+            format_nonce(status.sora_outbound_nonce),
+            format_nonce(status.network_inbound_nonce),
+            format_nonce(status.network_outbound_nonce),
+            format_nonce(status.sora_inbound_nonce),
+            format_nonce(status.reported_nonce),
+            approvals,
+            status.peers.len(),
+            format_nonce(status.sora_beefy_block),
+            format_nonce(status.network_beefy_block),
+        );
+    }
+    for status in statuses {
+        match &status.error {
+            Some(err) => println!("{} error: {}", status.network, err),
+            None => println!("{} peers: {}", status.network, status.peers.join(", ")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(hash: &[u8], chain_id: EVMChainId) -> Vec<u8> {
+        let mut key = sp_core::twox_128(b"BridgeInboundChannel").to_vec();
+        key.extend(sp_core::twox_128(b"EVMChannelAddresses"));
+        key.extend(hash);
+        key.extend(chain_id.encode());
+        key
+    }
+
+    #[test]
+    fn decodes_chain_id_from_key() {
+        let chain_id = EVMChainId::from(11155111u64);
+        let encoded = chain_id.encode();
+        for hash in [
+            vec![],
+            sp_core::twox_64(&encoded).to_vec(),
+            sp_core::blake2_128(&encoded).to_vec(),
+        ] {
+            assert_eq!(
+                evm_chain_id_from_key(&key(&hash, chain_id)).unwrap(),
+                chain_id
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_hashed_chain_id() {
+        let chain_id = EVMChainId::from(1u64);
+        let mut key = key(&[], chain_id);
+        key.truncate(32);
+        key.extend(sp_core::blake2_256(&chain_id.encode()));
+        key.extend([0; 4]);
+        assert!(evm_chain_id_from_key(&key).is_err());
+        assert!(evm_chain_id_from_key(&[0; 40]).is_err());
+    }
+}
@@ -32,7 +32,9 @@ use sp_core::H160;
 use sp_runtime::AccountId32;
 
 use crate::cli::prelude::*;
+use crate::ethereum::gas::GasRecord;
 use crate::substrate::AssetId;
+use bridge_types::EVMChainId;
 
 #[derive(Args, Clone, Debug)]
 pub(crate) struct Command {
@@ -47,46 +49,22 @@ pub(crate) struct Command {
     asset_id: AssetId,
     #[clap(long)]
     amount: u128,
+    /// FAApp contract address, looked up on SORA if not provided
+    #[clap(long)]
+    app_address: Option<H160>,
+    /// Token contract address, looked up on SORA if not provided
+    #[clap(long)]
+    token_address: Option<H160>,
 }
 
 impl Command {
     pub(super) async fn run(&self) -> AnyResult<()> {
         let eth = self.eth.get_signed_evm().await?;
-        let sub = self.sub.get_unsigned_substrate().await?;
         let chain_id = eth.chainid().await?;
         debug!("Eth chain id = {}", chain_id);
-        let Some(_channel_address) = sub
-            .storage_fetch(
-                &runtime::storage()
-                    .bridge_inbound_channel()
-                    .evm_channel_addresses(chain_id),
-                (),
-            )
-            .await?
-        else {
-            return Err(anyhow!("Bridge channel not registered"));
-        };
-        let Some(app_address) = sub
-            .storage_fetch(
-                &runtime::storage()
-                    .evm_fungible_app()
-                    .app_addresses(chain_id),
-                (),
-            )
-            .await?
-        else {
-            return Err(anyhow!("Bridge app not registered"));
-        };
-        let Some(asset_address) = sub
-            .storage_fetch(
-                &runtime::storage()
-                    .evm_fungible_app()
-                    .token_addresses(chain_id, self.asset_id),
-                (),
-            )
-            .await?
-        else {
-            return Err(anyhow!("Asset not registered"));
+        let (app_address, asset_address) = match (self.app_address, self.token_address) {
+            (Some(app_address), Some(token_address)) => (app_address, token_address),
+            _ => self.lookup_addresses(chain_id).await?,
         };
         let app = ethereum_gen::fa_app::FAApp::new(app_address, eth.inner());
         let amount = if asset_address == H160::zero() {
@@ -101,11 +79,65 @@ impl Command {
         }
         info!("Static call");
         call.call().await?;
+        let estimated_gas = call.estimate_gas().await?;
+        debug!("Estimated gas: {}", estimated_gas);
+        if crate::dry_run::is_enabled() {
+            eth.dry_run(&call).await?;
+            return Ok(());
+        }
         info!("Submit transaction");
         let tx = call.send().await?;
         info!("Wait for confirmations: {:?}", tx);
         let tx = tx.confirmations(1).await?;
         info!("Result: {:?}", tx);
+        let tx = tx.ok_or(anyhow!("Transaction dropped"))?;
+        eth.save_gas_record(&GasRecord::new(
+            &call.function.name,
+            None,
+            estimated_gas,
+            &tx,
+        ));
         Ok(())
     }
+
+    /// Reads bridge app and asset addresses registered on SORA.
+    async fn lookup_addresses(&self, chain_id: EVMChainId) -> AnyResult<(H160, H160)> {
+        let sub = self.sub.get_unsigned_substrate().await?;
+        let Some(_channel_address) = sub
+            .storage_fetch(
+                &runtime::storage()
+                    .bridge_inbound_channel()
+                    .evm_channel_addresses(chain_id),
+                (),
+            )
+            .await?
+        else {
+            return Err(anyhow!("Bridge channel not registered"));
+        };
+        let app_address = match self.app_address {
+            Some(app_address) => app_address,
+            None => sub
+                .storage_fetch(
+                    &runtime::storage()
+                        .evm_fungible_app()
+                        .app_addresses(chain_id),
+                    (),
+                )
+                .await?
+                .ok_or(anyhow!("Bridge app not registered"))?,
+        };
+        let asset_address = match self.token_address {
+            Some(token_address) => token_address,
+            None => sub
+                .storage_fetch(
+                    &runtime::storage()
+                        .evm_fungible_app()
+                        .token_addresses(chain_id, self.asset_id),
+                    (),
+                )
+                .await?
+                .ok_or(anyhow!("Asset not registered"))?,
+        };
+        Ok((app_address, asset_address))
+    }
 }
@@ -0,0 +1,81 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use sp_beefy::VersionedFinalityProof;
+
+use crate::cli::prelude::*;
+use crate::relay::justification::BeefyJustification;
+
+#[derive(Args, Clone, Debug)]
+pub(crate) struct Command {
+    #[clap(flatten)]
+    sub: SubstrateClient,
+    /// SORA block with BEEFY justification
+    #[clap(long)]
+    block: u32,
+}
+
+impl Command {
+    pub async fn run(&self) -> AnyResult<()> {
+        let sub = self.sub.get_unsigned_substrate().await?;
+        let block = sub.block(self.block).await?;
+        let justification = block
+            .justifications
+            .into_iter()
+            .flatten()
+            .find_map(|(engine, justification)| (&engine == b"BEEF").then_some(justification))
+            .ok_or(anyhow!("Block {} has no BEEFY justification", self.block))?;
+        let commitment = VersionedFinalityProof::decode(&mut justification.as_slice())?;
+        let justification = BeefyJustification::create(sub, commitment, false).await?;
+        let threshold =
+            BeefyJustification::<MainnetConfig>::signatures_threshold(justification.num_validators);
+
+        println!(
+            "BEEFY commitment at block {}, validator set {}",
+            justification.commitment.block_number, justification.commitment.validator_set_id
+        );
+        println!(
+            "Signatures: {} of {} validators, threshold {}",
+            justification.signed_validators.len(),
+            justification.num_validators,
+            threshold
+        );
+        if let Some(payload) = BeefyJustification::get_payload(&justification.commitment) {
+            println!("MMR root: {:?}", payload.mmr_root);
+        }
+        println!(
+            "MMR leaf: block {}",
+            justification.leaf_proof.leaf.parent_number_and_hash.0 + 1
+        );
+        justification.verify()?;
+        println!("Justification is valid");
+        Ok(())
+    }
+}
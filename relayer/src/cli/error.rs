@@ -54,4 +54,8 @@ pub enum CliError {
     TonEndpoint,
     #[error("Provide ton key via --ton-key or --ton-key-file")]
     TonKey,
+    #[error("Provide gas metrics file via --gas-metrics-path")]
+    GasMetricsPath,
+    #[error("Provide history file via --history-path")]
+    HistoryPath,
 }
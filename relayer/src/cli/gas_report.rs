@@ -0,0 +1,113 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use super::error::CliError;
+use crate::cli::prelude::*;
+use crate::ethereum::gas::GasRecord;
+use ethers::types::U256;
+
+#[derive(Args, Clone, Debug)]
+pub(super) struct Command {
+    #[clap(from_global)]
+    gas_metrics_path: Option<PathBuf>,
+}
+
+#[derive(Default)]
+struct Summary {
+    transactions: u64,
+    estimated_gas: U256,
+    gas_used: U256,
+    cost: U256,
+}
+
+impl Summary {
+    fn add(&mut self, record: &GasRecord) {
+        self.transactions += 1;
+        self.estimated_gas = self.estimated_gas.saturating_add(record.estimated_gas);
+        self.gas_used = self
+            .gas_used
+            .saturating_add(record.gas_used.unwrap_or_default());
+        self.cost = self.cost.saturating_add(record.cost.unwrap_or_default());
+    }
+
+    fn average_gas_price(&self) -> U256 {
+        if self.gas_used.is_zero() {
+            U256::zero()
+        } else {
+            self.cost / self.gas_used
+        }
+    }
+}
+
+impl Command {
+    pub(super) async fn run(&self) -> AnyResult<()> {
+        let path = self
+            .gas_metrics_path
+            .as_ref()
+            .ok_or(CliError::GasMetricsPath)?;
+        let records = GasRecord::read_all(path)?;
+        let mut summaries = BTreeMap::<(String, String), Summary>::new();
+        let mut total = Summary::default();
+        for record in records.iter() {
+            let day = chrono::DateTime::from_timestamp(record.timestamp, 0)
+                .map(|date| date.format("%Y-%m-%d").to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+            summaries
+                .entry((day, record.function.clone()))
+                .or_default()
+                .add(record);
+            total.add(record);
+        }
+        println!(
+            "{:<10}  {:<24}  {:>6}  {:>14}  {:>14}  {:>14}  {:>22}",
+            "day", "function", "txs", "estimated gas", "gas used", "avg price gwei", "cost eth"
+        );
+        let rows = summaries
+            .iter()
+            .map(|((day, function), summary)| (day.as_str(), function.as_str(), summary))
+            .chain(std::iter::once(("total", "", &total)));
+        for (day, function, summary) in rows {
+            println!(
+                "{:<10}  {:<24}  {:>6}  {:>14}  {:>14}  {:>14}  {:>22}",
+                day,
+                function,
+                summary.transactions,
+                summary.estimated_gas,
+                summary.gas_used,
+                ethers::utils::format_units(summary.average_gas_price(), "gwei")?,
+                ethers::utils::format_ether(summary.cost),
+            );
+        }
+        Ok(())
+    }
+}
@@ -29,6 +29,7 @@
 // USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
 use crate::cli::prelude::*;
+use crate::ethereum::gas::GasRecord;
 use bridge_types::H160;
 
 #[derive(Args, Clone, Debug)]
@@ -39,9 +40,6 @@ pub(super) struct Command {
     /// Amount of tokens to mint
     #[clap(long, short)]
     amount: u128,
-    /// Not send transaction to Ethereum
-    #[clap(long)]
-    dry_run: bool,
     #[clap(flatten)]
     eth: EvmClient,
 }
@@ -65,12 +63,21 @@ impl Command {
             .await?;
         debug!("Check {:?}", call);
         call.call().await?;
-        eth.save_gas_price(&call, "mint-test-token").await?;
-        if !self.dry_run {
-            debug!("Send");
-            let tx = call.send().await?.confirmations(3).await?.unwrap();
-            debug!("Tx: {:?}", tx);
+        let estimated_gas = call.estimate_gas().await?;
+        debug!("Estimated gas: {}", estimated_gas);
+        if crate::dry_run::is_enabled() {
+            eth.dry_run(&call).await?;
+            return Ok(());
         }
+        debug!("Send");
+        let tx = call.send().await?.confirmations(3).await?.unwrap();
+        debug!("Tx: {:?}", tx);
+        eth.save_gas_record(&GasRecord::new(
+            &call.function.name,
+            None,
+            estimated_gas,
+            &tx,
+        ));
         Ok(())
     }
 }
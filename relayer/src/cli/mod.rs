@@ -31,11 +31,13 @@
 mod bridge;
 mod copy_liquidity;
 mod error;
+mod gas_report;
 mod mint_test_token;
 mod old_bridge;
 mod subscribe_beefy;
 pub mod utils;
 
+use std::net::SocketAddr;
 use std::path::PathBuf;
 
 pub use utils::*;
@@ -59,57 +61,83 @@ pub struct Cli {
     /// File with Substrate account derive URI
     #[clap(long, global = true)]
     substrate_key_file: Option<String>,
-    /// Substrate node endpoint
-    #[clap(long, global = true)]
-    substrate_url: Option<String>,
+    /// Substrate node endpoint, repeat or separate with commas to fail over between endpoints
+    #[clap(long, global = true, value_delimiter = ',')]
+    substrate_url: Vec<String>,
     /// Parachain account derive URI
     #[clap(long, global = true)]
     parachain_key: Option<String>,
     /// File with Parachain account derive URI
     #[clap(long, global = true)]
     parachain_key_file: Option<String>,
-    /// Parachain node endpoint
-    #[clap(long, global = true)]
-    parachain_url: Option<String>,
+    /// Parachain node endpoint, repeat or separate with commas to fail over between endpoints
+    #[clap(long, global = true, value_delimiter = ',')]
+    parachain_url: Vec<String>,
     /// Liberland account derive URI
     #[clap(long, global = true)]
     liberland_key: Option<String>,
     /// File with Liberland account derive URI
     #[clap(long, global = true)]
     liberland_key_file: Option<String>,
-    /// Liberland node endpoint
-    #[clap(long, global = true)]
-    liberland_url: Option<String>,
+    /// Liberland node endpoint, repeat or separate with commas to fail over between endpoints
+    #[clap(long, global = true, value_delimiter = ',')]
+    liberland_url: Vec<String>,
     /// EVM private key
     #[clap(long, global = true)]
     evm_key: Option<String>,
     /// File with EVM private key
     #[clap(long, global = true)]
     evm_key_file: Option<String>,
-    /// EVM node endpoint
-    #[clap(long, global = true)]
-    evm_url: Option<Url>,
+    /// EVM node endpoint, repeat or separate with commas to fail over between endpoints
+    #[clap(long, global = true, value_delimiter = ',')]
+    evm_url: Vec<Url>,
     /// TON mnemonic
     #[clap(long, global = true)]
     ton_key: Option<String>,
     /// File with TON mnemonic
     #[clap(long, global = true)]
     ton_key_file: Option<String>,
-    /// TON HTTP API Url
-    #[clap(long, global = true)]
-    ton_url: Option<Url>,
+    /// TON HTTP API Url, repeat or separate with commas to fail over between endpoints
+    #[clap(long, global = true, value_delimiter = ',')]
+    ton_url: Vec<Url>,
     /// TON HTTP API Key
     #[clap(long, global = true)]
     ton_api_key: Option<String>,
-    /// Path for gas estimations
+    /// File to append JSON lines with gas spent by sent EVM transactions
     #[clap(long, global = true)]
     gas_metrics_path: Option<PathBuf>,
+    /// File to append JSON lines with approvals and commitments submitted by this relayer
+    #[clap(long, global = true)]
+    history_path: Option<PathBuf>,
+    /// Directory for relayer state (processed block checkpoints)
+    #[clap(long, global = true)]
+    state_dir: Option<PathBuf>,
+    /// Address to serve Prometheus metrics and health endpoints on
+    #[clap(long, global = true)]
+    metrics_addr: Option<SocketAddr>,
+    /// Seconds without relay loop progress after which relayer is reported as not alive
+    #[clap(long, global = true, default_value_t = crate::health::DEFAULT_LIVENESS_TIMEOUT.as_secs())]
+    liveness_timeout: u64,
+    /// Build, validate and log transactions without broadcasting them
+    #[clap(long, global = true)]
+    dry_run: bool,
     #[clap(subcommand)]
     commands: Commands,
 }
 
 impl Cli {
     pub async fn run(&self) -> AnyResult<()> {
+        crate::health::set_liveness_timeout(std::time::Duration::from_secs(self.liveness_timeout));
+        if let Some(path) = &self.history_path {
+            crate::history::set_path(path);
+        }
+        if self.dry_run {
+            info!("Dry run enabled, transactions will not be sent");
+            crate::dry_run::enable();
+        }
+        if let Some(addr) = self.metrics_addr {
+            crate::metrics::serve(addr)?;
+        }
         self.commands.run().await
     }
 }
@@ -120,6 +148,8 @@ enum Commands {
     SubscribeBeefy(subscribe_beefy::Command),
     /// Mint test token (work for tokens with mint method)
     MintTestToken(mint_test_token::Command),
+    /// Summarise gas spent by EVM transactions per function and per day
+    GasReport(gas_report::Command),
     /// Operations with bridge
     #[clap(subcommand)]
     Bridge(bridge::Commands),
@@ -134,6 +164,7 @@ impl Commands {
         match self {
             Self::SubscribeBeefy(cmd) => cmd.run().await,
             Self::MintTestToken(cmd) => cmd.run().await,
+            Self::GasReport(cmd) => cmd.run().await,
             Self::Bridge(cmd) => cmd.run().await,
             Self::OldBridge(cmd) => cmd.run().await,
             Self::CopyLiquidity(cmd) => cmd.run().await,
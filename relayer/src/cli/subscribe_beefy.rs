@@ -35,15 +35,22 @@ use futures::StreamExt;
 pub(super) struct Command {
     #[clap(flatten)]
     sub: SubstrateClient,
+    /// Receive BEEFY justifications from `beefy_subscribeJustifications` instead of scanning blocks
+    #[clap(long)]
+    subscribe_justifications: bool,
 }
 
 impl Command {
     pub(super) async fn run(&self) -> AnyResult<()> {
         let sub = self.sub.get_unsigned_substrate().await?;
 
-        let mut stream =
-            crate::substrate::beefy_subscription::subscribe_beefy_justifications(sub.clone(), 1)
-                .await?;
+        let mut stream = crate::substrate::beefy_subscription::subscribe_beefy_justifications(
+            sub.clone(),
+            1,
+            self.subscribe_justifications,
+            Default::default(),
+        )
+        .await?;
         while let Some(justification) = stream.next().await {
             println!("Justification: {:?}", justification);
         }
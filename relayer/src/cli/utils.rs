@@ -31,8 +31,11 @@
 use std::path::PathBuf;
 
 use super::error::*;
+use crate::ethereum::fees::FeeStrategy;
+use crate::relay::evm::profitability::ProfitabilityPolicy;
 use crate::{prelude::*, substrate::traits::KeyPair};
 use bridge_types::ton::TonNetworkId;
+use bridge_types::{GenericNetworkId, SubNetworkId};
 use clap::*;
 use sp_core::{crypto::Ss58Codec, H160};
 
@@ -43,7 +46,7 @@ pub struct SubstrateClient {
     #[clap(from_global)]
     substrate_key_file: Option<String>,
     #[clap(from_global)]
-    substrate_url: Option<String>,
+    substrate_url: Vec<String>,
 }
 
 impl SubstrateClient {
@@ -56,15 +59,15 @@ impl SubstrateClient {
         }
     }
 
-    pub fn get_url(&self) -> AnyResult<String> {
-        Ok(self
-            .substrate_url
-            .clone()
-            .ok_or(CliError::SubstrateEndpoint)?)
+    pub fn get_urls(&self) -> AnyResult<Vec<String>> {
+        if self.substrate_url.is_empty() {
+            return Err(CliError::SubstrateEndpoint.into());
+        }
+        Ok(self.substrate_url.clone())
     }
 
     pub async fn get_unsigned_substrate(&self) -> AnyResult<SubUnsignedClient<MainnetConfig>> {
-        let sub = SubUnsignedClient::new(self.get_url()?).await?;
+        let sub = SubUnsignedClient::with_endpoints(self.get_urls()?).await?;
         Ok(sub)
     }
 
@@ -88,10 +91,15 @@ pub struct ParachainClient {
     #[clap(from_global)]
     parachain_key_file: Option<String>,
     #[clap(from_global)]
-    parachain_url: Option<String>,
+    parachain_url: Vec<String>,
 }
 
 impl ParachainClient {
+    /// Returns true if endpoint is provided.
+    pub fn is_configured(&self) -> bool {
+        !self.parachain_url.is_empty()
+    }
+
     pub fn get_key_string(&self) -> AnyResult<String> {
         match (&self.parachain_key, &self.parachain_key_file) {
             (Some(_), Some(_)) => Err(CliError::BothKeyTypesProvided.into()),
@@ -101,15 +109,15 @@ impl ParachainClient {
         }
     }
 
-    pub fn get_url(&self) -> AnyResult<String> {
-        Ok(self
-            .parachain_url
-            .clone()
-            .ok_or(CliError::ParachainEndpoint)?)
+    pub fn get_urls(&self) -> AnyResult<Vec<String>> {
+        if self.parachain_url.is_empty() {
+            return Err(CliError::ParachainEndpoint.into());
+        }
+        Ok(self.parachain_url.clone())
     }
 
     pub async fn get_unsigned_substrate(&self) -> AnyResult<SubUnsignedClient<ParachainConfig>> {
-        let sub = SubUnsignedClient::new(self.get_url()?).await?;
+        let sub = SubUnsignedClient::with_endpoints(self.get_urls()?).await?;
         Ok(sub)
     }
 
@@ -133,12 +141,17 @@ pub struct EvmClient {
     #[clap(from_global)]
     evm_key_file: Option<String>,
     #[clap(from_global)]
-    evm_url: Option<Url>,
+    evm_url: Vec<Url>,
     #[clap(from_global)]
     gas_metrics_path: Option<PathBuf>,
 }
 
 impl EvmClient {
+    /// Returns true if endpoint is provided.
+    pub fn is_configured(&self) -> bool {
+        !self.evm_url.is_empty()
+    }
+
     pub fn get_key_string(&self) -> AnyResult<String> {
         match (&self.evm_key, &self.evm_key_file) {
             (Some(_), Some(_)) => Err(CliError::BothKeyTypesProvided.into()),
@@ -148,12 +161,15 @@ impl EvmClient {
         }
     }
 
-    pub fn get_url(&self) -> AnyResult<Url> {
-        Ok(self.evm_url.clone().ok_or(CliError::EvmEndpoint)?)
+    pub fn get_urls(&self) -> AnyResult<Vec<Url>> {
+        if self.evm_url.is_empty() {
+            return Err(CliError::EvmEndpoint.into());
+        }
+        Ok(self.evm_url.clone())
     }
 
     pub async fn get_unsigned_evm(&self) -> AnyResult<EthUnsignedClient> {
-        let eth = EthUnsignedClient::new(self.get_url()?).await?;
+        let eth = EthUnsignedClient::with_endpoints(self.get_urls()?).await?;
         Ok(eth)
     }
 
@@ -178,6 +194,108 @@ impl EvmClient {
     }
 }
 
+/// Fee settings for transactions sent to EVM network.
+#[derive(Args, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct EvmFeeArgs {
+    /// Send legacy transactions instead of EIP-1559 ones
+    #[clap(long)]
+    legacy_tx: bool,
+    /// Max fee per gas in gwei (gas price for legacy transactions)
+    #[clap(long)]
+    max_fee_per_gas: Option<String>,
+    /// Max priority fee per gas in gwei
+    #[clap(long)]
+    max_priority_fee_per_gas: Option<String>,
+    /// Wait instead of sending while network gas price in gwei is higher
+    #[clap(long)]
+    gas_price_ceiling: Option<String>,
+    /// Replace transaction with higher fees if it is not included after this number of blocks [default: 5]
+    #[clap(long)]
+    replace_after_blocks: Option<u64>,
+    /// Fee increase in percent for replacement transactions [default: 20]
+    #[clap(long)]
+    fee_bump_percent: Option<u64>,
+    /// Maximum number of replacements of one transaction [default: 5]
+    #[clap(long)]
+    max_fee_bumps: Option<u32>,
+}
+
+impl EvmFeeArgs {
+    fn parse_gwei(value: &Option<String>) -> AnyResult<Option<ethers::types::U256>> {
+        match value {
+            Some(value) => Ok(Some(ethers::utils::parse_units(value, "gwei")?.into())),
+            None => Ok(None),
+        }
+    }
+
+    pub fn fee_strategy(&self) -> AnyResult<FeeStrategy> {
+        let default = FeeStrategy::default();
+        Ok(FeeStrategy {
+            legacy: self.legacy_tx,
+            max_fee_per_gas: Self::parse_gwei(&self.max_fee_per_gas)?,
+            max_priority_fee_per_gas: Self::parse_gwei(&self.max_priority_fee_per_gas)?,
+            gas_price_ceiling: Self::parse_gwei(&self.gas_price_ceiling)?,
+            replace_after_blocks: self
+                .replace_after_blocks
+                .unwrap_or(default.replace_after_blocks),
+            bump_percent: self.fee_bump_percent.unwrap_or(default.bump_percent),
+            max_bumps: self.max_fee_bumps.unwrap_or(default.max_bumps),
+        })
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProfitabilitySelector {
+    /// Send every batch
+    #[default]
+    Always,
+    /// Send batch only when collected fees cover its cost
+    Profitable,
+    /// Send unprofitable batch after `--max-batch-delay`
+    MaxDelay,
+}
+
+/// Profitability check for batches sent to EVM network.
+#[derive(Args, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProfitabilityArgs {
+    /// Policy for batches which cost more than fees collected for them
+    #[clap(long, value_enum, default_value_t)]
+    profitability: ProfitabilitySelector,
+    /// Max delay in seconds for unprofitable batches with `max-delay` policy [default: 3600]
+    #[clap(long)]
+    max_batch_delay: Option<u64>,
+}
+
+impl ProfitabilityArgs {
+    pub fn policy(&self) -> ProfitabilityPolicy {
+        match self.profitability {
+            ProfitabilitySelector::Always => ProfitabilityPolicy::Always,
+            ProfitabilitySelector::Profitable => ProfitabilityPolicy::Profitable,
+            ProfitabilitySelector::MaxDelay => ProfitabilityPolicy::MaxDelay(
+                std::time::Duration::from_secs(self.max_batch_delay.unwrap_or(3600)),
+            ),
+        }
+    }
+}
+
+/// Pipelining of commitments relayed to Substrate networks.
+#[derive(Args, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct PipelineArgs {
+    /// Number of commitments prepared (proofs loaded and approvals sent) ahead and kept in the transaction pool at the same time [default: 1]
+    #[clap(long)]
+    pipeline_window: Option<usize>,
+}
+
+impl PipelineArgs {
+    pub fn window(&self) -> usize {
+        self.pipeline_window.unwrap_or(1).max(1)
+    }
+}
+
 #[derive(Args, Debug, Clone)]
 pub struct LiberlandClient {
     #[clap(from_global)]
@@ -185,10 +303,15 @@ pub struct LiberlandClient {
     #[clap(from_global)]
     liberland_key_file: Option<String>,
     #[clap(from_global)]
-    liberland_url: Option<String>,
+    liberland_url: Vec<String>,
 }
 
 impl LiberlandClient {
+    /// Returns true if endpoint is provided.
+    pub fn is_configured(&self) -> bool {
+        !self.liberland_url.is_empty()
+    }
+
     pub fn get_key_string(&self) -> AnyResult<String> {
         match (&self.liberland_key, &self.liberland_key_file) {
             (Some(_), Some(_)) => Err(CliError::BothKeyTypesProvided.into()),
@@ -198,15 +321,15 @@ impl LiberlandClient {
         }
     }
 
-    pub fn get_url(&self) -> AnyResult<String> {
-        Ok(self
-            .liberland_url
-            .clone()
-            .ok_or(CliError::LiberlandEndpoint)?)
+    pub fn get_urls(&self) -> AnyResult<Vec<String>> {
+        if self.liberland_url.is_empty() {
+            return Err(CliError::LiberlandEndpoint.into());
+        }
+        Ok(self.liberland_url.clone())
     }
 
     pub async fn get_unsigned_substrate(&self) -> AnyResult<SubUnsignedClient<LiberlandConfig>> {
-        let sub = SubUnsignedClient::new(self.get_url()?).await?;
+        let sub = SubUnsignedClient::with_endpoints(self.get_urls()?).await?;
         Ok(sub)
     }
 
@@ -261,12 +384,17 @@ pub struct TonClientCli {
     #[clap(from_global)]
     ton_key_file: Option<String>,
     #[clap(from_global)]
-    ton_url: Option<Url>,
+    ton_url: Vec<Url>,
     #[clap(from_global)]
     ton_api_key: Option<String>,
 }
 
 impl TonClientCli {
+    /// Returns true if endpoint is provided.
+    pub fn is_configured(&self) -> bool {
+        !self.ton_url.is_empty()
+    }
+
     pub fn get_key_string(&self) -> AnyResult<String> {
         match (&self.ton_key, &self.ton_key_file) {
             (Some(_), Some(_)) => Err(CliError::BothKeyTypesProvided.into()),
@@ -276,12 +404,16 @@ impl TonClientCli {
         }
     }
 
-    pub fn get_url(&self) -> AnyResult<Url> {
-        Ok(self.ton_url.clone().ok_or(CliError::TonEndpoint)?)
+    pub fn get_urls(&self) -> AnyResult<Vec<Url>> {
+        if self.ton_url.is_empty() {
+            return Err(CliError::TonEndpoint.into());
+        }
+        Ok(self.ton_url.clone())
     }
 
     pub fn get_unsigned_ton(&self) -> AnyResult<crate::ton::TonClient> {
-        let client = crate::ton::TonClient::new(self.get_url()?, self.ton_api_key.clone())?;
+        let client =
+            crate::ton::TonClient::with_endpoints(self.get_urls()?, self.ton_api_key.clone())?;
         Ok(client)
     }
 
@@ -306,3 +438,66 @@ impl TonNetworkSelector {
         }
     }
 }
+
+/// Parses network id in form `evm:<chain id>`, `sub:<network>` or `ton:<network>`,
+/// e.g. `evm:1`, `sub:kusama` or `ton:mainnet`.
+pub fn parse_network_id(s: &str) -> Result<GenericNetworkId, String> {
+    let (kind, network) = s
+        .split_once(':')
+        .ok_or(format!("Expected <kind>:<network>, got {s}"))?;
+    let network_id = match (
+        kind.to_lowercase().as_str(),
+        network.to_lowercase().as_str(),
+    ) {
+        ("evm", chain_id) => GenericNetworkId::EVM(
+            chain_id
+                .parse::<u64>()
+                .map_err(|e| format!("Invalid chain id {chain_id}: {e}"))?
+                .into(),
+        ),
+        ("sub", "mainnet") => GenericNetworkId::Sub(SubNetworkId::Mainnet),
+        ("sub", "kusama") => GenericNetworkId::Sub(SubNetworkId::Kusama),
+        ("sub", "polkadot") => GenericNetworkId::Sub(SubNetworkId::Polkadot),
+        ("sub", "rococo") => GenericNetworkId::Sub(SubNetworkId::Rococo),
+        ("sub", "alphanet") => GenericNetworkId::Sub(SubNetworkId::Alphanet),
+        ("sub", "liberland") => GenericNetworkId::Sub(SubNetworkId::Liberland),
+        ("ton", "mainnet") => GenericNetworkId::TON(TonNetworkId::Mainnet),
+        ("ton", "testnet") => GenericNetworkId::TON(TonNetworkId::Testnet),
+        _ => return Err(format!("Unknown network {s}")),
+    };
+    Ok(network_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_network_id() {
+        assert_eq!(
+            parse_network_id("evm:11155111"),
+            Ok(GenericNetworkId::EVM(11155111u64.into()))
+        );
+        assert_eq!(
+            parse_network_id("sub:kusama"),
+            Ok(GenericNetworkId::Sub(SubNetworkId::Kusama))
+        );
+        assert_eq!(
+            parse_network_id("SUB:Liberland"),
+            Ok(GenericNetworkId::Sub(SubNetworkId::Liberland))
+        );
+        assert_eq!(
+            parse_network_id("ton:testnet"),
+            Ok(GenericNetworkId::TON(TonNetworkId::Testnet))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_network_id() {
+        assert!(parse_network_id("kusama").is_err());
+        assert!(parse_network_id("evm:").is_err());
+        assert!(parse_network_id("evm:0x1").is_err());
+        assert!(parse_network_id("sub:unknown").is_err());
+        assert!(parse_network_id("btc:mainnet").is_err());
+    }
+}
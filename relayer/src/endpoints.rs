@@ -0,0 +1,223 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::fmt::Display;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::prelude::*;
+
+/// Interval between endpoint health checks.
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// Number of blocks the active endpoint may lag behind the best one before
+/// requests are switched to another endpoint.
+pub const MAX_ENDPOINT_LAG: u64 = 10;
+
+/// Redundant endpoints of one chain with the one currently used for requests.
+#[derive(Debug)]
+pub struct Endpoints<U> {
+    urls: Vec<U>,
+    active: AtomicUsize,
+}
+
+impl<U: Display> Endpoints<U> {
+    pub fn new(urls: Vec<U>) -> AnyResult<Self> {
+        if urls.is_empty() {
+            return Err(anyhow!("No endpoints provided"));
+        }
+        Ok(Self {
+            urls,
+            active: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.urls.len()
+    }
+
+    pub fn url(&self, index: usize) -> &U {
+        &self.urls[index]
+    }
+
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    pub fn set_active(&self, index: usize) {
+        let prev = self.active.swap(index, Ordering::SeqCst);
+        if prev != index {
+            warn!(
+                "Switching endpoint from {} to {}",
+                self.urls[prev], self.urls[index]
+            );
+        }
+    }
+
+    /// Endpoint indexes in the order they should be tried, starting with the active one.
+    pub fn failover_order(&self) -> impl Iterator<Item = usize> {
+        let active = self.active();
+        let len = self.len();
+        (0..len).map(move |i| (active + i) % len)
+    }
+
+    /// Switches to the endpoint with the highest block if the active one is
+    /// unavailable or lags behind it more than `MAX_ENDPOINT_LAG` blocks.
+    pub fn select(&self, heights: &[Option<u64>]) {
+        let Some((best, best_height)) = heights
+            .iter()
+            .enumerate()
+            .filter_map(|(index, height)| height.map(|height| (index, height)))
+            .max_by_key(|(_, height)| *height)
+        else {
+            warn!("All endpoints are unavailable");
+            return;
+        };
+        let active = self.active();
+        match heights[active] {
+            Some(height) if height + MAX_ENDPOINT_LAG >= best_height => {}
+            Some(height) => {
+                warn!(
+                    "Endpoint {} lags behind {}: {} < {}",
+                    self.urls[active], self.urls[best], height, best_height
+                );
+                self.set_active(best);
+            }
+            None => {
+                warn!("Endpoint {} is unavailable", self.urls[active]);
+                self.set_active(best);
+            }
+        }
+    }
+}
+
+/// Client which is able to check each of its endpoints.
+#[async_trait::async_trait]
+pub trait EndpointsHealth: Send + Sync + 'static {
+    type Url: Display + Send + Sync;
+
+    fn endpoints(&self) -> &Endpoints<Self::Url>;
+
+    /// Returns latest finalized block number known to the endpoint.
+    async fn finalized_height(&self, index: usize) -> AnyResult<u64>;
+}
+
+/// Periodically checks all endpoints of the client and switches the active
+/// one. The check stops when the client is dropped.
+pub fn spawn_health_check<C: EndpointsHealth>(client: &Arc<C>) {
+    if client.endpoints().len() < 2 {
+        return;
+    }
+    let client = Arc::downgrade(client);
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(HEALTH_CHECK_INTERVAL).await;
+            let Some(client) = client.upgrade() else {
+                return;
+            };
+            let endpoints = client.endpoints();
+            let heights = futures::future::join_all(
+                (0..endpoints.len()).map(|index| client.finalized_height(index)),
+            )
+            .await
+            .into_iter()
+            .enumerate()
+            .map(|(index, height)| match height {
+                Ok(height) => Some(height),
+                Err(err) => {
+                    debug!("Health check of {} failed: {:?}", endpoints.url(index), err);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+            endpoints.select(&heights);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn endpoints(active: usize) -> Endpoints<&'static str> {
+        let endpoints = Endpoints::new(vec!["a", "b", "c"]).unwrap();
+        endpoints.set_active(active);
+        endpoints
+    }
+
+    #[test]
+    fn requires_endpoints() {
+        assert!(Endpoints::<&str>::new(vec![]).is_err());
+    }
+
+    #[test]
+    fn failover_order_starts_with_active_endpoint() {
+        assert_eq!(
+            endpoints(0).failover_order().collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        assert_eq!(
+            endpoints(1).failover_order().collect::<Vec<_>>(),
+            vec![1, 2, 0]
+        );
+        assert_eq!(
+            endpoints(2).failover_order().collect::<Vec<_>>(),
+            vec![2, 0, 1]
+        );
+    }
+
+    #[test]
+    fn keeps_active_endpoint_within_lag() {
+        let endpoints = endpoints(0);
+        endpoints.select(&[Some(100), Some(100 + MAX_ENDPOINT_LAG), None]);
+        assert_eq!(endpoints.active(), 0);
+    }
+
+    #[test]
+    fn switches_from_lagging_endpoint() {
+        let endpoints = endpoints(0);
+        endpoints.select(&[Some(100), Some(100 + MAX_ENDPOINT_LAG + 1), Some(105)]);
+        assert_eq!(endpoints.active(), 1);
+    }
+
+    #[test]
+    fn switches_from_unavailable_endpoint() {
+        let endpoints = endpoints(1);
+        endpoints.select(&[Some(90), None, Some(100)]);
+        assert_eq!(endpoints.active(), 2);
+    }
+
+    #[test]
+    fn keeps_active_endpoint_if_all_are_unavailable() {
+        let endpoints = endpoints(1);
+        endpoints.select(&[None, None, None]);
+        assert_eq!(endpoints.active(), 1);
+    }
+}
@@ -0,0 +1,497 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::time::Duration;
+
+use super::SignedClientInner;
+use crate::prelude::*;
+use ethers::abi::Detokenize;
+use ethers::contract::ContractCall;
+use ethers::providers::Middleware;
+use ethers::signers::Signer;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{
+    BlockNumber, Bytes, Eip1559TransactionRequest, TransactionReceipt, H256, U256,
+};
+
+/// Interval between checks of network fees and sent transaction status.
+const POLL_INTERVAL: Duration = Duration::from_secs(6);
+
+pub const DEFAULT_REPLACE_AFTER_BLOCKS: u64 = 5;
+pub const DEFAULT_FEE_BUMP_PERCENT: u64 = 20;
+pub const DEFAULT_MAX_FEE_BUMPS: u32 = 5;
+
+/// Number of consecutive checks in which the node doesn't know any of the
+/// sent transactions before they are considered dropped. Load balanced RPCs
+/// could return nothing for transactions which are still in the mempool.
+const MAX_UNKNOWN_CHECKS: u32 = 3;
+
+/// How fees are chosen for transactions sent by relays.
+#[derive(Clone, Debug)]
+pub struct FeeStrategy {
+    /// Send legacy transactions even if network supports EIP-1559
+    pub legacy: bool,
+    /// Cap for max fee per gas, or for gas price of legacy transactions
+    pub max_fee_per_gas: Option<U256>,
+    /// Cap for max priority fee per gas
+    pub max_priority_fee_per_gas: Option<U256>,
+    /// Network gas price above which relay waits instead of sending
+    pub gas_price_ceiling: Option<U256>,
+    /// Number of blocks after which not included transaction is replaced
+    pub replace_after_blocks: u64,
+    /// Fee increase in percent for replacement transactions
+    pub bump_percent: u64,
+    /// Maximum number of replacements of one transaction
+    pub max_bumps: u32,
+}
+
+impl Default for FeeStrategy {
+    fn default() -> Self {
+        Self {
+            legacy: false,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            gas_price_ceiling: None,
+            replace_after_blocks: DEFAULT_REPLACE_AFTER_BLOCKS,
+            bump_percent: DEFAULT_FEE_BUMP_PERCENT,
+            max_bumps: DEFAULT_MAX_FEE_BUMPS,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Fees {
+    Legacy { gas_price: U256 },
+    Eip1559 { max_fee: U256, priority_fee: U256 },
+}
+
+fn cap(value: U256, cap: Option<U256>) -> U256 {
+    cap.map_or(value, |cap| value.min(cap))
+}
+
+impl Fees {
+    fn apply(&self, tx: &mut TypedTransaction) {
+        match *self {
+            Fees::Legacy { gas_price } => {
+                if let TypedTransaction::Eip1559(inner) = tx {
+                    *tx = TypedTransaction::Legacy(inner.clone().into());
+                }
+                tx.set_gas_price(gas_price);
+            }
+            Fees::Eip1559 {
+                max_fee,
+                priority_fee,
+            } => {
+                if !matches!(tx, TypedTransaction::Eip1559(_)) {
+                    *tx = TypedTransaction::Eip1559(Eip1559TransactionRequest::from(tx.clone()));
+                }
+                if let Some(inner) = tx.as_eip1559_mut() {
+                    inner.max_fee_per_gas = Some(max_fee);
+                    inner.max_priority_fee_per_gas = Some(priority_fee);
+                }
+            }
+        }
+    }
+
+    /// Returns increased fees for replacement transaction or `None` if they
+    /// would exceed the caps of the strategy.
+    fn bump(&self, strategy: &FeeStrategy) -> Option<Fees> {
+        let bump =
+            |value: U256| (value * (100 + strategy.bump_percent) / 100).max(value + U256::one());
+        let within = |value: U256, cap: Option<U256>| cap.map_or(true, |cap| value <= cap);
+        match *self {
+            Fees::Legacy { gas_price } => {
+                let gas_price = bump(gas_price);
+                within(gas_price, strategy.max_fee_per_gas).then_some(Fees::Legacy { gas_price })
+            }
+            Fees::Eip1559 {
+                max_fee,
+                priority_fee,
+            } => {
+                let max_fee = bump(max_fee);
+                let priority_fee = bump(priority_fee);
+                (within(max_fee, strategy.max_fee_per_gas)
+                    && within(priority_fee, strategy.max_priority_fee_per_gas))
+                .then_some(Fees::Eip1559 {
+                    max_fee,
+                    priority_fee,
+                })
+            }
+        }
+    }
+}
+
+/// Sets transaction type of the contract call, `fill_transaction` fails for
+/// EIP-1559 transactions on networks which don't support them.
+fn with_transaction_type<M: Middleware, D: Detokenize>(
+    call: ContractCall<M, D>,
+    legacy: bool,
+) -> ContractCall<M, D> {
+    if legacy {
+        call.legacy()
+    } else {
+        call
+    }
+}
+
+impl FeeStrategy {
+    /// Prepares contract call to be filled and sent with this strategy.
+    pub async fn prepare_call<M: Middleware, D: Detokenize>(
+        &self,
+        call: ContractCall<M, D>,
+    ) -> AnyResult<ContractCall<M, D>>
+    where
+        M::Error: 'static,
+    {
+        let legacy = self.legacy
+            || call
+                .client
+                .get_block(BlockNumber::Latest)
+                .await?
+                .ok_or(anyhow!("Latest block not found"))?
+                .base_fee_per_gas
+                .is_none();
+        Ok(with_transaction_type(call, legacy))
+    }
+
+    /// Waits while network gas price is above the ceiling and returns fees
+    /// for a new transaction.
+    async fn initial_fees(&self, client: &SignedClientInner) -> AnyResult<Fees> {
+        loop {
+            let block = client
+                .get_block(BlockNumber::Latest)
+                .await?
+                .ok_or(anyhow!("Latest block not found"))?;
+            let (fees, gas_price) = match block.base_fee_per_gas {
+                Some(base_fee) if !self.legacy => {
+                    let (max_fee, priority_fee) = client.estimate_eip1559_fees(None).await?;
+                    let priority_fee = cap(priority_fee, self.max_priority_fee_per_gas);
+                    let max_fee = cap(max_fee, self.max_fee_per_gas).max(priority_fee);
+                    (
+                        Fees::Eip1559 {
+                            max_fee,
+                            priority_fee,
+                        },
+                        base_fee.saturating_add(priority_fee),
+                    )
+                }
+                _ => {
+                    let gas_price = client.get_gas_price().await?;
+                    (
+                        Fees::Legacy {
+                            gas_price: cap(gas_price, self.max_fee_per_gas),
+                        },
+                        gas_price,
+                    )
+                }
+            };
+            match self.gas_price_ceiling {
+                Some(ceiling) if gas_price > ceiling => {
+                    warn!(
+                        "Gas price {} is above the ceiling {}, waiting",
+                        gas_price, ceiling
+                    );
+                    tokio::time::sleep(POLL_INTERVAL).await;
+                }
+                _ => return Ok(fees),
+            }
+        }
+    }
+
+    /// Signs transaction with given fees and sends it, returns hash and raw
+    /// signed transaction which could be broadcasted again.
+    async fn submit(
+        &self,
+        client: &SignedClientInner,
+        tx: &mut TypedTransaction,
+        fees: Fees,
+    ) -> AnyResult<(H256, Bytes)> {
+        fees.apply(tx);
+        tx.set_from(client.address());
+        tx.set_chain_id(client.signer().chain_id());
+        let signature = client.signer().sign_transaction(tx).await?;
+        let raw = tx.rlp_signed(&signature);
+        debug!("Send transaction with fees {:?}", fees);
+        let pending = client.send_raw_transaction(raw.clone()).await?;
+        Ok((pending.tx_hash(), raw))
+    }
+
+    /// Returns receipt of any of the sent transactions if it is included.
+    async fn receipt(
+        client: &SignedClientInner,
+        sent: &[H256],
+    ) -> AnyResult<Option<TransactionReceipt>> {
+        for hash in sent {
+            if let Some(receipt) = client.get_transaction_receipt(*hash).await? {
+                return Ok(Some(receipt));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Checks whether any of the sent transactions is still known by the node.
+    async fn is_known(client: &SignedClientInner, sent: &[H256]) -> AnyResult<bool> {
+        for hash in sent {
+            if client.get_transaction(*hash).await?.is_some() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Sends transaction and waits until it is included, replacing it with
+    /// bumped fees while it is stuck.
+    pub async fn send(
+        &self,
+        client: &SignedClientInner,
+        mut tx: TypedTransaction,
+    ) -> AnyResult<TransactionReceipt> {
+        let mut fees = self.initial_fees(client).await?;
+        // Nonce could change while we were waiting for acceptable fees
+        let nonce = client
+            .get_transaction_count(client.address(), Some(BlockNumber::Pending.into()))
+            .await?;
+        tx.set_nonce(nonce);
+        let (hash, mut raw) = self.submit(client, &mut tx, fees).await?;
+        let mut sent = vec![hash];
+        let mut sent_at = client.get_block_number().await?.as_u64();
+        let mut bumps = 0;
+        let mut unknown_checks = 0;
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            // Any of the sent transactions could be included
+            if let Some(receipt) = Self::receipt(client, &sent).await? {
+                return Ok(receipt);
+            }
+            let confirmed_nonce = client
+                .get_transaction_count(client.address(), Some(BlockNumber::Latest.into()))
+                .await?;
+            if confirmed_nonce > nonce {
+                // One of our transactions could be included after the receipts check
+                if let Some(receipt) = Self::receipt(client, &sent).await? {
+                    return Ok(receipt);
+                }
+                return Err(anyhow!(
+                    "Nonce {} was used by another transaction, sent transactions: {:?}",
+                    nonce,
+                    sent
+                ));
+            }
+            if Self::is_known(client, &sent).await? {
+                unknown_checks = 0;
+            } else {
+                unknown_checks += 1;
+                if unknown_checks >= MAX_UNKNOWN_CHECKS {
+                    return Err(anyhow!(
+                        "Sent transactions were dropped by the node: {:?}",
+                        sent
+                    ));
+                }
+                // The same signed transaction keeps the nonce, so it can't
+                // be included twice if the node just didn't return it
+                warn!(
+                    "Sent transaction {:?} is unknown to the node, broadcasting it again",
+                    sent.last()
+                );
+                if let Err(err) = client.send_raw_transaction(raw.clone()).await {
+                    warn!("Failed to broadcast transaction again: {:?}", err);
+                }
+                continue;
+            }
+            let block = client.get_block_number().await?.as_u64();
+            if block < sent_at + self.replace_after_blocks || bumps >= self.max_bumps {
+                continue;
+            }
+            let Some(bumped) = fees.bump(self) else {
+                debug!("Fee caps reached, waiting for {:?}", sent.last());
+                continue;
+            };
+            match self.submit(client, &mut tx, bumped).await {
+                Ok((hash, bumped_raw)) => {
+                    info!(
+                        "Replaced transaction {:?} stuck for {} blocks with {:?}",
+                        sent.last(),
+                        block - sent_at,
+                        hash
+                    );
+                    sent.push(hash);
+                    raw = bumped_raw;
+                    fees = bumped;
+                    bumps += 1;
+                    sent_at = block;
+                }
+                // Previous transaction could be included in the meantime
+                Err(err) => warn!("Failed to replace transaction: {:?}", err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethereum_gen::ChannelHandler;
+    use ethers::providers::{MockProvider, Provider};
+    use ethers::types::{Address, Block};
+    use std::sync::Arc;
+
+    fn call(provider: Provider<MockProvider>) -> ContractCall<Provider<MockProvider>, ()> {
+        let channel = ChannelHandler::new(Address::repeat_byte(1), Arc::new(provider));
+        channel.submit(Default::default(), vec![], vec![], vec![])
+    }
+
+    fn latest_block(base_fee: Option<u64>) -> Block<H256> {
+        Block {
+            number: Some(1u64.into()),
+            base_fee_per_gas: base_fee.map(U256::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sets_transaction_type() {
+        let (provider, _mock) = Provider::mocked();
+        let legacy = with_transaction_type(call(provider.clone()), true);
+        assert!(matches!(legacy.tx, TypedTransaction::Legacy(_)));
+        let eip1559 = with_transaction_type(call(provider), false);
+        assert!(matches!(eip1559.tx, TypedTransaction::Eip1559(_)));
+    }
+
+    #[tokio::test]
+    async fn prepares_legacy_call_if_configured() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(latest_block(Some(10))).unwrap();
+        let strategy = FeeStrategy {
+            legacy: true,
+            ..Default::default()
+        };
+        let call = strategy.prepare_call(call(provider)).await.unwrap();
+        assert!(matches!(call.tx, TypedTransaction::Legacy(_)));
+    }
+
+    #[tokio::test]
+    async fn prepares_legacy_call_without_base_fee() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(latest_block(None)).unwrap();
+        let call = FeeStrategy::default()
+            .prepare_call(call(provider))
+            .await
+            .unwrap();
+        assert!(matches!(call.tx, TypedTransaction::Legacy(_)));
+    }
+
+    #[tokio::test]
+    async fn prepares_eip1559_call_with_base_fee() {
+        let (provider, mock) = Provider::mocked();
+        mock.push(latest_block(Some(10))).unwrap();
+        let call = FeeStrategy::default()
+            .prepare_call(call(provider))
+            .await
+            .unwrap();
+        assert!(matches!(call.tx, TypedTransaction::Eip1559(_)));
+    }
+
+    fn strategy(max_fee: Option<u64>, max_priority_fee: Option<u64>) -> FeeStrategy {
+        FeeStrategy {
+            max_fee_per_gas: max_fee.map(U256::from),
+            max_priority_fee_per_gas: max_priority_fee.map(U256::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn caps_value() {
+        assert_eq!(cap(100.into(), None), U256::from(100));
+        assert_eq!(cap(100.into(), Some(50.into())), U256::from(50));
+        assert_eq!(cap(100.into(), Some(150.into())), U256::from(100));
+    }
+
+    #[test]
+    fn bumps_legacy_gas_price() {
+        let fees = Fees::Legacy {
+            gas_price: 100.into(),
+        };
+        let Some(Fees::Legacy { gas_price }) = fees.bump(&strategy(None, None)) else {
+            panic!("legacy fees expected");
+        };
+        assert_eq!(gas_price, U256::from(120));
+    }
+
+    #[test]
+    fn bumps_eip1559_fees() {
+        let fees = Fees::Eip1559 {
+            max_fee: 1000.into(),
+            priority_fee: 10.into(),
+        };
+        let Some(Fees::Eip1559 {
+            max_fee,
+            priority_fee,
+        }) = fees.bump(&strategy(None, None))
+        else {
+            panic!("EIP-1559 fees expected");
+        };
+        assert_eq!(max_fee, U256::from(1200));
+        assert_eq!(priority_fee, U256::from(12));
+    }
+
+    #[test]
+    fn bumps_small_fees_by_at_least_one() {
+        let fees = Fees::Eip1559 {
+            max_fee: 2.into(),
+            priority_fee: 1.into(),
+        };
+        let Some(Fees::Eip1559 {
+            max_fee,
+            priority_fee,
+        }) = fees.bump(&strategy(None, None))
+        else {
+            panic!("EIP-1559 fees expected");
+        };
+        assert_eq!(max_fee, U256::from(3));
+        assert_eq!(priority_fee, U256::from(2));
+    }
+
+    #[test]
+    fn bump_respects_caps() {
+        let legacy = Fees::Legacy {
+            gas_price: 100.into(),
+        };
+        assert!(legacy.bump(&strategy(Some(120), None)).is_some());
+        assert!(legacy.bump(&strategy(Some(119), None)).is_none());
+
+        let eip1559 = Fees::Eip1559 {
+            max_fee: 1000.into(),
+            priority_fee: 10.into(),
+        };
+        assert!(eip1559.bump(&strategy(Some(1200), Some(12))).is_some());
+        assert!(eip1559.bump(&strategy(Some(1199), None)).is_none());
+        assert!(eip1559.bump(&strategy(None, Some(11))).is_none());
+    }
+}
@@ -0,0 +1,109 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use crate::prelude::*;
+use ethers::types::{Address, TransactionReceipt, H256, U256};
+
+/// Gas spent by one transaction sent to EVM network, stored as a JSON line
+/// in the gas metrics file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GasRecord {
+    /// Unix timestamp in seconds taken by the relayer when it got the receipt, not the block timestamp
+    pub timestamp: i64,
+    pub tx_hash: H256,
+    pub to: Option<Address>,
+    pub function: String,
+    pub batch_nonce: Option<u64>,
+    pub estimated_gas: U256,
+    pub gas_used: Option<U256>,
+    pub effective_gas_price: Option<U256>,
+    /// Total cost in wei
+    pub cost: Option<U256>,
+}
+
+impl GasRecord {
+    pub fn new(
+        function: &str,
+        batch_nonce: Option<u64>,
+        estimated_gas: U256,
+        receipt: &TransactionReceipt,
+    ) -> Self {
+        let cost = receipt
+            .gas_used
+            .zip(receipt.effective_gas_price)
+            .map(|(gas_used, gas_price)| gas_used.saturating_mul(gas_price));
+        Self {
+            timestamp: chrono::Utc::now().timestamp(),
+            tx_hash: receipt.transaction_hash,
+            to: receipt.to,
+            function: function.to_string(),
+            batch_nonce,
+            estimated_gas,
+            gas_used: receipt.gas_used,
+            effective_gas_price: receipt.effective_gas_price,
+            cost,
+        }
+    }
+
+    /// Appends record to the file as a JSON line.
+    pub fn append_to(&self, path: &Path) -> AnyResult<()> {
+        let mut line = serde_json::to_string(self)?;
+        line.push('\n');
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .with_context(|| format!("open gas metrics file {}", path.display()))?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// Reads all records from the file, skipping lines which are not valid
+    /// records (e.g. written by older versions of relayer).
+    pub fn read_all(path: &Path) -> AnyResult<Vec<GasRecord>> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("open gas metrics file {}", path.display()))?;
+        let mut records = vec![];
+        for (index, line) in std::io::BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(record) => records.push(record),
+                Err(err) => warn!("Skip gas record at line {}: {}", index + 1, err),
+            }
+        }
+        Ok(records)
+    }
+}
@@ -0,0 +1,261 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+use std::borrow::Borrow;
+
+use super::UnsignedClient;
+use crate::prelude::*;
+use ethers::contract::{EthLogDecode, Event, LogMeta};
+use ethers::providers::Middleware;
+
+const MIN_PAGE_SIZE: u64 = 1;
+const DEFAULT_PAGE_SIZE: u64 = 1000;
+const DEFAULT_MAX_PAGE_SIZE: u64 = 10000;
+
+/// Substrings of RPC errors returned by providers which limit `eth_getLogs` range or result size
+const RANGE_ERRORS: &[&str] = &[
+    // Infura and geth based nodes
+    "query returned more than",
+    // Alchemy
+    "log response size exceeded",
+    // BSC
+    "exceed maximum block range",
+    // Erigon and Ankr
+    "block range is too wide",
+    // QuickNode
+    "eth_getlogs is limited to",
+];
+
+/// JSON-RPC error codes returned by providers which limit `eth_getLogs` range or result size
+const RANGE_ERROR_CODES: &[i64] = &[
+    // Infura "limit exceeded"
+    -32005,
+];
+
+/// Splits `eth_getLogs` queries into pages and adapts the page size to the provider limits.
+///
+/// Page size is halved when provider rejects the range and doubled after each successful page
+/// up to the maximum page size.
+#[derive(Clone, Debug)]
+pub struct LogsPager {
+    page_size: u64,
+    max_page_size: u64,
+}
+
+impl Default for LogsPager {
+    fn default() -> Self {
+        Self::new(DEFAULT_PAGE_SIZE, DEFAULT_MAX_PAGE_SIZE)
+    }
+}
+
+impl LogsPager {
+    pub fn new(page_size: u64, max_page_size: u64) -> Self {
+        let max_page_size = max_page_size.max(MIN_PAGE_SIZE);
+        Self {
+            page_size: page_size.clamp(MIN_PAGE_SIZE, max_page_size),
+            max_page_size,
+        }
+    }
+
+    pub fn page_size(&self) -> u64 {
+        self.page_size
+    }
+
+    pub fn is_range_error(err: &str) -> bool {
+        let err = err.to_lowercase();
+        RANGE_ERRORS.iter().any(|pattern| err.contains(pattern))
+            || RANGE_ERROR_CODES
+                .iter()
+                .any(|code| err.contains(&format!("code: {}", code)))
+    }
+
+    /// Last block of the page starting at `from`.
+    fn page_end(&self, from: u64, to_block: u64) -> u64 {
+        from.saturating_add(self.page_size - 1).min(to_block)
+    }
+
+    /// Grows page size after a successful page.
+    fn page_succeeded(&mut self) {
+        self.page_size = self.page_size.saturating_mul(2).min(self.max_page_size);
+    }
+
+    /// Halves page size if provider rejected the range. Returns false if the
+    /// error is not caused by the range or the page can't be reduced anymore.
+    fn page_failed(&mut self, err: &str) -> bool {
+        if self.page_size <= MIN_PAGE_SIZE || !Self::is_range_error(err) {
+            return false;
+        }
+        self.page_size = (self.page_size / 2).max(MIN_PAGE_SIZE);
+        true
+    }
+
+    /// Queries events from `from_block` to `to_block` inclusive.
+    pub async fn query_with_meta<B, M, D>(
+        &mut self,
+        mut event: Event<B, M, D>,
+        from_block: u64,
+        to_block: u64,
+    ) -> AnyResult<Vec<(D, LogMeta)>>
+    where
+        B: Borrow<M>,
+        M: Middleware + 'static,
+        D: EthLogDecode,
+    {
+        let mut events = vec![];
+        let mut from = from_block;
+        while from <= to_block {
+            let to = self.page_end(from, to_block);
+            event.filter = event.filter.from_block(from).to_block(to);
+            match event.query_with_meta().await {
+                Ok(page) => {
+                    trace!("Found {} logs from {} to {}", page.len(), from, to);
+                    events.extend(page);
+                    from = to + 1;
+                    self.page_succeeded();
+                }
+                Err(err) if self.page_failed(&err.to_string()) => {
+                    debug!(
+                        "Logs query from {} to {} rejected: {}, reducing page size to {}",
+                        from, to, err, self.page_size
+                    );
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(events)
+    }
+}
+
+/// EVM client which keeps its own [`LogsPager`] for the channel event queries.
+#[derive(Clone, Debug)]
+pub struct PagedClient {
+    eth: UnsignedClient,
+    logs: LogsPager,
+}
+
+impl PagedClient {
+    pub fn new(eth: UnsignedClient) -> Self {
+        Self {
+            eth,
+            logs: Default::default(),
+        }
+    }
+
+    pub fn eth(&self) -> &UnsignedClient {
+        &self.eth
+    }
+
+    pub fn logs(&mut self) -> &mut LogsPager {
+        &mut self.logs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Queries pages like [`LogsPager::query_with_meta`] from a provider which
+    /// rejects ranges longer than `limit` and returns queried ranges.
+    fn query(pager: &mut LogsPager, from_block: u64, to_block: u64, limit: u64) -> Vec<(u64, u64)> {
+        let mut pages = vec![];
+        let mut from = from_block;
+        while from <= to_block {
+            let to = pager.page_end(from, to_block);
+            if to - from + 1 > limit {
+                assert!(pager.page_failed("query returned more than 10000 results"));
+                continue;
+            }
+            pages.push((from, to));
+            from = to + 1;
+            pager.page_succeeded();
+        }
+        pages
+    }
+
+    #[test]
+    fn detects_provider_range_errors() {
+        for err in [
+            "(code: -32005, message: query returned more than 10000 results, data: None)",
+            "Log response size exceeded. You can make eth_getLogs requests with up to a 2K block range",
+            "exceed maximum block range: 5000",
+            "block range is too wide",
+            "eth_getLogs is limited to a 10,000 range",
+            "(code: -32005, message: limit exceeded, data: None)",
+        ] {
+            assert!(LogsPager::is_range_error(err), "{}", err);
+        }
+    }
+
+    #[test]
+    fn ignores_unrelated_errors() {
+        for err in [
+            "(code: -32000, message: header not found, data: None)",
+            "invalid block range params",
+            "rate limit exceeded",
+            "(code: -32602, message: invalid argument 0: hex string has odd length, data: None)",
+        ] {
+            assert!(!LogsPager::is_range_error(err), "{}", err);
+        }
+    }
+
+    #[test]
+    fn does_not_reduce_page_on_unrelated_error() {
+        let mut pager = LogsPager::new(100, 1000);
+        assert!(!pager.page_failed("header not found"));
+        assert_eq!(pager.page_size(), 100);
+    }
+
+    #[test]
+    fn halves_page_until_provider_accepts_it() {
+        let mut pager = LogsPager::new(100, 1000);
+        let pages = query(&mut pager, 1, 100, 30);
+        // Page grows after each success and is halved again when rejected
+        assert_eq!(pages, vec![(1, 25), (26, 50), (51, 75), (76, 100)]);
+        assert_eq!(pager.page_size(), 50);
+    }
+
+    #[test]
+    fn grows_page_after_success_up_to_max() {
+        let mut pager = LogsPager::new(10, 40);
+        let pages = query(&mut pager, 1, 150, 1000);
+        assert_eq!(
+            pages,
+            vec![(1, 10), (11, 30), (31, 70), (71, 110), (111, 150)]
+        );
+        assert_eq!(pager.page_size(), 40);
+    }
+
+    #[test]
+    fn page_is_not_reduced_below_minimum() {
+        let mut pager = LogsPager::new(2, 10);
+        assert!(pager.page_failed("block range is too wide"));
+        assert_eq!(pager.page_size(), MIN_PAGE_SIZE);
+        assert!(!pager.page_failed("block range is too wide"));
+    }
+}
@@ -30,9 +30,12 @@
 
 // TODO #167: fix clippy warnings
 #![allow(clippy::all)]
+pub mod fees;
+pub mod gas;
+pub mod logs;
 pub mod provider;
 
-use crate::ethereum::provider::UniversalClient;
+use crate::ethereum::provider::FailoverClient;
 use crate::prelude::*;
 use bridge_types::EVMChainId;
 pub use ethers::core::k256::ecdsa::SigningKey;
@@ -46,7 +49,7 @@ pub type EthWallet = Wallet<SigningKey>;
 
 pub type SignedClientInner = SignerMiddleware<UnsignedClientInner, EthWallet>;
 
-pub type UnsignedClientInner = Provider<UniversalClient>;
+pub type UnsignedClientInner = Provider<FailoverClient>;
 
 pub type UnsignedOrSignedClient = Either<UnsignedClient, SignedClient>;
 
@@ -62,8 +65,13 @@ impl Deref for UnsignedClient {
 
 impl UnsignedClient {
     pub async fn new(url: Url) -> AnyResult<Self> {
-        debug!("Connect to {}", url);
-        let provider = Provider::new(UniversalClient::new(url).await?);
+        Self::with_endpoints(vec![url]).await
+    }
+
+    /// Creates client which fails over between redundant endpoints.
+    pub async fn with_endpoints(urls: Vec<Url>) -> AnyResult<Self> {
+        debug!("Connect to {:?}", urls);
+        let provider = Provider::new(FailoverClient::new(urls).await?);
         Ok(Self(Arc::new(provider)))
     }
 
@@ -130,8 +138,8 @@ impl Deref for SignedClient {
 impl SignedClient {
     pub async fn new(url: Url, key: SigningKey, gas_metrics: Option<PathBuf>) -> AnyResult<Self> {
         debug!("Connect to {}", url);
-        let provider =
-            Provider::new(UniversalClient::new(url).await?).interval(Duration::from_millis(100));
+        let provider = Provider::new(FailoverClient::new(vec![url]).await?)
+            .interval(Duration::from_millis(100));
         let wallet = Wallet::from(key);
         let chain_id = provider.get_chainid().await?;
         let wallet = wallet.with_chain_id(chain_id.as_u64());
@@ -154,32 +162,42 @@ impl SignedClient {
         self.unsigned().chainid().await
     }
 
-    pub async fn save_gas_price<D, M>(
+    /// Checks the call with a static call and logs the signed transaction instead of sending it.
+    pub async fn dry_run<D: ethers::abi::Detokenize>(
         &self,
-        call: &ContractCall<M, D>,
-        additional: &str,
-    ) -> AnyResult<()>
-    where
-        D: abi::Detokenize + core::fmt::Debug,
-        M: Middleware + 'static,
-    {
-        use std::io::Write;
-        let gas = call.estimate_gas().await?.as_u128();
-        let metric = format!(
-            "{:?} {} '{}' {}\n",
-            call.tx.to(),
+        call: &ContractCall<SignedClientInner, D>,
+    ) -> AnyResult<H256> {
+        let mut tx = call.tx.clone();
+        self.inner.fill_transaction(&mut tx, call.block).await?;
+        self.inner.call(&tx, call.block).await?;
+        let signature = self.inner.signer().sign_transaction(&tx).await?;
+        let hash = H256(ethers::utils::keccak256(tx.rlp_signed(&signature)));
+        let args = tx
+            .data()
+            .and_then(|data| data.get(4..))
+            .and_then(|data| call.function.decode_input(data).ok())
+            .unwrap_or_default();
+        info!(
+            "Dry run: transaction {:?} is not sent: {}{:?} to {:?}, value {:?}, nonce {:?}, gas {:?}",
+            hash,
             call.function.name,
-            additional,
-            gas
+            args,
+            tx.to(),
+            tx.value(),
+            tx.nonce(),
+            tx.gas()
         );
-        debug!("Gas metric: {}", metric);
+        Ok(hash)
+    }
+
+    /// Stores gas spent by sent transaction to the gas metrics file if it is configured.
+    /// Write errors are only logged, since the transaction is already included.
+    pub fn save_gas_record(&self, record: &gas::GasRecord) {
+        debug!("Gas record: {:?}", record);
         if let Some(path) = &self.gas_metrics {
-            let mut file = std::fs::OpenOptions::new()
-                .append(true)
-                .create(true)
-                .open(path)?;
-            file.write_all(metric.as_bytes())?;
+            if let Err(err) = record.append_to(path) {
+                warn!("Failed to write gas record: {:?}", err);
+            }
         }
-        Ok(())
     }
 }
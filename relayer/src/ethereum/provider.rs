@@ -32,8 +32,12 @@ pub use ethers::prelude::*;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use std::fmt::Debug;
+use std::sync::Arc;
+use tokio::sync::Mutex;
 use url::Url;
 
+use crate::endpoints::{spawn_health_check, Endpoints, EndpointsHealth};
+
 #[derive(Clone, Debug)]
 pub enum UniversalClient {
     Ws(Ws),
@@ -48,6 +52,8 @@ pub enum UniversalClientError {
     Http(#[from] HttpClientError),
     #[error("Invalid scheme")]
     InvalidScheme,
+    #[error("No endpoints provided")]
+    NoEndpoints,
 }
 
 impl From<UniversalClientError> for ProviderError {
@@ -61,7 +67,7 @@ impl ethers::providers::RpcError for UniversalClientError {
         match self {
             Self::Ws(err) => err.as_error_response(),
             Self::Http(err) => err.as_error_response(),
-            Self::InvalidScheme => None,
+            Self::InvalidScheme | Self::NoEndpoints => None,
         }
     }
 
@@ -69,7 +75,7 @@ impl ethers::providers::RpcError for UniversalClientError {
         match self {
             Self::Ws(err) => err.as_serde_error(),
             Self::Http(err) => err.as_serde_error(),
-            Self::InvalidScheme => None,
+            Self::InvalidScheme | Self::NoEndpoints => None,
         }
     }
 }
@@ -104,3 +110,162 @@ impl UniversalClient {
         }
     }
 }
+
+/// Client which sends requests to one of the redundant endpoints and fails
+/// over to the next one on transport errors.
+#[derive(Clone, Debug)]
+pub struct FailoverClient(Arc<FailoverClientInner>);
+
+#[derive(Debug)]
+struct FailoverClientInner {
+    endpoints: Endpoints<Url>,
+    /// Client of each endpoint, connected on first use and dropped after a
+    /// transport error, so the next request connects again.
+    clients: Vec<Mutex<Option<Arc<UniversalClient>>>>,
+}
+
+impl FailoverClientInner {
+    fn new(endpoints: Endpoints<Url>) -> Self {
+        Self {
+            clients: (0..endpoints.len()).map(|_| Default::default()).collect(),
+            endpoints,
+        }
+    }
+
+    async fn client(&self, index: usize) -> Result<Arc<UniversalClient>, UniversalClientError> {
+        let mut slot = self.clients[index].lock().await;
+        if let Some(client) = slot.as_ref() {
+            return Ok(client.clone());
+        }
+        let client = Arc::new(UniversalClient::new(self.endpoints.url(index).clone()).await?);
+        *slot = Some(client.clone());
+        Ok(client)
+    }
+
+    /// Sends request to the endpoint and drops its client on transport error.
+    async fn request<T, R>(
+        &self,
+        index: usize,
+        method: &str,
+        params: T,
+    ) -> Result<R, UniversalClientError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let client = self.client(index).await?;
+        let res = JsonRpcClient::request(&*client, method, params).await;
+        if let Err(err) = &res {
+            if err.as_error_response().is_none() && err.as_serde_error().is_none() {
+                let mut slot = self.clients[index].lock().await;
+                // Client could be already replaced by another request
+                if slot
+                    .as_ref()
+                    .map_or(false, |slot| Arc::ptr_eq(slot, &client))
+                {
+                    *slot = None;
+                }
+            }
+        }
+        res
+    }
+}
+
+#[async_trait::async_trait]
+impl EndpointsHealth for FailoverClientInner {
+    type Url = Url;
+
+    fn endpoints(&self) -> &Endpoints<Url> {
+        &self.endpoints
+    }
+
+    async fn finalized_height(&self, index: usize) -> anyhow::Result<u64> {
+        let block: Option<Block<TxHash>> = self
+            .request(
+                index,
+                "eth_getBlockByNumber",
+                (BlockNumber::Finalized, false),
+            )
+            .await?;
+        let number = block
+            .and_then(|block| block.number)
+            .ok_or(anyhow::anyhow!("Finalized block not found"))?;
+        Ok(number.as_u64())
+    }
+}
+
+#[async_trait::async_trait]
+impl JsonRpcClient for FailoverClient {
+    type Error = UniversalClientError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        let mut last_err = UniversalClientError::NoEndpoints;
+        for index in self.0.endpoints.failover_order() {
+            match self.0.request(index, method, &params).await {
+                Ok(res) => {
+                    self.0.endpoints.set_active(index);
+                    return Ok(res);
+                }
+                // Node responded, so the endpoint itself is fine
+                Err(err) if err.as_error_response().is_some() => return Err(err),
+                Err(err) => {
+                    warn!(
+                        "Request {} to {} failed: {}",
+                        method,
+                        self.0.endpoints.url(index),
+                        err
+                    );
+                    last_err = err;
+                }
+            }
+        }
+        Err(last_err)
+    }
+}
+
+impl FailoverClient {
+    pub async fn new(urls: Vec<Url>) -> Result<Self, UniversalClientError> {
+        let endpoints = Endpoints::new(urls).map_err(|_| UniversalClientError::NoEndpoints)?;
+        let inner = Arc::new(FailoverClientInner::new(endpoints));
+        let mut last_err = UniversalClientError::NoEndpoints;
+        for index in inner.endpoints.failover_order() {
+            match inner.client(index).await {
+                Ok(_) => {
+                    inner.endpoints.set_active(index);
+                    spawn_health_check(&inner);
+                    return Ok(Self(inner));
+                }
+                Err(err) => {
+                    warn!(
+                        "Failed to connect to {}: {}",
+                        inner.endpoints.url(index),
+                        err
+                    );
+                    last_err = err;
+                }
+            }
+        }
+        Err(last_err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn drops_client_after_transport_error() {
+        let url: Url = "http://127.0.0.1:1".parse().unwrap();
+        let inner = FailoverClientInner::new(Endpoints::new(vec![url]).unwrap());
+        let client = inner.client(0).await.unwrap();
+        assert!(Arc::ptr_eq(&client, &inner.client(0).await.unwrap()));
+        let res: Result<U64, _> = inner.request(0, "eth_blockNumber", ()).await;
+        assert!(res.is_err());
+        assert!(inner.clients[0].lock().await.is_none());
+        assert!(!Arc::ptr_eq(&client, &inner.client(0).await.unwrap()));
+    }
+}
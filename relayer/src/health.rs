@@ -0,0 +1,220 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use bridge_types::GenericNetworkId;
+
+pub const DEFAULT_LIVENESS_TIMEOUT: Duration = Duration::from_secs(300);
+
+struct LoopState {
+    registered: Instant,
+    last_beat: Option<Instant>,
+}
+
+struct HealthState {
+    liveness_timeout: Duration,
+    loops: BTreeMap<String, LoopState>,
+}
+
+impl HealthState {
+    fn new(liveness_timeout: Duration) -> Self {
+        Self {
+            liveness_timeout,
+            loops: Default::default(),
+        }
+    }
+
+    fn register(&mut self, name: String, now: Instant) {
+        self.loops.insert(
+            name,
+            LoopState {
+                registered: now,
+                last_beat: None,
+            },
+        );
+    }
+
+    fn beat(&mut self, name: &str, now: Instant) {
+        if let Some(state) = self.loops.get_mut(name) {
+            state.last_beat = Some(now);
+        }
+    }
+
+    fn is_ready(&self, now: Instant) -> bool {
+        !self.loops.is_empty()
+            && self.loops.values().all(|state| {
+                state.last_beat.map_or(false, |last_beat| {
+                    now.duration_since(last_beat) <= self.liveness_timeout
+                })
+            })
+    }
+
+    fn stalled_loops(&self, now: Instant) -> Vec<(String, Duration)> {
+        self.loops
+            .iter()
+            .map(|(name, state)| {
+                let last_beat = state.last_beat.unwrap_or(state.registered);
+                (name.clone(), now.duration_since(last_beat))
+            })
+            .filter(|(_, elapsed)| *elapsed > self.liveness_timeout)
+            .collect()
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref STATE: Mutex<HealthState> = Mutex::new(HealthState::new(DEFAULT_LIVENESS_TIMEOUT));
+}
+
+pub fn set_liveness_timeout(timeout: Duration) {
+    STATE.lock().expect("poisoned").liveness_timeout = timeout;
+}
+
+/// Relayer is ready when every registered relay has completed a main loop
+/// iteration within liveness timeout.
+pub fn is_ready() -> bool {
+    STATE.lock().expect("poisoned").is_ready(Instant::now())
+}
+
+/// Returns relay loops which didn't complete an iteration within liveness
+/// timeout, counting from registration for loops which never did.
+pub fn stalled_loops() -> Vec<(String, Duration)> {
+    STATE
+        .lock()
+        .expect("poisoned")
+        .stalled_loops(Instant::now())
+}
+
+/// Reports progress of the relay main loop.
+#[derive(Clone, Debug)]
+pub struct LoopHeartbeat {
+    name: String,
+}
+
+impl LoopHeartbeat {
+    pub fn register(
+        source: impl Into<GenericNetworkId>,
+        target: impl Into<GenericNetworkId>,
+    ) -> Self {
+        Self::named(loop_name(None, source.into(), target.into()))
+    }
+
+    /// Registers a loop which shares source and target networks with another one.
+    pub fn register_kind(
+        kind: &str,
+        source: impl Into<GenericNetworkId>,
+        target: impl Into<GenericNetworkId>,
+    ) -> Self {
+        Self::named(loop_name(Some(kind), source.into(), target.into()))
+    }
+
+    fn named(name: String) -> Self {
+        STATE
+            .lock()
+            .expect("poisoned")
+            .register(name.clone(), Instant::now());
+        Self { name }
+    }
+
+    pub fn beat(&self) {
+        STATE
+            .lock()
+            .expect("poisoned")
+            .beat(&self.name, Instant::now());
+    }
+}
+
+fn loop_name(kind: Option<&str>, source: GenericNetworkId, target: GenericNetworkId) -> String {
+    match kind {
+        Some(kind) => format!("{} {:?} -> {:?}", kind, source, target),
+        None => format!("{:?} -> {:?}", source, target),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bridge_types::SubNetworkId;
+
+    const TIMEOUT: Duration = Duration::from_secs(60);
+
+    fn state(now: Instant) -> HealthState {
+        let mut state = HealthState::new(TIMEOUT);
+        state.register("first".into(), now);
+        state.register("second".into(), now);
+        state
+    }
+
+    #[test]
+    fn not_ready_without_loops() {
+        assert!(!HealthState::new(TIMEOUT).is_ready(Instant::now()));
+    }
+
+    #[test]
+    fn ready_when_every_loop_beats() {
+        let now = Instant::now();
+        let mut state = state(now);
+        assert!(!state.is_ready(now));
+        state.beat("first", now);
+        assert!(!state.is_ready(now));
+        state.beat("second", now);
+        assert!(state.is_ready(now));
+        // Loop which stopped beating makes relayer not ready again
+        state.beat("first", now + TIMEOUT);
+        assert!(!state.is_ready(now + TIMEOUT * 2));
+    }
+
+    #[test]
+    fn reports_stalled_loops() {
+        let now = Instant::now();
+        let mut state = state(now);
+        assert!(state.stalled_loops(now + TIMEOUT).is_empty());
+        state.beat("first", now + TIMEOUT);
+        let stalled = state.stalled_loops(now + TIMEOUT * 2);
+        assert_eq!(stalled, vec![("second".to_string(), TIMEOUT * 2)]);
+        state.beat("second", now + TIMEOUT * 2);
+        assert!(state.stalled_loops(now + TIMEOUT * 2).is_empty());
+    }
+
+    #[test]
+    fn names_loops_with_generic_network_ids() {
+        let sub = SubNetworkId::Mainnet;
+        let generic = GenericNetworkId::Sub(SubNetworkId::Kusama);
+        assert_eq!(
+            loop_name(None, sub.into(), generic),
+            loop_name(None, GenericNetworkId::Sub(sub), generic)
+        );
+        assert_eq!(
+            loop_name(Some("BEEFY"), sub.into(), generic),
+            "BEEFY Sub(Mainnet) -> Sub(Kusama)"
+        );
+    }
+}
@@ -0,0 +1,322 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Journal of commitments relayed by this relayer instance, stored as JSON
+//! lines in the file set by `--history-path`.
+
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::Mutex;
+
+use crate::prelude::*;
+use crate::substrate::SubmittedExtrinsic;
+use bridge_types::GenericNetworkId;
+use ethers::types::TransactionReceipt;
+use sp_core::H256;
+
+lazy_static::lazy_static! {
+    static ref HISTORY_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+    static ref WRITER: Mutex<mpsc::Sender<WriterMessage>> = Mutex::new(spawn_writer());
+}
+
+/// Enables journal, entries are appended to the file at `path`.
+pub fn set_path(path: &Path) {
+    *HISTORY_PATH.lock().expect("poisoned") = Some(path.to_path_buf());
+}
+
+enum WriterMessage {
+    Entry(PathBuf, HistoryEntry),
+    Flush(mpsc::SyncSender<()>),
+}
+
+/// Starts thread which appends entries to the journal, so relay loops
+/// don't block on file IO.
+fn spawn_writer() -> mpsc::Sender<WriterMessage> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("history-writer".to_string())
+        .spawn(move || {
+            for message in receiver {
+                match message {
+                    WriterMessage::Entry(path, entry) => {
+                        if let Err(err) = entry.append_to(&path) {
+                            warn!("Failed to write history entry: {:?}", err);
+                        }
+                    }
+                    WriterMessage::Flush(done) => {
+                        done.send(()).ok();
+                    }
+                }
+            }
+        })
+        .expect("failed to spawn history writer");
+    sender
+}
+
+/// Waits until all recorded entries are written.
+pub fn flush() {
+    if HISTORY_PATH.lock().expect("poisoned").is_none() {
+        return;
+    }
+    let (done, wait) = mpsc::sync_channel(1);
+    let sent = WRITER
+        .lock()
+        .expect("poisoned")
+        .send(WriterMessage::Flush(done));
+    if sent.is_ok() {
+        wait.recv().ok();
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EntryKind {
+    /// Approval of a commitment sent to SORA
+    Approval,
+    /// Commitment submitted to the Substrate inbound channel
+    InboundCommitment,
+    /// BEEFY commitment submitted to the light client
+    BeefyCommitment,
+    /// Batch submitted to the EVM channel
+    EvmBatch,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    Success,
+    Failed,
+}
+
+/// Action performed by the relayer.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// Unix timestamp in seconds
+    pub timestamp: i64,
+    pub kind: EntryKind,
+    pub source: String,
+    pub target: String,
+    pub nonce: Option<u64>,
+    pub commitment_hash: Option<H256>,
+    /// Extrinsic or transaction hash
+    pub tx_hash: Option<H256>,
+    pub block_hash: Option<H256>,
+    pub block_number: Option<u64>,
+    pub outcome: Outcome,
+    pub error: Option<String>,
+}
+
+impl HistoryEntry {
+    pub fn new(
+        kind: EntryKind,
+        source: impl Into<GenericNetworkId>,
+        target: impl Into<GenericNetworkId>,
+    ) -> Self {
+        Self {
+            timestamp: chrono::Utc::now().timestamp(),
+            kind,
+            source: format!("{:?}", source.into()),
+            target: format!("{:?}", target.into()),
+            nonce: None,
+            commitment_hash: None,
+            tx_hash: None,
+            block_hash: None,
+            block_number: None,
+            outcome: Outcome::Success,
+            error: None,
+        }
+    }
+
+    pub fn with_nonce(mut self, nonce: u64) -> Self {
+        self.nonce = Some(nonce);
+        self
+    }
+
+    pub fn with_commitment_hash(mut self, commitment_hash: H256) -> Self {
+        self.commitment_hash = Some(commitment_hash);
+        self
+    }
+
+    pub fn with_block_number(mut self, block_number: u64) -> Self {
+        self.block_number = Some(block_number);
+        self
+    }
+
+    pub fn with_extrinsic(mut self, extrinsic: &SubmittedExtrinsic) -> Self {
+        self.tx_hash = Some(extrinsic.extrinsic_hash);
+        self.block_hash = extrinsic.block_hash;
+        self
+    }
+
+    pub fn with_receipt(mut self, receipt: &TransactionReceipt) -> Self {
+        self.tx_hash = Some(H256(receipt.transaction_hash.0));
+        self.block_hash = receipt.block_hash.map(|hash| H256(hash.0));
+        self.block_number = receipt.block_number.map(|number| number.as_u64());
+        if receipt.status == Some(0u64.into()) {
+            self.outcome = Outcome::Failed;
+        }
+        self
+    }
+
+    pub fn with_error(mut self, err: &anyhow::Error) -> Self {
+        self.outcome = Outcome::Failed;
+        self.error = Some(format!("{:#}", err));
+        self
+    }
+
+    /// Records outcome of the extrinsic submission.
+    pub fn record_extrinsic(self, result: &AnyResult<SubmittedExtrinsic>) {
+        match result {
+            Ok(extrinsic) => self.with_extrinsic(extrinsic).record(),
+            Err(err) => self.with_error(err).record(),
+        }
+    }
+
+    /// Appends entry to the journal if it is enabled. Entry is written in
+    /// background and failures are only logged, so journal never stops relaying.
+    pub fn record(self) {
+        if crate::dry_run::is_enabled() {
+            return;
+        }
+        let Some(path) = HISTORY_PATH.lock().expect("poisoned").clone() else {
+            return;
+        };
+        debug!("History entry: {:?}", self);
+        let sent = WRITER
+            .lock()
+            .expect("poisoned")
+            .send(WriterMessage::Entry(path, self));
+        if let Err(err) = sent {
+            warn!(
+                "Failed to write history entry: history writer stopped: {}",
+                err
+            );
+        }
+    }
+
+    /// Appends entry to the file as a JSON line.
+    pub fn append_to(&self, path: &Path) -> AnyResult<()> {
+        let mut line = serde_json::to_string(self)?;
+        line.push('\n');
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .with_context(|| format!("open history file {}", path.display()))?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    /// Reads all entries from the file, skipping lines which are not valid entries.
+    pub fn read_all(path: &Path) -> AnyResult<Vec<HistoryEntry>> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("open history file {}", path.display()))?;
+        let mut entries = vec![];
+        for (index, line) in std::io::BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(&line) {
+                Ok(entry) => entries.push(entry),
+                Err(err) => warn!("Skip history entry at line {}: {}", index + 1, err),
+            }
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bridge_types::SubNetworkId;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "relayer-history-{}-{}.jsonl",
+            std::process::id(),
+            name
+        ));
+        std::fs::remove_file(&path).ok();
+        path
+    }
+
+    fn entry(nonce: u64) -> HistoryEntry {
+        HistoryEntry::new(
+            EntryKind::InboundCommitment,
+            GenericNetworkId::Sub(SubNetworkId::Mainnet),
+            GenericNetworkId::EVM(1u64.into()),
+        )
+        .with_nonce(nonce)
+    }
+
+    #[test]
+    fn reads_appended_entries() {
+        let path = temp_path("append");
+        entry(1).append_to(&path).unwrap();
+        entry(2)
+            .with_error(&anyhow!("failed"))
+            .append_to(&path)
+            .unwrap();
+        let entries = HistoryEntry::read_all(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].nonce, Some(1));
+        assert_eq!(entries[0].outcome, Outcome::Success);
+        assert_eq!(entries[1].outcome, Outcome::Failed);
+        assert_eq!(entries[1].error.as_deref(), Some("failed"));
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn skips_invalid_lines() {
+        let path = temp_path("invalid");
+        entry(1).append_to(&path).unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"not json\n\n{\"timestamp\":1}\n")
+            .unwrap();
+        entry(2).append_to(&path).unwrap();
+        let nonces = HistoryEntry::read_all(&path)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.nonce)
+            .collect::<Vec<_>>();
+        assert_eq!(nonces, vec![Some(1), Some(2)]);
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn fails_on_missing_file() {
+        assert!(HistoryEntry::read_all(&temp_path("missing")).is_err());
+    }
+}
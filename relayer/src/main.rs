@@ -29,7 +29,12 @@
 // USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
 mod cli;
+mod dry_run;
+mod endpoints;
 mod ethereum;
+mod health;
+mod history;
+mod metrics;
 mod relay;
 mod substrate;
 mod ton;
@@ -42,11 +47,16 @@ extern crate log;
 #[macro_use]
 extern crate anyhow;
 
+#[macro_use]
+extern crate prometheus;
+
 #[tokio::main]
 async fn main() -> AnyResult<()> {
     init_log();
     let cli = cli::Cli::parse();
-    cli.run().await.map_err(|e| {
+    let res = cli.run().await;
+    history::flush();
+    res.map_err(|e| {
         error!("Relayer returned error: {:?}", e);
         e
     })?;
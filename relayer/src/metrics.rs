@@ -0,0 +1,222 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+use std::net::SocketAddr;
+
+use crate::prelude::*;
+use crate::substrate::InboundCommitmentStatus;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, StatusCode};
+use prometheus::{Encoder, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, TextEncoder};
+
+const RELAY_LABELS: &[&str] = &["source", "target"];
+
+lazy_static::lazy_static! {
+    static ref INBOUND_NONCE: IntGaugeVec = register_int_gauge_vec!(
+        "relayer_inbound_channel_nonce",
+        "Latest nonce accepted by inbound channel on the target network",
+        RELAY_LABELS
+    )
+    .expect("metric can be created");
+    static ref OUTBOUND_NONCE: IntGaugeVec = register_int_gauge_vec!(
+        "relayer_outbound_channel_nonce",
+        "Latest nonce sent by outbound channel on the source network",
+        RELAY_LABELS
+    )
+    .expect("metric can be created");
+    static ref NONCE_LAG: IntGaugeVec = register_int_gauge_vec!(
+        "relayer_channel_nonce_lag",
+        "Number of commitments which are not relayed yet",
+        RELAY_LABELS
+    )
+    .expect("metric can be created");
+    static ref LATEST_BEEFY_BLOCK: IntGaugeVec = register_int_gauge_vec!(
+        "relayer_latest_beefy_block_sent",
+        "Latest BEEFY block sent to the light client on the target network",
+        RELAY_LABELS
+    )
+    .expect("metric can be created");
+    static ref COMMITMENTS_SUBMITTED: IntCounterVec = register_int_counter_vec!(
+        "relayer_commitments_submitted_total",
+        "Number of commitments submitted by this relayer",
+        RELAY_LABELS
+    )
+    .expect("metric can be created");
+    static ref COMMITMENTS_FAILED: IntCounterVec = register_int_counter_vec!(
+        "relayer_commitments_failed_total",
+        "Number of commitments which this relayer failed to submit",
+        RELAY_LABELS
+    )
+    .expect("metric can be created");
+    static ref APPROVALS_SENT: IntCounterVec = register_int_counter_vec!(
+        "relayer_approvals_sent_total",
+        "Number of commitment approvals sent by this relayer",
+        RELAY_LABELS
+    )
+    .expect("metric can be created");
+    static ref EVM_GAS_SPENT: IntCounterVec = register_int_counter_vec!(
+        "relayer_evm_gas_spent_total",
+        "Gas spent by this relayer for EVM transactions",
+        RELAY_LABELS
+    )
+    .expect("metric can be created");
+}
+
+/// Metrics of the single bridge direction.
+#[derive(Clone)]
+pub struct RelayMetrics {
+    inbound_nonce: IntGauge,
+    outbound_nonce: IntGauge,
+    nonce_lag: IntGauge,
+    latest_beefy_block: IntGauge,
+    commitments_submitted: IntCounter,
+    commitments_failed: IntCounter,
+    approvals_sent: IntCounter,
+    evm_gas_spent: IntCounter,
+}
+
+impl RelayMetrics {
+    pub fn new(source: impl core::fmt::Debug, target: impl core::fmt::Debug) -> Self {
+        let source = format!("{:?}", source);
+        let target = format!("{:?}", target);
+        let labels = [source.as_str(), target.as_str()];
+        Self {
+            inbound_nonce: INBOUND_NONCE.with_label_values(&labels),
+            outbound_nonce: OUTBOUND_NONCE.with_label_values(&labels),
+            nonce_lag: NONCE_LAG.with_label_values(&labels),
+            latest_beefy_block: LATEST_BEEFY_BLOCK.with_label_values(&labels),
+            commitments_submitted: COMMITMENTS_SUBMITTED.with_label_values(&labels),
+            commitments_failed: COMMITMENTS_FAILED.with_label_values(&labels),
+            approvals_sent: APPROVALS_SENT.with_label_values(&labels),
+            evm_gas_spent: EVM_GAS_SPENT.with_label_values(&labels),
+        }
+    }
+
+    pub fn set_nonces(&self, inbound: u64, outbound: u64) {
+        self.inbound_nonce.set(inbound as i64);
+        self.outbound_nonce.set(outbound as i64);
+        self.nonce_lag.set(outbound.saturating_sub(inbound) as i64);
+    }
+
+    pub fn set_latest_beefy_block(&self, block: u64) {
+        self.latest_beefy_block.set(block as i64);
+    }
+
+    pub fn commitment_submitted(&self) {
+        self.commitments_submitted.inc();
+    }
+
+    pub fn commitment_failed(&self) {
+        self.commitments_failed.inc();
+    }
+
+    pub fn approval_sent(&self) {
+        self.approvals_sent.inc();
+    }
+
+    pub fn inbound_commitment(&self, status: InboundCommitmentStatus) {
+        if status.approval_sent {
+            self.approval_sent();
+        }
+        if status.commitment_submitted {
+            self.commitment_submitted();
+        }
+    }
+
+    pub fn evm_gas_spent(&self, gas: u64) {
+        self.evm_gas_spent.inc_by(gas);
+    }
+}
+
+fn response(status: StatusCode, body: impl Into<Body>) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(body.into())
+        .expect("response is valid")
+}
+
+fn metrics_response() -> Response<Body> {
+    let encoder = TextEncoder::new();
+    let mut buffer = vec![];
+    if let Err(err) = encoder.encode(&prometheus::gather(), &mut buffer) {
+        error!("Failed to encode metrics: {:?}", err);
+        return response(StatusCode::INTERNAL_SERVER_ERROR, Body::empty());
+    }
+    Response::builder()
+        .header(hyper::header::CONTENT_TYPE, encoder.format_type())
+        .body(Body::from(buffer))
+        .expect("response is valid")
+}
+
+fn liveness_response() -> Response<Body> {
+    let stalled = crate::health::stalled_loops();
+    if stalled.is_empty() {
+        return response(StatusCode::OK, "OK");
+    }
+    let body = stalled
+        .into_iter()
+        .map(|(name, elapsed)| format!("{} stalled for {}s\n", name, elapsed.as_secs()))
+        .collect::<String>();
+    response(StatusCode::SERVICE_UNAVAILABLE, body)
+}
+
+fn readiness_response() -> Response<Body> {
+    if crate::health::is_ready() {
+        response(StatusCode::OK, "OK")
+    } else {
+        response(StatusCode::SERVICE_UNAVAILABLE, "Not ready")
+    }
+}
+
+async fn handle_request(req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
+    let res = match req.uri().path() {
+        "/metrics" => metrics_response(),
+        "/healthz" => liveness_response(),
+        "/readyz" => readiness_response(),
+        _ => response(StatusCode::NOT_FOUND, Body::empty()),
+    };
+    Ok(res)
+}
+
+/// Starts serving Prometheus metrics on `http://{addr}/metrics` and health probes on
+/// `http://{addr}/healthz` and `http://{addr}/readyz` in background.
+pub fn serve(addr: SocketAddr) -> AnyResult<()> {
+    let server = hyper::Server::try_bind(&addr)
+        .context(format!("bind metrics endpoint to {}", addr))?
+        .serve(make_service_fn(|_| async {
+            Ok::<_, hyper::Error>(service_fn(handle_request))
+        }));
+    info!("Serving metrics on http://{}/metrics", addr);
+    tokio::spawn(async move {
+        if let Err(err) = server.await {
+            error!("Metrics server failed: {:?}", err);
+        }
+    });
+    Ok(())
+}
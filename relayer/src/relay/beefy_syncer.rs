@@ -55,7 +55,14 @@ impl BeefySyncer {
         self.latest_sent.load(Ordering::Relaxed)
     }
 
-    #[allow(dead_code)]
+    /// Returns true if the BEEFY commitment at `block` should be sent to
+    /// deliver messages, i.e. it is the first one at or after the requested
+    /// block which is not sent yet.
+    pub fn is_requested(&self, block: u64) -> bool {
+        let latest_requested = self.latest_requested();
+        self.latest_sent() < latest_requested && latest_requested <= block
+    }
+
     pub fn update_latest_requested(&self, block: u64) {
         self.latest_requested
             .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| {
@@ -82,3 +89,30 @@ impl BeefySyncer {
             .ok();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_first_commitment_at_or_after_block() {
+        let syncer = BeefySyncer::new();
+        assert!(!syncer.is_requested(1));
+
+        syncer.update_latest_requested(5);
+        assert!(!syncer.is_requested(4));
+        assert!(syncer.is_requested(5));
+        assert!(syncer.is_requested(8));
+
+        syncer.update_latest_sent(8);
+        assert!(!syncer.is_requested(9));
+    }
+
+    #[test]
+    fn keeps_latest_request() {
+        let syncer = BeefySyncer::new();
+        syncer.update_latest_requested(10);
+        syncer.update_latest_requested(5);
+        assert_eq!(syncer.latest_requested(), 10);
+    }
+}
@@ -0,0 +1,87 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+use std::path::{Path, PathBuf};
+
+use crate::prelude::*;
+
+/// Storage for the latest fully processed block of a relayed channel.
+pub trait CheckpointStore: Send + Sync {
+    fn load(&self, key: &str) -> AnyResult<Option<u64>>;
+
+    fn store(&self, key: &str, block: u64) -> AnyResult<()>;
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Checkpoint {
+    block: u64,
+}
+
+/// Keeps every checkpoint in a separate JSON file inside the state directory.
+#[derive(Clone, Debug)]
+pub struct FileCheckpointStore {
+    dir: PathBuf,
+}
+
+impl FileCheckpointStore {
+    pub fn new(dir: impl AsRef<Path>) -> AnyResult<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)
+            .context(format!("create state directory {}", dir.display()))?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{key}.json"))
+    }
+}
+
+impl CheckpointStore for FileCheckpointStore {
+    fn load(&self, key: &str) -> AnyResult<Option<u64>> {
+        let path = self.path(key);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let data = std::fs::read(&path).context(format!("read {}", path.display()))?;
+        let checkpoint: Checkpoint =
+            serde_json::from_slice(&data).context(format!("decode {}", path.display()))?;
+        Ok(Some(checkpoint.block))
+    }
+
+    fn store(&self, key: &str, block: u64) -> AnyResult<()> {
+        let path = self.path(key);
+        // Write to a temporary file first, so the checkpoint is never left half written
+        let tmp_path = path.with_extension("json.tmp");
+        let data = serde_json::to_vec(&Checkpoint { block })?;
+        std::fs::write(&tmp_path, data).context(format!("write {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, &path).context(format!("write {}", path.display()))?;
+        trace!("Checkpoint {} updated to block {}", key, block);
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Encoding and signing of batches sent from SORA to EVM channel contract.
+//!
+//! The module doesn't depend on the rest of the crate, so integration tests
+//! include it to submit batches the same way the relay does.
+
+use bridge_types::evm::OutboundCommitment;
+use bridge_types::GenericNetworkId;
+use ethereum_gen::ChannelHandler;
+use ethers::abi::Tokenize;
+use ethers::providers::Middleware;
+use sp_core::{ecdsa, H256};
+use sp_runtime::traits::{Hash, Keccak256};
+
+/// Same as `crate::substrate::MaxU32`.
+pub type MaxU32 = sp_runtime::traits::ConstU32<{ core::u32::MAX }>;
+
+/// Channel contract call which submits the batch with peer approvals.
+pub fn submit_call<M: Middleware>(
+    channel: &ChannelHandler<M>,
+    commitment: &OutboundCommitment<MaxU32, MaxU32>,
+    approvals: Vec<ecdsa::Signature>,
+) -> ethers::contract::ContractCall<M, ()> {
+    let batch = prepare_batch(commitment);
+    let (v, r, s) = approvals
+        .into_iter()
+        .map(|approval| {
+            (
+                approval.0[64],
+                approval.0[..32].try_into().unwrap(),
+                approval.0[32..64].try_into().unwrap(),
+            )
+        })
+        .fold((vec![], vec![], vec![]), |mut vrs, (v, r, s)| {
+            vrs.0.push(v + 27);
+            vrs.1.push(r);
+            vrs.2.push(s);
+            vrs
+        });
+    channel.submit(batch, v, r, s)
+}
+
+fn prepare_batch(
+    commitment: &OutboundCommitment<MaxU32, MaxU32>,
+) -> ethereum_gen::channel_handler::Batch {
+    ethereum_gen::channel_handler::Batch {
+        nonce: commitment.nonce.into(),
+        total_max_gas: commitment.total_max_gas.into(),
+        messages: commitment
+            .messages
+            .iter()
+            .map(|message| ethereum_gen::channel_handler::Message {
+                max_gas: message.max_gas.into(),
+                target: message.target.into(),
+                payload: message.payload.to_vec().into(),
+            })
+            .collect(),
+    }
+}
+
+fn prepare_evm_signed_message(msg: H256) -> H256 {
+    let mut prefix = b"\x19Ethereum Signed Message:\n32".to_vec();
+    prefix.extend(msg.as_bytes());
+    sp_core::keccak_256(&prefix).into()
+}
+
+/// Message which peers approve on SORA to submit the batch to EVM network.
+pub fn message_to_sign(
+    sub_network_id: GenericNetworkId,
+    evm_network_id: GenericNetworkId,
+    commitment: &OutboundCommitment<MaxU32, MaxU32>,
+) -> H256 {
+    let batch = prepare_batch(commitment);
+
+    let tokens = batch.clone().into_tokens();
+    let tokens = ethers::abi::Token::Tuple(tokens);
+    let encoded_batch = ethers::abi::encode(&[tokens]);
+    let batch_hash = Keccak256::hash(&encoded_batch);
+    let message = Keccak256::hash_of(&(sub_network_id, evm_network_id, batch_hash));
+    let message = prepare_evm_signed_message(message);
+    message
+}
@@ -31,59 +31,112 @@
 use std::time::Duration;
 
 use bridge_types::evm::{BaseFeeUpdate, InboundCommitment, StatusReport};
-use bridge_types::{EVMChainId, GenericNetworkId};
+use bridge_types::GenericNetworkId;
 use sp_core::ecdsa;
 
+use crate::ethereum::logs::PagedClient;
+use crate::health::LoopHeartbeat;
+use crate::metrics::RelayMetrics;
 use crate::prelude::*;
+use crate::relay::checkpoint::CheckpointStore;
+use crate::relay::traits::{
+    EvmChannelReader, EvmInboundChannelReader, InboundChannelReader, InboundCommitmentSubmitter,
+};
 use crate::substrate::UnboundedGenericCommitment;
 use ethers::prelude::*;
 
 const BLOCKS_TO_INITIAL_SEARCH: u64 = 49000; // Ethereum light client keep 50000 blocks
 
-pub struct SubstrateMessagesRelay {
-    sub: SubUnsignedClient<MainnetConfig>,
-    eth: EthUnsignedClient,
+/// Selects channel events which continue the sequence after `nonce`, in
+/// nonce order. Events of other contracts, already relayed events and events
+/// after a gap are skipped.
+fn next_in_sequence<T>(
+    events: Vec<(T, LogMeta)>,
+    channel: Address,
+    mut nonce: u64,
+    event_nonce: impl Fn(&T) -> u64,
+) -> Vec<(T, LogMeta)> {
+    let mut selected = vec![];
+    for (event, meta) in events {
+        if event_nonce(&event) == nonce + 1 && meta.address == channel {
+            selected.push((event, meta));
+            nonce += 1;
+        }
+    }
+    selected
+}
+
+pub struct SubstrateMessagesRelay<S = SubUnsignedClient<MainnetConfig>, E = PagedClient> {
+    sub: S,
+    eth: E,
     evm_network_id: GenericNetworkId,
     sub_network_id: GenericNetworkId,
     channel: Address,
     latest_channel_block: u64,
     signer: ecdsa::Pair,
+    checkpoint: Option<Box<dyn CheckpointStore>>,
+    /// Checkpoint isn't saved in dry run, since messages are not relayed.
+    dry_run: bool,
+    metrics: RelayMetrics,
+    heartbeat: LoopHeartbeat,
 }
 
-impl SubstrateMessagesRelay {
-    pub async fn new(
-        sub: SubUnsignedClient<MainnetConfig>,
-        eth: EthUnsignedClient,
-        signer: ecdsa::Pair,
-    ) -> AnyResult<Self> {
-        let chain_id = eth.chainid().await? as EVMChainId;
+impl<S, E> SubstrateMessagesRelay<S, E>
+where
+    S: InboundChannelReader + EvmInboundChannelReader + InboundCommitmentSubmitter,
+    E: EvmChannelReader,
+{
+    pub async fn new(sub: S, eth: E, signer: ecdsa::Pair) -> AnyResult<Self> {
+        let chain_id = eth.chain_id().await?;
         let channel = sub
-            .storage_fetch(
-                &runtime::storage()
-                    .bridge_inbound_channel()
-                    .evm_channel_addresses(&chain_id),
-                (),
-            )
+            .evm_channel_address(chain_id)
             .await?
             .ok_or(anyhow::anyhow!("Inbound channel is not registered"))?;
-        let sub_network_id = sub.constant_fetch_or_default(
-            &runtime::constants()
-                .bridge_inbound_channel()
-                .this_network_id(),
-        )?;
+        let sub_network_id = InboundChannelReader::network_id(&sub)?;
+        let evm_network_id = GenericNetworkId::from(chain_id);
         Ok(Self {
+            metrics: RelayMetrics::new(evm_network_id, sub_network_id),
+            heartbeat: LoopHeartbeat::register(evm_network_id, sub_network_id),
             latest_channel_block: 0,
             sub,
             eth,
-            evm_network_id: chain_id.into(),
+            evm_network_id,
             sub_network_id,
             channel,
             signer,
+            checkpoint: None,
+            dry_run: crate::dry_run::is_enabled(),
         })
     }
 
+    pub fn with_checkpoint_store(mut self, store: impl CheckpointStore + 'static) -> Self {
+        self.checkpoint = Some(Box::new(store));
+        self
+    }
+
+    fn checkpoint_key(&self) -> String {
+        let GenericNetworkId::EVM(chain_id) = self.evm_network_id else {
+            unreachable!()
+        };
+        format!("evm-{}-{:?}", chain_id, self.channel)
+    }
+
+    fn load_checkpoint(&self) -> AnyResult<Option<u64>> {
+        let Some(store) = &self.checkpoint else {
+            return Ok(None);
+        };
+        store.load(&self.checkpoint_key())
+    }
+
+    fn save_checkpoint(&self, block: u64) -> AnyResult<()> {
+        let Some(store) = &self.checkpoint else {
+            return Ok(());
+        };
+        store.store(&self.checkpoint_key(), block)
+    }
+
     pub async fn handle_messages(&mut self) -> AnyResult<()> {
-        let current_eth_block = self.eth.get_finalized_block_number().await?;
+        let current_eth_block = self.eth.finalized_block_number().await?;
         if current_eth_block < self.latest_channel_block {
             debug!("Skip handling channel messages, current block number is less than latest basic {} < {}", current_eth_block, self.latest_channel_block);
             return Ok(());
@@ -94,35 +147,49 @@ impl SubstrateMessagesRelay {
         self.handle_base_fee_update(current_eth_block).await?;
 
         self.latest_channel_block = current_eth_block + 1;
+        if self.dry_run {
+            debug!("Dry run: channel checkpoint is not saved");
+        } else if let Err(err) = self.save_checkpoint(current_eth_block) {
+            warn!("Failed to save channel checkpoint: {:?}", err);
+        }
         Ok(())
     }
 
+    async fn submit_commitment(&self, commitment: UnboundedGenericCommitment) -> AnyResult<()> {
+        let res = self
+            .sub
+            .submit_inbound_commitment(
+                self.signer.clone(),
+                self.evm_network_id,
+                self.sub_network_id,
+                commitment,
+            )
+            .await;
+        match res {
+            Ok(status) => {
+                self.metrics.inbound_commitment(status);
+                Ok(())
+            }
+            Err(err) => {
+                self.metrics.commitment_failed();
+                Err(err)
+            }
+        }
+    }
+
     async fn handle_base_fee_update(&mut self, current_eth_block: u64) -> AnyResult<()> {
         let GenericNetworkId::EVM(chain_id) = self.evm_network_id else {
             unreachable!()
         };
         let eth_block = current_eth_block - current_eth_block % 10;
 
-        let last_update = self
-            .sub
-            .storage_fetch(
-                &runtime::storage().evm_fungible_app().base_fees(chain_id),
-                (),
-            )
-            .await?
-            .map(|x| x.evm_block_number)
-            .unwrap_or(Default::default());
+        let last_update = self.sub.base_fee_block(chain_id).await?.unwrap_or_default();
         if eth_block <= last_update {
             info!("Skip base fee update, too early");
             return Ok(());
         }
 
-        let block = self
-            .eth
-            .get_block(eth_block)
-            .await?
-            .ok_or(anyhow::anyhow!("Block {} not found", eth_block))?;
-        let base_fee = block.base_fee_per_gas.unwrap_or_default();
+        let base_fee = self.eth.base_fee(eth_block).await?;
         let commitment = UnboundedGenericCommitment::EVM(
             bridge_types::evm::Commitment::BaseFeeUpdate(BaseFeeUpdate {
                 new_base_fee: base_fee,
@@ -130,28 +197,15 @@ impl SubstrateMessagesRelay {
             }),
         );
         info!("Submitting base fee update: {}", base_fee);
-        self.sub
-            .submit_inbound_commitment(
-                self.signer.clone(),
-                self.evm_network_id,
-                self.sub_network_id,
-                commitment,
-            )
-            .await?;
+        self.submit_commitment(commitment).await?;
         Ok(())
     }
 
     async fn handle_message_events(&mut self, current_eth_block: u64) -> AnyResult<()> {
-        let eth = self.eth.inner();
-        let channel = ethereum_gen::ChannelHandler::new(self.channel, eth.clone());
-        let events: Vec<(
-            ethereum_gen::channel_handler::MessageDispatchedFilter,
-            LogMeta,
-        )> = channel
-            .message_dispatched_filter()
-            .from_block(self.latest_channel_block)
-            .to_block(current_eth_block)
-            .query_with_meta()
+        let from_block = self.latest_channel_block;
+        let events = self
+            .eth
+            .message_dispatched(self.channel, from_block, current_eth_block)
             .await?;
         debug!(
             "Channel: Found {} Message events from {} to {}",
@@ -159,58 +213,39 @@ impl SubstrateMessagesRelay {
             self.latest_channel_block,
             current_eth_block
         );
-        let mut sub_nonce = self
-            .sub
-            .storage_fetch_or_default(
-                &runtime::storage()
-                    .bridge_inbound_channel()
-                    .channel_nonces(&self.evm_network_id),
-                (),
-            )
-            .await?;
+        let sub_nonce = self.sub.inbound_nonce(self.evm_network_id).await?;
+        let evm_nonce = self.eth.message_nonce(self.channel).await?;
+        self.metrics.set_nonces(sub_nonce, evm_nonce);
 
+        let events = next_in_sequence(events, self.channel, sub_nonce, |event| {
+            event.nonce.as_u64()
+        });
         for (event, meta) in events {
-            if event.nonce.as_u64() == sub_nonce + 1 && meta.address == self.channel {
-                let commitment = UnboundedGenericCommitment::EVM(
-                    bridge_types::evm::Commitment::Inbound(InboundCommitment {
-                        channel: meta.address,
-                        source: event.source,
-                        block_number: meta.block_number.as_u64(),
-                        nonce: event.nonce.as_u64(),
-                        payload: event
-                            .payload
-                            .to_vec()
-                            .try_into()
-                            .map_err(|_| anyhow::anyhow!("Invalid payload"))?,
-                    }),
-                );
-                info!("Submit commitment: {}", commitment.nonce());
-                self.sub
-                    .submit_inbound_commitment(
-                        self.signer.clone(),
-                        self.evm_network_id,
-                        self.sub_network_id,
-                        commitment,
-                    )
-                    .await?;
-                sub_nonce += 1;
-            }
+            let commitment = UnboundedGenericCommitment::EVM(
+                bridge_types::evm::Commitment::Inbound(InboundCommitment {
+                    channel: meta.address,
+                    source: event.source,
+                    block_number: meta.block_number.as_u64(),
+                    nonce: event.nonce.as_u64(),
+                    payload: event
+                        .payload
+                        .to_vec()
+                        .try_into()
+                        .map_err(|_| anyhow::anyhow!("Invalid payload"))?,
+                }),
+            );
+            info!("Submit commitment: {}", commitment.nonce());
+            self.submit_commitment(commitment).await?;
         }
 
         Ok(())
     }
 
     async fn handle_batch_dispatched(&mut self, current_eth_block: u64) -> AnyResult<()> {
-        let eth = self.eth.inner();
-        let inbound_channel = ethereum_gen::ChannelHandler::new(self.channel, eth.clone());
-        let events: Vec<(
-            ethereum_gen::channel_handler::BatchDispatchedFilter,
-            LogMeta,
-        )> = inbound_channel
-            .batch_dispatched_filter()
-            .from_block(self.latest_channel_block)
-            .to_block(current_eth_block)
-            .query_with_meta()
+        let from_block = self.latest_channel_block;
+        let events = self
+            .eth
+            .batch_dispatched(self.channel, from_block, current_eth_block)
             .await?;
         debug!(
             "Channel: Found {} BatchDispatched events from {} to {}",
@@ -219,71 +254,59 @@ impl SubstrateMessagesRelay {
             current_eth_block
         );
 
-        let mut sub_reported_nonce = self
-            .sub
-            .storage_fetch_or_default(
-                &runtime::storage()
-                    .bridge_inbound_channel()
-                    .reported_channel_nonces(&self.evm_network_id),
-                (),
-            )
-            .await?;
+        let sub_reported_nonce = self.sub.reported_nonce(self.evm_network_id).await?;
 
+        let events = next_in_sequence(events, self.channel, sub_reported_nonce, |event| {
+            event.batch_nonce.as_u64()
+        });
         for (event, meta) in events {
-            if event.batch_nonce.as_u64() == sub_reported_nonce + 1 && meta.address == self.channel
-            {
-                let mut results = vec![];
-                for i in 0..event.results_length.as_usize() {
-                    if event.results.bit(i) {
-                        results.push(true);
-                    } else {
-                        results.push(false);
-                    }
+            let mut results = vec![];
+            for i in 0..event.results_length.as_usize() {
+                if event.results.bit(i) {
+                    results.push(true);
+                } else {
+                    results.push(false);
                 }
-                let commitment = UnboundedGenericCommitment::EVM(
-                    bridge_types::evm::Commitment::StatusReport(StatusReport {
-                        nonce: event.batch_nonce.as_u64(),
-                        base_fee: event.base_fee,
-                        gas_spent: event.gas_spent,
-                        relayer: event.relayer,
-                        results: results.try_into().unwrap(),
-                        channel: meta.address,
-                        block_number: meta.block_number.as_u64(),
-                    }),
-                );
-                info!("Submitting status report: {:?}", commitment.nonce());
-                self.sub
-                    .submit_inbound_commitment(
-                        self.signer.clone(),
-                        self.evm_network_id,
-                        self.sub_network_id,
-                        commitment,
-                    )
-                    .await?;
-                sub_reported_nonce += 1;
             }
+            let commitment = UnboundedGenericCommitment::EVM(
+                bridge_types::evm::Commitment::StatusReport(StatusReport {
+                    nonce: event.batch_nonce.as_u64(),
+                    base_fee: event.base_fee,
+                    gas_spent: event.gas_spent,
+                    relayer: event.relayer,
+                    results: results.try_into().unwrap(),
+                    channel: meta.address,
+                    block_number: meta.block_number.as_u64(),
+                }),
+            );
+            info!("Submitting status report: {:?}", commitment.nonce());
+            self.submit_commitment(commitment).await?;
         }
 
         Ok(())
     }
 
     pub async fn run(mut self) -> AnyResult<()> {
-        let current_eth_block = self.eth.get_finalized_block_number().await?;
-
-        self.latest_channel_block = current_eth_block.saturating_sub(BLOCKS_TO_INITIAL_SEARCH);
-        let inbound_channel = ethereum_gen::ChannelHandler::new(self.channel, self.eth.inner());
-        let events: Vec<(ethereum_gen::channel_handler::ResetedFilter, LogMeta)> = inbound_channel
-            .reseted_filter()
-            .from_block(self.latest_channel_block)
-            .to_block(current_eth_block)
-            .query_with_meta()
+        let current_eth_block = self.eth.finalized_block_number().await?;
+
+        self.latest_channel_block = match self.load_checkpoint()? {
+            Some(block) => {
+                info!("Resuming channel scan from checkpoint at block {}", block);
+                (block + 1).min(current_eth_block)
+            }
+            None => current_eth_block.saturating_sub(BLOCKS_TO_INITIAL_SEARCH),
+        };
+        let from_block = self.latest_channel_block;
+        let reset_blocks = self
+            .eth
+            .reset_blocks(self.channel, from_block, current_eth_block)
             .await?;
-        self.latest_channel_block = events
+        self.latest_channel_block = reset_blocks
             .into_iter()
-            .map(|(_log, meta)| meta.block_number.as_u64())
             .max()
             .unwrap_or(self.latest_channel_block);
         loop {
+            self.heartbeat.beat();
             debug!("Handle channel messages");
             if let Err(err) = self.handle_messages().await {
                 warn!("Failed to handle channel messages: {}", err);
@@ -292,3 +315,441 @@ impl SubstrateMessagesRelay {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::substrate::InboundCommitmentStatus;
+    use bridge_types::evm::Commitment;
+    use bridge_types::{EVMChainId, SubNetworkId};
+    use ethereum_gen::channel_handler::{BatchDispatchedFilter, MessageDispatchedFilter};
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::sync::{Arc, Mutex};
+
+    const CHANNEL: Address = H160([1; 20]);
+    const CHAIN_ID: u64 = 5;
+
+    #[derive(Default)]
+    struct FakeEvmState {
+        finalized_block: u64,
+        base_fees: BTreeMap<u64, U256>,
+        messages: Vec<(MessageDispatchedFilter, LogMeta)>,
+        batches: Vec<(BatchDispatchedFilter, LogMeta)>,
+    }
+
+    /// EVM network with the channel contract at `CHANNEL`.
+    #[derive(Clone, Default)]
+    struct FakeEvm(Arc<Mutex<FakeEvmState>>);
+
+    impl FakeEvm {
+        fn state(&self) -> std::sync::MutexGuard<FakeEvmState> {
+            self.0.lock().unwrap()
+        }
+
+        fn send_message(&self, nonce: u64, block_number: u64) {
+            let message = MessageDispatchedFilter {
+                source: H160([2; 20]),
+                nonce: nonce.into(),
+                payload: vec![nonce as u8].into(),
+            };
+            self.state()
+                .messages
+                .push((message, meta(CHANNEL, block_number)));
+        }
+
+        fn dispatch_batch(&self, batch_nonce: u64, results: &[bool], block_number: u64) {
+            let mut bits = U256::zero();
+            for (i, result) in results.iter().enumerate() {
+                if *result {
+                    bits |= U256::one() << i;
+                }
+            }
+            let batch = BatchDispatchedFilter {
+                batch_nonce: batch_nonce.into(),
+                relayer: H160([3; 20]),
+                results: bits,
+                results_length: (results.len() as u64).into(),
+                gas_spent: 100.into(),
+                base_fee: 10.into(),
+            };
+            self.state()
+                .batches
+                .push((batch, meta(CHANNEL, block_number)));
+        }
+    }
+
+    fn in_range<T: Clone>(
+        events: &[(T, LogMeta)],
+        from_block: u64,
+        to_block: u64,
+    ) -> Vec<(T, LogMeta)> {
+        events
+            .iter()
+            .filter(|(_, meta)| (from_block..=to_block).contains(&meta.block_number.as_u64()))
+            .cloned()
+            .collect()
+    }
+
+    #[async_trait::async_trait]
+    impl EvmChannelReader for FakeEvm {
+        async fn chain_id(&self) -> AnyResult<EVMChainId> {
+            Ok(CHAIN_ID.into())
+        }
+
+        async fn finalized_block_number(&self) -> AnyResult<u64> {
+            Ok(self.state().finalized_block)
+        }
+
+        async fn base_fee(&self, block_number: u64) -> AnyResult<U256> {
+            self.state()
+                .base_fees
+                .get(&block_number)
+                .cloned()
+                .ok_or(anyhow!("Block {} not found", block_number))
+        }
+
+        async fn message_nonce(&self, _channel: H160) -> AnyResult<u64> {
+            Ok(self.state().messages.len() as u64)
+        }
+
+        async fn message_dispatched(
+            &mut self,
+            _channel: H160,
+            from_block: u64,
+            to_block: u64,
+        ) -> AnyResult<Vec<(MessageDispatchedFilter, LogMeta)>> {
+            Ok(in_range(&self.state().messages, from_block, to_block))
+        }
+
+        async fn batch_dispatched(
+            &mut self,
+            _channel: H160,
+            from_block: u64,
+            to_block: u64,
+        ) -> AnyResult<Vec<(BatchDispatchedFilter, LogMeta)>> {
+            Ok(in_range(&self.state().batches, from_block, to_block))
+        }
+
+        async fn reset_blocks(
+            &mut self,
+            _channel: H160,
+            _from_block: u64,
+            _to_block: u64,
+        ) -> AnyResult<Vec<u64>> {
+            Ok(vec![])
+        }
+    }
+
+    #[derive(Default)]
+    struct FakeSoraState {
+        channel: Option<H160>,
+        inbound_nonce: u64,
+        reported_nonce: u64,
+        base_fee_block: Option<u64>,
+        submitted: Vec<UnboundedGenericCommitment>,
+    }
+
+    /// SORA inbound channel which accepts commitments in nonce order.
+    #[derive(Clone, Default)]
+    struct FakeSora(Arc<Mutex<FakeSoraState>>);
+
+    impl FakeSora {
+        fn with_channel() -> Self {
+            let sora = Self::default();
+            sora.state().channel = Some(CHANNEL);
+            sora
+        }
+
+        fn state(&self) -> std::sync::MutexGuard<FakeSoraState> {
+            self.0.lock().unwrap()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl InboundChannelReader for FakeSora {
+        fn network_id(&self) -> AnyResult<GenericNetworkId> {
+            Ok(GenericNetworkId::Sub(SubNetworkId::Mainnet))
+        }
+
+        async fn inbound_nonce(&self, _source: GenericNetworkId) -> AnyResult<u64> {
+            Ok(self.state().inbound_nonce)
+        }
+
+        async fn peers(&self, _source: GenericNetworkId) -> AnyResult<BTreeSet<ecdsa::Public>> {
+            Ok(Default::default())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl EvmInboundChannelReader for FakeSora {
+        async fn evm_channel_address(&self, _chain_id: EVMChainId) -> AnyResult<Option<H160>> {
+            Ok(self.state().channel)
+        }
+
+        async fn reported_nonce(&self, _source: GenericNetworkId) -> AnyResult<u64> {
+            Ok(self.state().reported_nonce)
+        }
+
+        async fn base_fee_block(&self, _chain_id: EVMChainId) -> AnyResult<Option<u64>> {
+            Ok(self.state().base_fee_block)
+        }
+
+        async fn base_fee(&self, _chain_id: EVMChainId) -> AnyResult<Option<U256>> {
+            Ok(None)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl InboundCommitmentSubmitter for FakeSora {
+        async fn submit_inbound_commitment(
+            &self,
+            _signer: ecdsa::Pair,
+            _source: GenericNetworkId,
+            _target: GenericNetworkId,
+            commitment: UnboundedGenericCommitment,
+        ) -> AnyResult<InboundCommitmentStatus> {
+            let mut state = self.state();
+            match &commitment {
+                UnboundedGenericCommitment::EVM(Commitment::Inbound(inbound)) => {
+                    if inbound.nonce != state.inbound_nonce + 1 {
+                        return Err(anyhow!("Wrong nonce {}", inbound.nonce));
+                    }
+                    state.inbound_nonce = inbound.nonce;
+                }
+                UnboundedGenericCommitment::EVM(Commitment::StatusReport(report)) => {
+                    if report.nonce != state.reported_nonce + 1 {
+                        return Err(anyhow!("Wrong batch nonce {}", report.nonce));
+                    }
+                    state.reported_nonce = report.nonce;
+                }
+                UnboundedGenericCommitment::EVM(Commitment::BaseFeeUpdate(update)) => {
+                    state.base_fee_block = Some(update.evm_block_number);
+                }
+                _ => return Err(anyhow!("Unexpected commitment")),
+            }
+            state.submitted.push(commitment);
+            Ok(InboundCommitmentStatus {
+                approval_sent: true,
+                commitment_submitted: true,
+            })
+        }
+    }
+
+    fn meta(address: Address, block_number: u64) -> LogMeta {
+        LogMeta {
+            address,
+            block_number: block_number.into(),
+            block_hash: Default::default(),
+            transaction_hash: Default::default(),
+            transaction_index: Default::default(),
+            log_index: Default::default(),
+        }
+    }
+
+    async fn relay(sora: &FakeSora, evm: &FakeEvm) -> SubstrateMessagesRelay<FakeSora, FakeEvm> {
+        SubstrateMessagesRelay::new(sora.clone(), evm.clone(), ecdsa::Pair::from_seed(&[4; 32]))
+            .await
+            .unwrap()
+    }
+
+    fn inbound_nonces(sora: &FakeSora) -> Vec<u64> {
+        sora.state()
+            .submitted
+            .iter()
+            .filter_map(|commitment| match commitment {
+                UnboundedGenericCommitment::EVM(Commitment::Inbound(inbound)) => {
+                    Some(inbound.nonce)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn fails_without_registered_channel() {
+        let res = SubstrateMessagesRelay::new(
+            FakeSora::default(),
+            FakeEvm::default(),
+            ecdsa::Pair::from_seed(&[4; 32]),
+        )
+        .await;
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn relays_messages_after_sora_nonce() {
+        let (sora, evm) = (FakeSora::with_channel(), FakeEvm::default());
+        for nonce in 1..=3 {
+            evm.send_message(nonce, nonce);
+        }
+        evm.state().finalized_block = 5;
+        sora.state().inbound_nonce = 1;
+        let mut relay = relay(&sora, &evm).await;
+        relay.handle_messages().await.unwrap();
+        assert_eq!(inbound_nonces(&sora), vec![2, 3]);
+        let UnboundedGenericCommitment::EVM(Commitment::Inbound(inbound)) =
+            &sora.state().submitted[0]
+        else {
+            panic!("inbound commitment expected");
+        };
+        assert_eq!(inbound.channel, CHANNEL);
+        assert_eq!(inbound.block_number, 2);
+        assert_eq!(inbound.payload.to_vec(), vec![2]);
+
+        // Scanned blocks are not queried again
+        evm.send_message(4, 6);
+        evm.state().finalized_block = 6;
+        relay.handle_messages().await.unwrap();
+        assert_eq!(inbound_nonces(&sora), vec![2, 3, 4]);
+    }
+
+    /// Checkpoints kept in memory and shared with the test.
+    #[derive(Clone, Default)]
+    struct MemoryCheckpoints(Arc<Mutex<BTreeMap<String, u64>>>);
+
+    impl CheckpointStore for MemoryCheckpoints {
+        fn load(&self, key: &str) -> AnyResult<Option<u64>> {
+            Ok(self.0.lock().unwrap().get(key).copied())
+        }
+
+        fn store(&self, key: &str, block: u64) -> AnyResult<()> {
+            self.0.lock().unwrap().insert(key.to_string(), block);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn saves_checkpoint_of_handled_blocks() {
+        let (sora, evm) = (FakeSora::with_channel(), FakeEvm::default());
+        evm.send_message(1, 2);
+        evm.state().finalized_block = 5;
+        let checkpoints = MemoryCheckpoints::default();
+        let mut relay = relay(&sora, &evm)
+            .await
+            .with_checkpoint_store(checkpoints.clone());
+        relay.handle_messages().await.unwrap();
+        assert_eq!(inbound_nonces(&sora), vec![1]);
+        let saved = checkpoints
+            .0
+            .lock()
+            .unwrap()
+            .values()
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(saved, vec![5]);
+    }
+
+    #[tokio::test]
+    async fn dry_run_does_not_save_checkpoint() {
+        let (sora, evm) = (FakeSora::with_channel(), FakeEvm::default());
+        evm.send_message(1, 2);
+        evm.state().finalized_block = 5;
+        let checkpoints = MemoryCheckpoints::default();
+        let mut relay = relay(&sora, &evm)
+            .await
+            .with_checkpoint_store(checkpoints.clone());
+        relay.dry_run = true;
+        relay.handle_messages().await.unwrap();
+        assert!(checkpoints.0.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn reports_dispatched_batches() {
+        let (sora, evm) = (FakeSora::with_channel(), FakeEvm::default());
+        evm.dispatch_batch(1, &[true, false, true], 2);
+        evm.dispatch_batch(2, &[false], 3);
+        evm.state().finalized_block = 5;
+        let mut relay = relay(&sora, &evm).await;
+        relay.handle_messages().await.unwrap();
+        let state = sora.state();
+        let reports = state
+            .submitted
+            .iter()
+            .filter_map(|commitment| match commitment {
+                UnboundedGenericCommitment::EVM(Commitment::StatusReport(report)) => Some(report),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].nonce, 1);
+        assert_eq!(reports[0].results.to_vec(), vec![true, false, true]);
+        assert_eq!(reports[0].relayer, H160([3; 20]));
+        assert_eq!(reports[1].results.to_vec(), vec![false]);
+    }
+
+    #[tokio::test]
+    async fn updates_base_fee_every_ten_blocks() {
+        let (sora, evm) = (FakeSora::with_channel(), FakeEvm::default());
+        {
+            let mut state = evm.state();
+            state.finalized_block = 25;
+            state.base_fees.insert(20, 7.into());
+            state.base_fees.insert(30, 8.into());
+        }
+        let mut relay = relay(&sora, &evm).await;
+        relay.handle_messages().await.unwrap();
+        assert_eq!(sora.state().base_fee_block, Some(20));
+
+        evm.state().finalized_block = 29;
+        relay.handle_messages().await.unwrap();
+        assert_eq!(sora.state().submitted.len(), 1);
+
+        evm.state().finalized_block = 30;
+        relay.handle_messages().await.unwrap();
+        let state = sora.state();
+        assert_eq!(state.submitted.len(), 2);
+        let UnboundedGenericCommitment::EVM(Commitment::BaseFeeUpdate(update)) =
+            &state.submitted[1]
+        else {
+            panic!("base fee update expected");
+        };
+        assert_eq!(update.new_base_fee, U256::from(8));
+        assert_eq!(update.evm_block_number, 30);
+    }
+
+    fn events(logs: &[(u64, Address)]) -> Vec<(u64, LogMeta)> {
+        logs.iter()
+            .enumerate()
+            .map(|(i, (nonce, address))| (*nonce, meta(*address, i as u64)))
+            .collect()
+    }
+
+    fn select(logs: &[(u64, Address)], nonce: u64) -> Vec<u64> {
+        next_in_sequence(events(logs), CHANNEL, nonce, |nonce| *nonce)
+            .into_iter()
+            .map(|(nonce, _)| nonce)
+            .collect()
+    }
+
+    #[test]
+    fn selects_events_after_accepted_nonce() {
+        let logs = [(1, CHANNEL), (2, CHANNEL), (3, CHANNEL), (4, CHANNEL)];
+        assert_eq!(select(&logs, 0), vec![1, 2, 3, 4]);
+        assert_eq!(select(&logs, 2), vec![3, 4]);
+    }
+
+    #[test]
+    fn stops_at_nonce_gap() {
+        let logs = [(1, CHANNEL), (2, CHANNEL), (4, CHANNEL), (5, CHANNEL)];
+        assert_eq!(select(&logs, 0), vec![1, 2]);
+    }
+
+    #[test]
+    fn skips_duplicates_and_other_contracts() {
+        let other = H160([2; 20]);
+        let logs = [
+            (1, other),
+            (1, CHANNEL),
+            (1, CHANNEL),
+            (2, other),
+            (2, CHANNEL),
+        ];
+        assert_eq!(select(&logs, 0), vec![1, 2]);
+    }
+
+    #[test]
+    fn selects_nothing_when_sora_is_ahead() {
+        let logs = [(1, CHANNEL), (2, CHANNEL)];
+        assert!(select(&logs, 5).is_empty());
+    }
+}
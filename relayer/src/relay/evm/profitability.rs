@@ -0,0 +1,153 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bridge_types::U256;
+
+/// When to submit batches which cost more gas than the fees collected for them.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum ProfitabilityPolicy {
+    /// Submit every batch
+    #[default]
+    Always,
+    /// Postpone unprofitable batches until they become profitable
+    Profitable,
+    /// Postpone unprofitable batches, but not longer than given duration
+    MaxDelay(Duration),
+}
+
+/// Decides whether a batch should be submitted now. Since batches are
+/// processed in nonce order, postponed batch also holds back the next ones.
+#[derive(Clone, Debug, Default)]
+pub struct ProfitabilityGuard {
+    policy: ProfitabilityPolicy,
+    /// Nonce of the postponed batch and time it was postponed first
+    postponed: Arc<Mutex<Option<(u64, Instant)>>>,
+}
+
+impl ProfitabilityGuard {
+    pub fn new(policy: ProfitabilityPolicy) -> Self {
+        Self {
+            policy,
+            postponed: Default::default(),
+        }
+    }
+
+    /// Returns true if batch profitability should be checked before submit.
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self.policy, ProfitabilityPolicy::Always)
+    }
+
+    /// Returns true if batch with given nonce, estimated cost and collected
+    /// fees should be submitted now.
+    pub fn should_send(&self, nonce: u64, cost: U256, fees: U256) -> bool {
+        let mut postponed = self.postponed.lock().expect("poisoned lock");
+        if cost <= fees {
+            *postponed = None;
+            return true;
+        }
+        let since = match *postponed {
+            Some((postponed_nonce, since)) if postponed_nonce == nonce => since,
+            _ => {
+                let now = Instant::now();
+                *postponed = Some((nonce, now));
+                now
+            }
+        };
+        let send = match self.policy {
+            ProfitabilityPolicy::Always => true,
+            ProfitabilityPolicy::Profitable => false,
+            ProfitabilityPolicy::MaxDelay(delay) => since.elapsed() >= delay,
+        };
+        if send {
+            warn!(
+                "Sending unprofitable batch {}: cost {} > fees {}",
+                nonce, cost, fees
+            );
+            *postponed = None;
+        } else {
+            info!(
+                "Postponing unprofitable batch {} for {:?}: cost {} > fees {}",
+                nonce,
+                since.elapsed(),
+                cost,
+                fees
+            );
+        }
+        send
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn always_sends_unprofitable_batches() {
+        let guard = ProfitabilityGuard::new(ProfitabilityPolicy::Always);
+        assert!(!guard.is_enabled());
+        assert!(guard.should_send(1, 100.into(), 10.into()));
+        assert!(guard.should_send(2, 10.into(), 100.into()));
+    }
+
+    #[test]
+    fn profitable_postpones_unprofitable_batches() {
+        let guard = ProfitabilityGuard::new(ProfitabilityPolicy::Profitable);
+        assert!(guard.is_enabled());
+        assert!(!guard.should_send(1, 100.into(), 10.into()));
+        assert!(!guard.should_send(1, 100.into(), 10.into()));
+        // Batch is sent once collected fees cover the cost
+        assert!(guard.should_send(1, 100.into(), 100.into()));
+        assert!(guard.should_send(2, 10.into(), 100.into()));
+    }
+
+    #[test]
+    fn max_delay_sends_batch_after_delay() {
+        let delay = Duration::from_millis(50);
+        let guard = ProfitabilityGuard::new(ProfitabilityPolicy::MaxDelay(delay));
+        assert!(guard.is_enabled());
+        assert!(!guard.should_send(1, 100.into(), 10.into()));
+        std::thread::sleep(delay);
+        assert!(guard.should_send(1, 100.into(), 10.into()));
+        // Delay starts again for the next batch
+        assert!(!guard.should_send(2, 100.into(), 10.into()));
+    }
+
+    #[test]
+    fn max_delay_restarts_for_another_nonce() {
+        let delay = Duration::from_millis(50);
+        let guard = ProfitabilityGuard::new(ProfitabilityPolicy::MaxDelay(delay));
+        assert!(!guard.should_send(1, 100.into(), 10.into()));
+        std::thread::sleep(delay);
+        assert!(!guard.should_send(2, 100.into(), 10.into()));
+    }
+}
@@ -28,51 +28,67 @@
 // STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
 // USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
+use std::collections::BTreeSet;
+
+use super::batch::{message_to_sign, submit_call};
+use super::profitability::{ProfitabilityGuard, ProfitabilityPolicy};
+use crate::ethereum::fees::FeeStrategy;
+use crate::ethereum::gas::GasRecord;
 use crate::ethereum::EthLogDecode;
 use crate::ethereum::SignedClientInner;
 use crate::ethereum::UnsignedClientInner;
+use crate::health::LoopHeartbeat;
+use crate::history::{EntryKind, HistoryEntry};
+use crate::metrics::RelayMetrics;
 use crate::prelude::*;
+use crate::relay::retry::RetryBackoff;
+use crate::relay::traits::{
+    has_enough_approvals, peer_approvals, pending_nonces, ApprovalStore, EvmBatchSubmitter,
+    EvmInboundChannelReader, OutboundChannelReader,
+};
 use crate::substrate::MaxU32;
-use crate::substrate::{BlockNumberOrHash, UnboundedGenericCommitment};
+use crate::substrate::UnboundedGenericCommitment;
 use bridge_types::evm::OutboundCommitment;
-use bridge_types::GenericNetworkId;
-use bridge_types::{Address, U256};
+use bridge_types::{Address, EVMChainId, GenericNetworkId, U256};
 use ethereum_gen::ChannelHandler;
 use ethers::abi::RawLog;
-use ethers::abi::Tokenize;
 use ethers::providers::Middleware;
+use ethers::types::TransactionReceipt;
 use sp_core::{ecdsa, H256};
-use std::time::Duration;
 
-pub struct RelayBuilder {
-    sender: Option<SubUnsignedClient<MainnetConfig>>,
-    receiver: Option<EthUnsignedOrSignedClient>,
-    channel: Option<Address>,
+pub struct RelayBuilder<S, E> {
+    sender: Option<S>,
+    receiver: Option<E>,
     signer: Option<ecdsa::Pair>,
+    profitability: ProfitabilityPolicy,
 }
 
-impl Default for RelayBuilder {
+impl<S, E> Default for RelayBuilder<S, E> {
     fn default() -> Self {
         Self {
             sender: None,
             receiver: None,
-            channel: None,
             signer: None,
+            profitability: Default::default(),
         }
     }
 }
 
-impl RelayBuilder {
+impl<S, E> RelayBuilder<S, E>
+where
+    S: OutboundChannelReader + ApprovalStore + EvmInboundChannelReader,
+    E: EvmBatchSubmitter,
+{
     pub fn new() -> Self {
         Default::default()
     }
 
-    pub fn with_sender_client(mut self, sender: SubUnsignedClient<MainnetConfig>) -> Self {
+    pub fn with_sender_client(mut self, sender: S) -> Self {
         self.sender = Some(sender);
         self
     }
 
-    pub fn with_receiver_client(mut self, receiver: EthUnsignedOrSignedClient) -> Self {
+    pub fn with_receiver_client(mut self, receiver: E) -> Self {
         self.receiver = Some(receiver);
         self
     }
@@ -82,177 +98,293 @@ impl RelayBuilder {
         self
     }
 
-    pub fn with_channel_contract(mut self, address: Address) -> Self {
-        self.channel = Some(address);
+    pub fn with_profitability_policy(mut self, policy: ProfitabilityPolicy) -> Self {
+        self.profitability = policy;
         self
     }
 
-    pub async fn build(self) -> AnyResult<Relay> {
+    pub async fn build(self) -> AnyResult<Relay<S, E>> {
         let sender = self.sender.expect("sender client is needed");
         let receiver = self.receiver.expect("receiver client is needed");
-        let channel_address = self.channel.expect("inbound channel address is needed");
-        let inbound_channel = receiver.as_ref().map_either(
-            |l| ChannelHandler::new(channel_address, l.inner()),
-            |r| ChannelHandler::new(channel_address, r.inner()),
-        );
-        let sub_network_id = sender.constant_fetch_or_default(
-            &runtime::constants()
-                .bridge_inbound_channel()
-                .this_network_id(),
-        )?;
+        let sub_network_id = OutboundChannelReader::network_id(&sender)?;
+        let evm_network_id = GenericNetworkId::EVM(receiver.chain_id().await?);
         Ok(Relay {
-            evm_network_id: either::for_both!(&receiver, r => r.chainid().await?.into()),
+            metrics: RelayMetrics::new(sub_network_id, evm_network_id),
+            heartbeat: LoopHeartbeat::register(sub_network_id, evm_network_id),
+            evm_network_id,
             sub_network_id,
-            sub: sender,
-            evm: receiver,
-            inbound_channel,
+            sender,
+            receiver,
             signer: self.signer,
+            profitability: ProfitabilityGuard::new(self.profitability),
         })
     }
 }
 
+/// Channel contract on EVM network and the client which submits batches to it.
 #[derive(Clone)]
-pub struct Relay {
-    sub: SubUnsignedClient<MainnetConfig>,
+pub struct EvmChannel {
     evm: EthUnsignedOrSignedClient,
-    inbound_channel: Either<ChannelHandler<UnsignedClientInner>, ChannelHandler<SignedClientInner>>,
-    evm_network_id: GenericNetworkId,
-    sub_network_id: GenericNetworkId,
-    signer: Option<ecdsa::Pair>,
+    channel: Either<ChannelHandler<UnsignedClientInner>, ChannelHandler<SignedClientInner>>,
+    fees: FeeStrategy,
 }
 
-// Relays batches of messages from Substrate to Ethereum.
-impl Relay {
-    fn submit_message_gas(&self, messages_total_gas: U256) -> U256 {
+impl EvmChannel {
+    pub fn new(evm: EthUnsignedOrSignedClient, address: Address, fees: FeeStrategy) -> Self {
+        let channel = evm.as_ref().map_either(
+            |l| ChannelHandler::new(address, l.inner()),
+            |r| ChannelHandler::new(address, r.inner()),
+        );
+        Self { evm, channel, fees }
+    }
+
+    fn submit_message_gas(messages_total_gas: U256) -> U256 {
         messages_total_gas.saturating_add(260000.into())
     }
 
-    async fn inbound_channel_nonce(&self) -> AnyResult<u64> {
-        let nonce = either::for_both!(&self.inbound_channel, c => c.batch_nonce().call().await?);
+    /// Filled contract call which submits the batch from the relayer account.
+    async fn batch_call(
+        &self,
+        commitment: &OutboundCommitment<MaxU32, MaxU32>,
+        approvals: Vec<ecdsa::Signature>,
+    ) -> AnyResult<(
+        &EthSignedClient,
+        ethers::contract::ContractCall<SignedClientInner, ()>,
+    )> {
+        let (Some(evm), Some(channel)) = (self.evm.as_ref().right(), self.channel.as_ref().right())
+        else {
+            return Err(anyhow!("Don't have a relayer account private key"));
+        };
+        let mut call = self
+            .fees
+            .prepare_call(submit_call(channel, commitment, approvals))
+            .await?;
+        debug!("Fill submit messages");
+        evm.fill_transaction(&mut call.tx, call.block).await?;
+        debug!("Messages total gas: {}", commitment.total_max_gas);
+        call.tx
+            .set_gas(Self::submit_message_gas(commitment.total_max_gas));
+        Ok((evm, call))
+    }
+}
+
+#[async_trait::async_trait]
+impl EvmBatchSubmitter for EvmChannel {
+    async fn chain_id(&self) -> AnyResult<EVMChainId> {
+        either::for_both!(&self.evm, e => e.chainid().await)
+    }
+
+    async fn batch_nonce(&self) -> AnyResult<u64> {
+        let nonce = either::for_both!(&self.channel, c => c.batch_nonce().call().await?);
         Ok(nonce as u64)
     }
 
-    async fn outbound_channel_nonce(&self) -> AnyResult<u64> {
-        let nonce = self
-            .sub
-            .storage_fetch_or_default(
-                &mainnet_runtime::storage()
-                    .bridge_outbound_channel()
-                    .channel_nonces(&self.evm_network_id),
-                BlockNumberOrHash::Finalized,
-            )
-            .await?;
-        Ok(nonce)
+    fn can_submit(&self) -> bool {
+        self.evm.is_right()
     }
 
-    pub fn prepare_evm_signed_message(msg: H256) -> H256 {
-        let mut prefix = b"\x19Ethereum Signed Message:\n32".to_vec();
-        prefix.extend(msg.as_bytes());
-        sp_core::keccak_256(&prefix).into()
+    async fn gas_price(&self) -> AnyResult<U256> {
+        either::for_both!(&self.evm, evm => {
+            let base_fee = evm
+                .get_block(ethers::types::BlockNumber::Latest)
+                .await?
+                .and_then(|block| block.base_fee_per_gas);
+            match base_fee {
+                Some(base_fee) => Ok(base_fee),
+                None => Ok(evm.get_gas_price().await?),
+            }
+        })
     }
 
-    async fn send_commitment(
+    async fn estimate_batch(
         &self,
-        commitment: OutboundCommitment<MaxU32, MaxU32>,
-        signed_message: H256,
-    ) -> AnyResult<()> {
-        let (Some(evm), Some(channel)) = (
-            self.evm.as_ref().right(),
-            self.inbound_channel.as_ref().right(),
-        ) else {
-            log::debug!("Don't have a relayer account private key, skipping commitment send");
-            return Ok(());
-        };
-        let batch = Self::prepare_batch(&commitment);
-        let messages_total_gas = commitment.total_max_gas;
-        let approvals = self
-            .sub
-            .bridge_approvals(&self.evm_network_id, signed_message)
-            .await?;
-        let (v, r, s) = approvals
-            .into_iter()
-            .map(|approval| {
-                (
-                    approval.0[64],
-                    approval.0[..32].try_into().unwrap(),
-                    approval.0[32..64].try_into().unwrap(),
-                )
-            })
-            .fold((vec![], vec![], vec![]), |mut vrs, (v, r, s)| {
-                vrs.0.push(v + 27);
-                vrs.1.push(r);
-                vrs.2.push(s);
-                vrs
-            });
-        let mut call: ethers::contract::ContractCall<_, ()> =
-            channel.submit(batch, v, r, s).legacy();
-
-        debug!("Fill submit messages");
-        evm.fill_transaction(&mut call.tx, call.block).await?;
-        debug!("Messages total gas: {}", messages_total_gas);
-        call.tx.set_gas(self.submit_message_gas(messages_total_gas));
+        commitment: &OutboundCommitment<MaxU32, MaxU32>,
+        approvals: Vec<ecdsa::Signature>,
+    ) -> AnyResult<U256> {
+        let (_, call) = self.batch_call(commitment, approvals).await?;
         debug!("Check submit messages");
         call.call().await?;
-        evm.save_gas_price(&call, "submit-messages").await?;
+        Ok(call.estimate_gas().await?)
+    }
+
+    async fn submit_batch(
+        &self,
+        commitment: &OutboundCommitment<MaxU32, MaxU32>,
+        approvals: Vec<ecdsa::Signature>,
+        estimated_gas: U256,
+    ) -> AnyResult<Option<TransactionReceipt>> {
+        let (evm, call) = self.batch_call(commitment, approvals).await?;
+        if crate::dry_run::is_enabled() {
+            evm.dry_run(&call).await?;
+            return Ok(None);
+        }
         debug!("Send submit messages");
-        let tx = call.send().await?;
-        debug!("Wait for confirmations submit messages: {:?}", tx);
-        let tx = tx.confirmations(1).await?;
+        let function = call.function.name.clone();
+        let tx = self.fees.send(evm, call.tx).await?;
         debug!("Submit messages: {:?}", tx);
-        if let Some(tx) = tx {
-            for log in tx.logs {
-                let raw_log = RawLog {
-                    topics: log.topics.clone(),
-                    data: log.data.to_vec(),
-                };
-                if let Ok(log) =
-                    <ethereum_gen::channel_handler::BatchDispatchedFilter as EthLogDecode>::decode_log(&raw_log)
-                {
-                    info!("Batch dispatched: {:?}", log);
-                }
+        evm.save_gas_record(&GasRecord::new(
+            &function,
+            Some(commitment.nonce),
+            estimated_gas,
+            &tx,
+        ));
+        for log in tx.logs.iter() {
+            let raw_log = RawLog {
+                topics: log.topics.clone(),
+                data: log.data.to_vec(),
+            };
+            if let Ok(log) =
+                <ethereum_gen::channel_handler::BatchDispatchedFilter as EthLogDecode>::decode_log(
+                    &raw_log,
+                )
+            {
+                info!("Batch dispatched: {:?}", log);
             }
         }
-        Ok(())
+        Ok(Some(tx))
     }
+}
 
-    fn prepare_batch(
-        commitment: &OutboundCommitment<MaxU32, MaxU32>,
-    ) -> ethereum_gen::channel_handler::Batch {
-        ethereum_gen::channel_handler::Batch {
-            nonce: commitment.nonce.into(),
-            total_max_gas: commitment.total_max_gas.into(),
-            messages: commitment
-                .messages
-                .iter()
-                .map(|message| ethereum_gen::channel_handler::Message {
-                    max_gas: message.max_gas.into(),
-                    target: message.target.into(),
-                    payload: message.payload.to_vec().into(),
-                })
-                .collect(),
-        }
+#[derive(Clone)]
+pub struct Relay<S, E> {
+    sender: S,
+    receiver: E,
+    evm_network_id: GenericNetworkId,
+    sub_network_id: GenericNetworkId,
+    signer: Option<ecdsa::Pair>,
+    profitability: ProfitabilityGuard,
+    metrics: RelayMetrics,
+    heartbeat: LoopHeartbeat,
+}
+
+// Relays batches of messages from Substrate to Ethereum.
+impl<S, E> Relay<S, E>
+where
+    S: OutboundChannelReader + ApprovalStore + EvmInboundChannelReader,
+    E: EvmBatchSubmitter,
+{
+    async fn inbound_channel_nonce(&self) -> AnyResult<u64> {
+        self.receiver.batch_nonce().await
     }
 
-    fn prepare_message_to_sign(&self, commitment: &OutboundCommitment<MaxU32, MaxU32>) -> H256 {
-        let batch = Self::prepare_batch(&commitment);
+    async fn outbound_channel_nonce(&self) -> AnyResult<u64> {
+        self.sender.outbound_nonce(self.evm_network_id).await
+    }
 
-        let tokens = batch.clone().into_tokens();
-        let tokens = ethers::abi::Token::Tuple(tokens);
-        let encoded_batch = ethers::abi::encode(&[tokens]);
-        let batch_hash = sp_runtime::traits::Keccak256::hash(&encoded_batch);
-        let message = sp_runtime::traits::Keccak256::hash_of(&(
-            self.sub_network_id,
-            self.evm_network_id,
-            batch_hash,
-        ));
-        let message = Self::prepare_evm_signed_message(message);
-        message
+    async fn peers(&self) -> AnyResult<BTreeSet<ecdsa::Public>> {
+        self.sender.approval_peers(self.evm_network_id).await
+    }
+
+    async fn approvals(
+        &self,
+        peers: &BTreeSet<ecdsa::Public>,
+        message: H256,
+    ) -> AnyResult<Vec<ecdsa::Signature>> {
+        let approvals = self.sender.approvals(self.evm_network_id, message).await?;
+        peer_approvals(approvals, peers, message)
     }
 
+    /// Estimates fees paid on SORA for the batch messages from the base fee
+    /// known to SORA.
+    async fn batch_fees(&self, total_max_gas: U256) -> AnyResult<U256> {
+        let GenericNetworkId::EVM(chain_id) = self.evm_network_id else {
+            return Err(anyhow!("EVM network id is expected"));
+        };
+        let base_fee = self.sender.base_fee(chain_id).await?.unwrap_or_default();
+        Ok(base_fee.saturating_mul(total_max_gas))
+    }
+
+    /// Returns true if sent, false if the batch is postponed by profitability policy.
+    async fn send_commitment(
+        &self,
+        commitment: OutboundCommitment<MaxU32, MaxU32>,
+        commitment_hash: H256,
+        approvals: Vec<ecdsa::Signature>,
+    ) -> AnyResult<bool> {
+        if !self.receiver.can_submit() {
+            log::debug!("Don't have a relayer account private key, skipping commitment send");
+            return Ok(true);
+        }
+        let batch_nonce = commitment.nonce;
+        let estimated_gas = self
+            .receiver
+            .estimate_batch(&commitment, approvals.clone())
+            .await?;
+        if self.profitability.is_enabled() {
+            let cost = estimated_gas.saturating_mul(self.receiver.gas_price().await?);
+            let fees = self.batch_fees(commitment.total_max_gas).await?;
+            if !self.profitability.should_send(batch_nonce, cost, fees) {
+                return Ok(false);
+            }
+        }
+        let res = self
+            .receiver
+            .submit_batch(&commitment, approvals, estimated_gas)
+            .await;
+        let history_entry = self.history_entry(EntryKind::EvmBatch, batch_nonce, commitment_hash);
+        let tx = match res {
+            Ok(Some(tx)) => {
+                history_entry.with_receipt(&tx).record();
+                tx
+            }
+            Ok(None) => return Ok(true),
+            Err(err) => {
+                history_entry.with_error(&err).record();
+                return Err(err);
+            }
+        };
+        if tx.status == Some(0u64.into()) {
+            return Err(anyhow!(
+                "Submit messages transaction {:?} failed",
+                tx.transaction_hash
+            ));
+        }
+        self.metrics.commitment_submitted();
+        if let Some(gas_used) = tx.gas_used {
+            self.metrics.evm_gas_spent(gas_used.as_u64());
+        }
+        Ok(true)
+    }
+
+    /// Sends approval of the message if there are still not enough approvals.
+    async fn approve(
+        &self,
+        signer: &ecdsa::Pair,
+        nonce: u64,
+        commitment_hash: H256,
+        message: H256,
+    ) -> AnyResult<()> {
+        let peers = self.peers().await?;
+        let approvals = self.approvals(&peers, message).await?;
+        let is_already_approved = approvals
+            .iter()
+            .filter_map(|approval| approval.recover_prehashed(&message.0))
+            .any(|public| signer.public() == public);
+        if has_enough_approvals(&approvals, &peers) || is_already_approved {
+            return Ok(());
+        }
+        info!("Sending approval");
+        let res = self
+            .sender
+            .submit_approval(
+                self.evm_network_id,
+                message,
+                signer.sign_prehashed(&message.0),
+            )
+            .await;
+        self.history_entry(EntryKind::Approval, nonce, commitment_hash)
+            .record_extrinsic(&res);
+        res?;
+        self.metrics.approval_sent();
+        Ok(())
+    }
+
+    /// Returns false if the commitment is postponed and next ones should wait.
     async fn approve_and_send_commitment(
         &self,
         commitment: UnboundedGenericCommitment,
-    ) -> AnyResult<()> {
+    ) -> AnyResult<bool> {
+        let commitment_hash = commitment.hash();
         let UnboundedGenericCommitment::EVM(bridge_types::evm::Commitment::Outbound(commitment)) =
             commitment
         else {
@@ -260,20 +392,32 @@ impl Relay {
                 "Invalid commitment. EVM outbound commitment is expected"
             ));
         };
-        let message = self.prepare_message_to_sign(&commitment);
+        let message = message_to_sign(self.sub_network_id, self.evm_network_id, &commitment);
         if let Some(signer) = &self.signer {
-            self.sub
-                .approve_message(signer.clone(), self.evm_network_id, message)
+            self.approve(signer, commitment.nonce, commitment_hash, message)
                 .await?;
         }
-        if self
-            .sub
-            .should_send_commitment(&self.evm_network_id, message)
-            .await?
-        {
-            self.send_commitment(commitment, message).await?;
+        let peers = self.peers().await?;
+        let approvals = self.approvals(&peers, message).await?;
+        if has_enough_approvals(&approvals, &peers) {
+            match self
+                .send_commitment(commitment, commitment_hash, approvals)
+                .await
+            {
+                Ok(sent) => return Ok(sent),
+                Err(err) => {
+                    self.metrics.commitment_failed();
+                    return Err(err);
+                }
+            }
         }
-        Ok(())
+        Ok(true)
+    }
+
+    fn history_entry(&self, kind: EntryKind, nonce: u64, commitment_hash: H256) -> HistoryEntry {
+        HistoryEntry::new(kind, self.sub_network_id, self.evm_network_id)
+            .with_nonce(nonce)
+            .with_commitment_hash(commitment_hash)
     }
 
     fn signer_public(&self) -> AnyResult<ecdsa::Public> {
@@ -287,44 +431,274 @@ impl Relay {
 
     async fn is_peer(&self) -> AnyResult<bool> {
         let signer_public = self.signer_public()?;
-        let peers = self.sub.bridge_peers(&self.evm_network_id).await?;
-        Ok(peers.iter().any(|public| signer_public == *public))
+        let peers = self.peers().await?;
+        Ok(peers.contains(&signer_public))
+    }
+
+    async fn relay_commitments(&self) -> AnyResult<()> {
+        let inbound_nonce = self.inbound_channel_nonce().await?;
+        let outbound_nonce = self.outbound_channel_nonce().await?;
+        self.metrics.set_nonces(inbound_nonce, outbound_nonce);
+        let nonces = pending_nonces(inbound_nonce, outbound_nonce);
+        if nonces.is_empty() {
+            return Ok(());
+        }
+        info!(
+            "Submit commitments from {} to {}",
+            inbound_nonce, outbound_nonce
+        );
+        for nonce in nonces {
+            let offchain_data = self.sender.commitment(self.evm_network_id, nonce).await?;
+            if !self
+                .approve_and_send_commitment(offchain_data.commitment)
+                .await?
+            {
+                break;
+            }
+        }
+        Ok(())
     }
 
     pub async fn run(self) -> AnyResult<()> {
         if self.signer.is_some() && !self.is_peer().await? {
             return Err(anyhow::anyhow!("Provided signer key is not a peer"));
         }
-        let mut interval = tokio::time::interval(Duration::from_secs(6));
+        let mut interval = tokio::time::interval(self.sender.average_block_time());
+        let mut backoff = RetryBackoff::new(self.sender.average_block_time());
         loop {
             interval.tick().await;
-            let inbound_nonce = self.inbound_channel_nonce().await?;
-            let outbound_nonce = self.outbound_channel_nonce().await?;
-            if inbound_nonce >= outbound_nonce {
-                if inbound_nonce > outbound_nonce {
-                    error!(
-                        "Inbound channel nonce is higher than outbound channel nonce: {} > {}",
-                        inbound_nonce, outbound_nonce
-                    );
-                }
-                continue;
-            }
-            info!(
-                "Submit commitments from {} to {}",
-                inbound_nonce, outbound_nonce
-            );
-            for nonce in (inbound_nonce + 1)..=outbound_nonce {
-                let offchain_data = self
-                    .sub
-                    .commitment_with_nonce(
-                        self.evm_network_id.into(),
-                        nonce,
-                        BlockNumberOrHash::Finalized,
-                    )
-                    .await?;
-                self.approve_and_send_commitment(offchain_data.commitment)
-                    .await?;
-            }
+            self.heartbeat.beat();
+            backoff.handle(self.relay_commitments().await).await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relay::fakes::{commitment, wait_until, FakeChain, FakeEvmChannel};
+    use bridge_types::SubNetworkId;
+    use ethereum_gen::channel_handler::SubmitCall;
+    use ethers::abi::{AbiDecode, Tokenize};
+    use ethers::providers::Provider;
+    use ethers::signers::{LocalWallet, Signer};
+    use ethers::types::{RecoveryMessage, Signature};
+    use std::sync::Arc;
+
+    const PEER_SEEDS: [u8; 3] = [1, 2, 3];
+    const SORA: GenericNetworkId = GenericNetworkId::Sub(SubNetworkId::Mainnet);
+    const CHAIN_ID: u64 = 5;
+
+    fn evm() -> GenericNetworkId {
+        GenericNetworkId::EVM(CHAIN_ID.into())
+    }
+
+    fn pair(seed: u8) -> ecdsa::Pair {
+        ecdsa::Pair::from_seed(&[seed; 32])
+    }
+
+    /// Message which peers approve for the commitment with the given nonce.
+    fn message(nonce: u64) -> H256 {
+        let UnboundedGenericCommitment::EVM(bridge_types::evm::Commitment::Outbound(commitment)) =
+            commitment(nonce)
+        else {
+            unreachable!()
+        };
+        message_to_sign(SORA, evm(), &commitment)
+    }
+
+    /// Chains trusting given peers, the first peer runs the relay.
+    async fn setup(
+        peers: &[ecdsa::Pair],
+    ) -> (FakeChain, FakeEvmChannel, Relay<FakeChain, FakeEvmChannel>) {
+        let sender = FakeChain::new(SORA);
+        let receiver = FakeEvmChannel::new(CHAIN_ID.into(), SORA);
+        let public = peers
+            .iter()
+            .map(|peer| peer.public())
+            .collect::<BTreeSet<_>>();
+        sender.state().approval_peers = public.clone();
+        receiver.state().peers = public;
+        let relay = RelayBuilder::new()
+            .with_sender_client(sender.clone())
+            .with_receiver_client(receiver.clone())
+            .with_signer(Some(peers[0].clone()))
+            .build()
+            .await
+            .unwrap();
+        (sender, receiver, relay)
+    }
+
+    #[tokio::test]
+    async fn relays_pending_batches_in_order() {
+        let (sender, receiver, relay) = setup(&[pair(1)]).await;
+        for block in 1..=3 {
+            sender.send_commitment(block);
+        }
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1, 2, 3]);
+        assert!(receiver.state().rejected.is_empty());
+        assert_eq!(sender.state().approvals[&message(2)].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn does_not_relay_batches_after_nonce_gap() {
+        let (sender, receiver, relay) = setup(&[pair(1)]).await;
+        for block in 1..=3 {
+            sender.send_commitment(block);
+        }
+        sender.state().outbound.remove(&2);
+        assert!(relay.relay_commitments().await.is_err());
+        assert_eq!(receiver.state().accepted, vec![1]);
+        assert!(!sender.state().approvals.contains_key(&message(3)));
+    }
+
+    #[tokio::test]
+    async fn relays_only_batches_after_channel_nonce() {
+        let (sender, receiver, relay) = setup(&[pair(1)]).await;
+        for block in 1..=4 {
+            sender.send_commitment(block);
+        }
+        receiver.state().batch_nonce = 2;
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![3, 4]);
+        assert!(!sender.state().approvals.contains_key(&message(2)));
+    }
+
+    #[tokio::test]
+    async fn waits_for_enough_approvals() {
+        let peers = [pair(1), pair(2), pair(3), pair(4)];
+        let (sender, receiver, relay) = setup(&peers).await;
+        sender.send_commitment(1);
+        // Approval from a key which is not a peer is not counted
+        sender
+            .state()
+            .approvals
+            .insert(message(1), vec![pair(5).sign_prehashed(&message(1).0)]);
+
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(sender.state().approvals[&message(1)].len(), 2);
+        assert!(receiver.state().accepted.is_empty());
+
+        // Approval is not sent twice
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(sender.state().approvals[&message(1)].len(), 2);
+        assert!(receiver.state().accepted.is_empty());
+
+        for peer in &peers[1..3] {
+            sender
+                .state()
+                .approvals
+                .get_mut(&message(1))
+                .unwrap()
+                .push(peer.sign_prehashed(&message(1).0));
+        }
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1]);
+        assert!(receiver.state().rejected.is_empty());
+    }
+
+    #[tokio::test]
+    async fn keeps_running_after_transport_error() {
+        let (sender, receiver, relay) = setup(&[pair(1)]).await;
+        sender.send_commitment(1);
+        sender.state().transport_failures = 1;
+        let relay = tokio::spawn(relay.run());
+        wait_until(|| !receiver.state().accepted.is_empty()).await;
+        assert!(!relay.is_finished());
+        relay.abort();
+        assert_eq!(sender.state().transport_failures, 0);
+        assert_eq!(receiver.state().accepted, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn does_not_submit_batch_twice() {
+        let (sender, receiver, relay) = setup(&[pair(1)]).await;
+        sender.send_commitment(1);
+        relay.relay_commitments().await.unwrap();
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1]);
+
+        // Batch is already submitted, e.g. by another relayer, after the nonce was read
+        let commitment = sender.state().outbound[&1].commitment.clone();
+        assert!(relay.approve_and_send_commitment(commitment).await.is_err());
+        assert_eq!(receiver.state().accepted, vec![1]);
+        assert_eq!(sender.state().approvals[&message(1)].len(), 1);
+    }
+
+    fn commitment() -> OutboundCommitment<MaxU32, MaxU32> {
+        OutboundCommitment {
+            nonce: 3,
+            total_max_gas: 300000.into(),
+            messages: vec![bridge_types::evm::Message {
+                target: Address::repeat_byte(2),
+                max_gas: 200000.into(),
+                payload: vec![1, 2, 3].try_into().unwrap(),
+            }]
+            .try_into()
+            .unwrap(),
+        }
+    }
+
+    fn submit_call(
+        commitment: &OutboundCommitment<MaxU32, MaxU32>,
+        approvals: Vec<ecdsa::Signature>,
+    ) -> SubmitCall {
+        let (provider, _mock) = Provider::mocked();
+        let channel = ChannelHandler::new(Address::repeat_byte(1), Arc::new(provider));
+        let call = super::submit_call(&channel, commitment, approvals);
+        SubmitCall::decode(call.calldata().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn submit_call_contains_batch() {
+        let commitment = commitment();
+        let submit = submit_call(&commitment, vec![]);
+        assert_eq!(submit.batch.nonce, 3.into());
+        assert_eq!(submit.batch.total_max_gas, 300000.into());
+        assert_eq!(submit.batch.messages.len(), 1);
+        let message = &submit.batch.messages[0];
+        assert_eq!(message.target, Address::repeat_byte(2));
+        assert_eq!(message.max_gas, 200000.into());
+        assert_eq!(message.payload.to_vec(), vec![1, 2, 3]);
+        assert!(submit.v.is_empty());
+    }
+
+    #[test]
+    fn submitted_approvals_recover_to_peers() {
+        let commitment = commitment();
+        let sub_network_id = GenericNetworkId::Sub(SubNetworkId::Mainnet);
+        let evm_network_id = GenericNetworkId::EVM(5u64.into());
+        let message = message_to_sign(sub_network_id, evm_network_id, &commitment);
+        let approvals = PEER_SEEDS
+            .iter()
+            .map(|seed| ecdsa::Pair::from_seed(&[*seed; 32]).sign_prehashed(&message.0))
+            .collect();
+        let submit = submit_call(&commitment, approvals);
+
+        // Peers sign EIP-191 message with the hash of the submitted batch
+        let batch_hash = sp_runtime::traits::Keccak256::hash(&ethers::abi::encode(&[
+            ethers::abi::Token::Tuple(submit.batch.clone().into_tokens()),
+        ]));
+        let raw_message =
+            sp_runtime::traits::Keccak256::hash_of(&(sub_network_id, evm_network_id, batch_hash));
+        assert_eq!(
+            message.0,
+            ethers::utils::hash_message(raw_message.as_bytes()).0
+        );
+
+        assert_eq!(submit.v.len(), PEER_SEEDS.len());
+        for (i, seed) in PEER_SEEDS.iter().enumerate() {
+            let signature = Signature {
+                r: U256::from_big_endian(&submit.r[i]),
+                s: U256::from_big_endian(&submit.s[i]),
+                v: submit.v[i] as u64,
+            };
+            let signer = signature
+                .recover(RecoveryMessage::Hash(message.0.into()))
+                .unwrap();
+            let peer = LocalWallet::from_bytes(&[*seed; 32]).unwrap();
+            assert_eq!(signer, peer.address());
         }
     }
 }
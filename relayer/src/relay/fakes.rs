@@ -0,0 +1,511 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! In-memory chains implementing [`crate::relay::traits`] for relay tests.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use super::evm::batch::message_to_sign;
+use super::traits::*;
+use crate::prelude::*;
+use crate::substrate::{MaxU32, SubmittedExtrinsic, UnboundedGenericCommitment};
+use bridge_types::evm::OutboundCommitment;
+use bridge_types::types::{AuxiliaryDigest, AuxiliaryDigestItem};
+use bridge_types::{EVMChainId, GenericNetworkId, H160, H256, U256};
+use ethers::types::TransactionReceipt;
+use sp_core::ecdsa;
+use sp_runtime::traits::Keccak256;
+
+/// Proof produced by [`FakeChain`], valid once the light client on the
+/// receiving network knows the block with the MMR leaf of the commitment.
+#[derive(Clone, Debug)]
+pub struct FakeBeefyProof {
+    pub block_number: u64,
+}
+
+#[derive(Default)]
+pub struct FakeChainState {
+    /// Commitments sent by the outbound channel, by nonce.
+    pub outbound: BTreeMap<u64, CommitmentWithBlock>,
+    pub outbound_nonce: u64,
+    /// Peers which approve outbound commitments.
+    pub approval_peers: BTreeSet<ecdsa::Public>,
+    pub approvals: BTreeMap<H256, Vec<ecdsa::Signature>>,
+    pub inbound_nonce: u64,
+    /// Peers which are trusted by the inbound channel.
+    pub peers: BTreeSet<ecdsa::Public>,
+    /// Nonces of commitments accepted by the inbound channel.
+    pub accepted: Vec<u64>,
+    /// Nonces of commitments rejected by the inbound channel.
+    pub rejected: Vec<u64>,
+    /// Nonces of commitments in the transaction pool.
+    pub pool: Vec<u64>,
+    /// Largest number of commitments which were in the pool at the same time.
+    pub max_pool_size: usize,
+    /// Pool checks the nonce against the inbound channel only, so it rejects
+    /// commitments sent while the previous one is in the pool.
+    pub strict_pool: bool,
+    /// Latest produced block, all blocks are produced if not set.
+    pub best_block: Option<u64>,
+    /// Base fee of EVM network known to the chain.
+    pub evm_base_fee: Option<U256>,
+    /// Number of next outbound nonce requests failing like the node restarted.
+    pub transport_failures: u32,
+}
+
+/// Substrate chain with a single outbound and a single inbound channel.
+#[derive(Clone)]
+pub struct FakeChain {
+    network_id: GenericNetworkId,
+    state: Arc<Mutex<FakeChainState>>,
+}
+
+impl FakeChain {
+    pub fn new(network_id: GenericNetworkId) -> Self {
+        Self {
+            network_id,
+            state: Default::default(),
+        }
+    }
+
+    pub fn state(&self) -> MutexGuard<FakeChainState> {
+        self.state.lock().expect("poisoned")
+    }
+
+    /// Adds outbound commitment with the next nonce and returns the nonce.
+    pub fn send_commitment(&self, block_number: u64) -> u64 {
+        let mut state = self.state();
+        state.outbound_nonce += 1;
+        let nonce = state.outbound_nonce;
+        state.outbound.insert(
+            nonce,
+            CommitmentWithBlock {
+                block_number,
+                commitment: commitment(nonce),
+            },
+        );
+        nonce
+    }
+
+    fn accept(&self, commitment: &UnboundedGenericCommitment) -> AnyResult<SubmittedExtrinsic> {
+        let mut state = self.state();
+        let nonce = commitment.nonce();
+        if nonce != state.inbound_nonce + 1 {
+            state.rejected.push(nonce);
+            return Err(anyhow!("Invalid nonce {}", nonce));
+        }
+        state.inbound_nonce = nonce;
+        state.accepted.push(nonce);
+        Ok(SubmittedExtrinsic::default())
+    }
+
+    /// Checks the nonce when the commitment is submitted, like the transaction
+    /// pool does, and includes the commitment once the returned future is
+    /// awaited. Pool accepts the nonce after the last one it has, unless it is
+    /// strict.
+    fn broadcast(&self, commitment: UnboundedGenericCommitment) -> AnyResult<PendingSubmission> {
+        let mut state = self.state();
+        let nonce = commitment.nonce();
+        let expected = match state.pool.last() {
+            Some(pooled) if !state.strict_pool => pooled + 1,
+            Some(_) => state.inbound_nonce,
+            None => state.inbound_nonce + 1,
+        };
+        if nonce != expected {
+            state.rejected.push(nonce);
+            return Err(anyhow!("Invalid nonce {}", nonce));
+        }
+        state.pool.push(nonce);
+        state.max_pool_size = state.max_pool_size.max(state.pool.len());
+        let chain = self.clone();
+        Ok(Box::pin(async move {
+            let res = chain.accept(&commitment);
+            let mut state = chain.state();
+            match res {
+                Ok(_) => state.pool.retain(|pooled| *pooled != nonce),
+                // Commitments after the failed one are dropped from the pool
+                Err(_) => state.pool.retain(|pooled| *pooled < nonce),
+            }
+            res
+        }))
+    }
+
+    fn check_approvals(
+        &self,
+        commitment: &UnboundedGenericCommitment,
+        proof: MultisigProof,
+    ) -> AnyResult<()> {
+        let digest_hash = Keccak256::hash_of(&proof.digest);
+        let peers = self.state().peers.clone();
+        let approvals = peer_approvals(proof.approvals, &peers, digest_hash)?;
+        if !has_enough_approvals(&approvals, &peers) {
+            self.state().rejected.push(commitment.nonce());
+            return Err(anyhow!("Not enough approvals"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct FakeEvmChannelState {
+    pub batch_nonce: u64,
+    /// Peers which are trusted by the channel contract.
+    pub peers: BTreeSet<ecdsa::Public>,
+    /// Nonces of batches accepted by the channel.
+    pub accepted: Vec<u64>,
+    /// Nonces of batches rejected by the channel.
+    pub rejected: Vec<u64>,
+    pub gas_price: U256,
+}
+
+/// Channel contract on EVM network which accepts batches from `source`.
+#[derive(Clone)]
+pub struct FakeEvmChannel {
+    chain_id: EVMChainId,
+    source: GenericNetworkId,
+    state: Arc<Mutex<FakeEvmChannelState>>,
+}
+
+impl FakeEvmChannel {
+    /// Gas estimated for any batch.
+    pub const BATCH_GAS: u64 = 100000;
+
+    pub fn new(chain_id: EVMChainId, source: GenericNetworkId) -> Self {
+        Self {
+            chain_id,
+            source,
+            state: Default::default(),
+        }
+    }
+
+    pub fn state(&self) -> MutexGuard<FakeEvmChannelState> {
+        self.state.lock().expect("poisoned")
+    }
+
+    /// Checks the batch like the channel contract does.
+    fn check(
+        &self,
+        commitment: &OutboundCommitment<MaxU32, MaxU32>,
+        approvals: Vec<ecdsa::Signature>,
+    ) -> AnyResult<()> {
+        let message = message_to_sign(
+            self.source,
+            GenericNetworkId::EVM(self.chain_id),
+            commitment,
+        );
+        let state = self.state();
+        if commitment.nonce != state.batch_nonce + 1 {
+            return Err(anyhow!("Invalid batch nonce {}", commitment.nonce));
+        }
+        let approvals = peer_approvals(approvals, &state.peers, message)?;
+        if !has_enough_approvals(&approvals, &state.peers) {
+            return Err(anyhow!("Not enough approvals"));
+        }
+        Ok(())
+    }
+}
+
+pub fn commitment(nonce: u64) -> UnboundedGenericCommitment {
+    UnboundedGenericCommitment::EVM(bridge_types::evm::Commitment::Outbound(
+        bridge_types::evm::OutboundCommitment {
+            nonce,
+            total_max_gas: Default::default(),
+            messages: Default::default(),
+        },
+    ))
+}
+
+/// Waits until the condition holds for the state changed by a running relay.
+pub async fn wait_until(condition: impl Fn() -> bool) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !condition() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("condition is not met in time");
+}
+
+/// Block of the MMR leaf with the digest of the block with the commitment.
+pub fn leaf_block(block_number: u64) -> u64 {
+    block_number + 1
+}
+
+/// Digest of the block with the commitment, as produced by the sending network.
+pub fn digest(target: GenericNetworkId, commitment_hash: H256) -> AuxiliaryDigest {
+    AuxiliaryDigest {
+        logs: vec![AuxiliaryDigestItem::Commitment(target, commitment_hash)],
+    }
+}
+
+#[async_trait::async_trait]
+impl OutboundChannelReader for FakeChain {
+    fn network_id(&self) -> AnyResult<GenericNetworkId> {
+        Ok(self.network_id)
+    }
+
+    fn average_block_time(&self) -> Duration {
+        Duration::from_millis(10)
+    }
+
+    async fn outbound_nonce(&self, _target: GenericNetworkId) -> AnyResult<u64> {
+        let mut state = self.state();
+        if state.transport_failures > 0 {
+            state.transport_failures -= 1;
+            return Err(jsonrpsee::core::Error::RestartNeeded("node restarted".to_string()).into());
+        }
+        Ok(state.outbound_nonce)
+    }
+
+    async fn commitment(
+        &self,
+        _target: GenericNetworkId,
+        nonce: u64,
+    ) -> AnyResult<CommitmentWithBlock> {
+        self.state()
+            .outbound
+            .get(&nonce)
+            .cloned()
+            .ok_or(anyhow!("Commitment with nonce {} not found", nonce))
+    }
+
+    async fn digest(
+        &self,
+        target: GenericNetworkId,
+        _block_number: u64,
+        commitment_hash: H256,
+    ) -> AnyResult<AuxiliaryDigest> {
+        Ok(digest(target, commitment_hash))
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageProofReader for FakeChain {
+    type Proof = FakeBeefyProof;
+
+    async fn commitment_with_proof(
+        &self,
+        target: GenericNetworkId,
+        nonce: u64,
+        latest_beefy_block: u32,
+    ) -> AnyResult<(UnboundedGenericCommitment, Self::Proof)> {
+        let commitment = OutboundChannelReader::commitment(self, target, nonce).await?;
+        if leaf_block(commitment.block_number) > latest_beefy_block as u64 {
+            return Err(anyhow!(
+                "Leaf of block {} is not finalized by BEEFY",
+                commitment.block_number
+            ));
+        }
+        Ok((
+            commitment.commitment,
+            FakeBeefyProof {
+                block_number: commitment.block_number,
+            },
+        ))
+    }
+
+    async fn commitment_beefy_block(
+        &self,
+        target: GenericNetworkId,
+        nonce: u64,
+    ) -> AnyResult<Option<u64>> {
+        let commitment = OutboundChannelReader::commitment(self, target, nonce).await?;
+        let leaf_block = leaf_block(commitment.block_number);
+        match self.state().best_block {
+            Some(best_block) if best_block < leaf_block => Ok(None),
+            _ => Ok(Some(leaf_block)),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl ApprovalStore for FakeChain {
+    async fn approval_peers(
+        &self,
+        _target: GenericNetworkId,
+    ) -> AnyResult<BTreeSet<ecdsa::Public>> {
+        Ok(self.state().approval_peers.clone())
+    }
+
+    async fn approvals(
+        &self,
+        _target: GenericNetworkId,
+        message: H256,
+    ) -> AnyResult<Vec<ecdsa::Signature>> {
+        Ok(self
+            .state()
+            .approvals
+            .get(&message)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn submit_approval(
+        &self,
+        _target: GenericNetworkId,
+        message: H256,
+        signature: ecdsa::Signature,
+    ) -> AnyResult<SubmittedExtrinsic> {
+        let mut state = self.state();
+        let approvals = state.approvals.entry(message).or_default();
+        if approvals.contains(&signature) {
+            return Err(anyhow!("Already approved"));
+        }
+        approvals.push(signature);
+        Ok(SubmittedExtrinsic::default())
+    }
+}
+
+#[async_trait::async_trait]
+impl InboundChannelReader for FakeChain {
+    fn network_id(&self) -> AnyResult<GenericNetworkId> {
+        Ok(self.network_id)
+    }
+
+    async fn inbound_nonce(&self, _source: GenericNetworkId) -> AnyResult<u64> {
+        Ok(self.state().inbound_nonce)
+    }
+
+    async fn peers(&self, _source: GenericNetworkId) -> AnyResult<BTreeSet<ecdsa::Public>> {
+        Ok(self.state().peers.clone())
+    }
+}
+
+#[async_trait::async_trait]
+impl EvmInboundChannelReader for FakeChain {
+    async fn evm_channel_address(&self, _chain_id: EVMChainId) -> AnyResult<Option<H160>> {
+        Ok(None)
+    }
+
+    async fn reported_nonce(&self, _source: GenericNetworkId) -> AnyResult<u64> {
+        Ok(self.state().inbound_nonce)
+    }
+
+    async fn base_fee_block(&self, _chain_id: EVMChainId) -> AnyResult<Option<u64>> {
+        Ok(None)
+    }
+
+    async fn base_fee(&self, _chain_id: EVMChainId) -> AnyResult<Option<U256>> {
+        Ok(self.state().evm_base_fee)
+    }
+}
+
+#[async_trait::async_trait]
+impl ExtrinsicSubmitter<MultisigProof> for FakeChain {
+    async fn submit_messages_commitment(
+        &self,
+        _source: GenericNetworkId,
+        commitment: UnboundedGenericCommitment,
+        proof: MultisigProof,
+    ) -> AnyResult<SubmittedExtrinsic> {
+        self.check_approvals(&commitment, proof)?;
+        self.accept(&commitment)
+    }
+
+    async fn broadcast_messages_commitment(
+        &self,
+        _source: GenericNetworkId,
+        commitment: UnboundedGenericCommitment,
+        proof: MultisigProof,
+    ) -> AnyResult<PendingSubmission> {
+        self.check_approvals(&commitment, proof)?;
+        self.broadcast(commitment)
+    }
+}
+
+#[async_trait::async_trait]
+impl ExtrinsicSubmitter<FakeBeefyProof> for FakeChain {
+    async fn submit_messages_commitment(
+        &self,
+        _source: GenericNetworkId,
+        commitment: UnboundedGenericCommitment,
+        _proof: FakeBeefyProof,
+    ) -> AnyResult<SubmittedExtrinsic> {
+        self.accept(&commitment)
+    }
+    async fn broadcast_messages_commitment(
+        &self,
+        _source: GenericNetworkId,
+        commitment: UnboundedGenericCommitment,
+        _proof: FakeBeefyProof,
+    ) -> AnyResult<PendingSubmission> {
+        self.broadcast(commitment)
+    }
+}
+
+#[async_trait::async_trait]
+impl EvmBatchSubmitter for FakeEvmChannel {
+    async fn chain_id(&self) -> AnyResult<EVMChainId> {
+        Ok(self.chain_id)
+    }
+
+    async fn batch_nonce(&self) -> AnyResult<u64> {
+        Ok(self.state().batch_nonce)
+    }
+
+    fn can_submit(&self) -> bool {
+        true
+    }
+
+    async fn gas_price(&self) -> AnyResult<U256> {
+        Ok(self.state().gas_price)
+    }
+
+    async fn estimate_batch(
+        &self,
+        commitment: &OutboundCommitment<MaxU32, MaxU32>,
+        approvals: Vec<ecdsa::Signature>,
+    ) -> AnyResult<U256> {
+        self.check(commitment, approvals)?;
+        Ok(Self::BATCH_GAS.into())
+    }
+
+    async fn submit_batch(
+        &self,
+        commitment: &OutboundCommitment<MaxU32, MaxU32>,
+        approvals: Vec<ecdsa::Signature>,
+        estimated_gas: U256,
+    ) -> AnyResult<Option<TransactionReceipt>> {
+        if let Err(err) = self.check(commitment, approvals) {
+            self.state().rejected.push(commitment.nonce);
+            return Err(err);
+        }
+        let mut state = self.state();
+        state.batch_nonce = commitment.nonce;
+        state.accepted.push(commitment.nonce);
+        Ok(Some(TransactionReceipt {
+            status: Some(1u64.into()),
+            gas_used: Some(estimated_gas),
+            ..Default::default()
+        }))
+    }
+}
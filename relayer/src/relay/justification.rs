@@ -40,6 +40,7 @@ use bridge_common::{
 use ethers::prelude::*;
 use sp_beefy::crypto::Signature;
 use sp_beefy::SignedCommitment;
+use sp_core::ecdsa;
 use sp_runtime::traits::{AtLeast32Bit, Keccak256, UniqueSaturatedInto};
 use sp_runtime::traits::{Convert, Hash as HashTrait};
 use sp_runtime::Saturating;
@@ -115,20 +116,12 @@ where
         for block_number in 0u32..=6u32 {
             let block_number = commitment.block_number.saturating_sub(block_number.into());
             let leaf_proof = sub.mmr_generate_proof(block_number, block_number).await?;
-            let hashed_leaf = leaf_proof.leaf.using_encoded(Keccak256::hash);
-            debug!("Hashed leaf: {:?}", hashed_leaf);
             let proof = convert_to_simplified_mmr_proof(
                 leaf_proof.proof.leaf_indices[0],
                 leaf_proof.proof.leaf_count,
                 &leaf_proof.proof.items,
             );
-            let computed_root = proof.root(
-                |a, b| {
-                    let res = [a.as_bytes(), b.as_bytes()].concat();
-                    Keccak256::hash(&res)
-                },
-                hashed_leaf,
-            );
+            let computed_root = Self::compute_mmr_root(&leaf_proof, &proof);
             if computed_root != root {
                 warn!("MMR root mismatch: {:?} != {:?}", root, computed_root);
                 continue;
@@ -138,6 +131,78 @@ where
         return Err(anyhow!("Could not find MMR proof"));
     }
 
+    fn compute_mmr_root(leaf_proof: &LeafProof<T>, proof: &Proof<H256>) -> H256 {
+        let hashed_leaf = leaf_proof.leaf.using_encoded(Keccak256::hash);
+        debug!("Hashed leaf: {:?}", hashed_leaf);
+        proof.root(
+            |a, b| {
+                let res = [a.as_bytes(), b.as_bytes()].concat();
+                Keccak256::hash(&res)
+            },
+            hashed_leaf,
+        )
+    }
+
+    /// Checks the justification the same way the receiver light client does:
+    /// every signature must be made by the validator at its position, enough
+    /// validators must have signed and the MMR leaf must be included in the
+    /// signed MMR root.
+    pub fn verify(&self) -> AnyResult<()> {
+        if self.signatures.len() != self.validators.len() {
+            return Err(anyhow!(
+                "Validator set size mismatch: {} signatures, {} validators",
+                self.signatures.len(),
+                self.validators.len()
+            ));
+        }
+        let commitment_hash = Keccak256::hash(&self.commitment.encode());
+        for (pos, signature) in self.signatures.iter().enumerate() {
+            let Some(signature) = signature else {
+                continue;
+            };
+            let public = ecdsa::Signature::from(signature.clone())
+                .recover_prehashed(&commitment_hash.0)
+                .ok_or(anyhow!("Unable to recover signature of validator {}", pos))?;
+            let signer = H160::from_slice(&pallet_beefy_mmr::BeefyEcdsaToEthereum::convert(
+                public.into(),
+            ));
+            if signer != self.validators[pos] {
+                return Err(anyhow!(
+                    "Signature of validator {} is made by {:?}, expected {:?}",
+                    pos,
+                    signer,
+                    self.validators[pos]
+                ));
+            }
+        }
+        let threshold = Self::signatures_threshold(self.num_validators);
+        if (self.signed_validators.len() as u32) < threshold {
+            return Err(anyhow!(
+                "Not enough signatures: {} < {} of {} validators",
+                self.signed_validators.len(),
+                threshold,
+                self.num_validators
+            ));
+        }
+        let payload =
+            Self::get_payload(&self.commitment).ok_or(anyhow!("Payload is not supported"))?;
+        let computed_root = Self::compute_mmr_root(&self.leaf_proof, &self.simplified_proof);
+        if computed_root != payload.mmr_root {
+            return Err(anyhow!(
+                "MMR root mismatch: {:?} != {:?}",
+                payload.mmr_root,
+                computed_root
+            ));
+        }
+        Ok(())
+    }
+
+    /// BEEFY tolerates less than a third of faulty validators, so more than
+    /// two thirds of the validator set must sign a commitment.
+    pub fn signatures_threshold(num_validators: u32) -> u32 {
+        num_validators - num_validators.saturating_sub(1) / 3
+    }
+
     pub fn get_payload(commitment: &BeefyCommitment<T>) -> Option<MmrPayload> {
         commitment
             .payload
@@ -224,3 +289,148 @@ where
         Ok((mmr_leaf, proof))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::substrate::MmrLeaf;
+    use sp_beefy::known_payloads::MMR_ROOT_ID;
+
+    type Justification = BeefyJustification<MainnetConfig>;
+
+    fn pairs(count: u8) -> Vec<ecdsa::Pair> {
+        (1..=count)
+            .map(|seed| ecdsa::Pair::from_seed(&[seed; 32]))
+            .collect()
+    }
+
+    fn address(pair: &ecdsa::Pair) -> H160 {
+        H160::from_slice(&pallet_beefy_mmr::BeefyEcdsaToEthereum::convert(
+            pair.public().into(),
+        ))
+    }
+
+    /// Justification of a single leaf MMR, where `signers[i]` signs at position `i`.
+    fn justification(
+        validators: &[ecdsa::Pair],
+        signers: &[Option<&ecdsa::Pair>],
+    ) -> Justification {
+        // Content of the leaf doesn't matter, only its hash is checked
+        let leaf = MmrLeaf::<MainnetConfig>::decode(&mut &[0u8; 256][..]).unwrap();
+        let mmr_root = leaf.using_encoded(Keccak256::hash);
+        let commitment = BeefyCommitment::<MainnetConfig> {
+            payload: sp_beefy::Payload::from_single_entry(MMR_ROOT_ID, mmr_root.encode()),
+            block_number: 10,
+            validator_set_id: 1,
+        };
+        let commitment_hash = Keccak256::hash(&commitment.encode());
+        let signatures: Vec<Option<Signature>> = signers
+            .iter()
+            .map(|signer| signer.map(|pair| pair.sign_prehashed(&commitment_hash.0).into()))
+            .collect();
+        let signed_validators = (0u32..)
+            .zip(signatures.iter())
+            .filter(|(_, signature)| signature.is_some())
+            .map(|(pos, _)| pos)
+            .collect();
+        Justification {
+            commitment,
+            num_validators: signatures.len() as u32,
+            signatures,
+            signed_validators,
+            validators: validators.iter().map(address).collect(),
+            leaf_proof: LeafProof {
+                block_hash: Default::default(),
+                leaf,
+                proof: sp_mmr_primitives::Proof {
+                    leaf_indices: vec![0],
+                    leaf_count: 1,
+                    items: vec![],
+                },
+            },
+            simplified_proof: Proof {
+                items: vec![],
+                order: 0,
+            },
+            is_mandatory: false,
+        }
+    }
+
+    fn verify_error(justification: Justification) -> String {
+        justification.verify().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn verifies_valid_justification() {
+        let validators = pairs(4);
+        let all = validators.iter().map(Some).collect::<Vec<_>>();
+        justification(&validators, &all).verify().unwrap();
+        let enough = [
+            Some(&validators[0]),
+            None,
+            Some(&validators[2]),
+            Some(&validators[3]),
+        ];
+        justification(&validators, &enough).verify().unwrap();
+    }
+
+    #[test]
+    fn rejects_signer_at_wrong_position() {
+        let validators = pairs(4);
+        let swapped = [
+            Some(&validators[1]),
+            Some(&validators[0]),
+            Some(&validators[2]),
+            Some(&validators[3]),
+        ];
+        assert!(
+            verify_error(justification(&validators, &swapped)).contains("Signature of validator 0")
+        );
+        let outsider = pairs(5).pop().unwrap();
+        let signed_by_outsider = [
+            Some(&validators[0]),
+            Some(&validators[1]),
+            Some(&outsider),
+            None,
+        ];
+        assert!(
+            verify_error(justification(&validators, &signed_by_outsider))
+                .contains("Signature of validator 2")
+        );
+    }
+
+    #[test]
+    fn rejects_signatures_below_threshold() {
+        let validators = pairs(4);
+        let signers = [Some(&validators[0]), None, None, Some(&validators[3])];
+        assert!(verify_error(justification(&validators, &signers))
+            .contains("Not enough signatures: 2 < 3 of 4 validators"));
+    }
+
+    #[test]
+    fn rejects_validator_count_mismatch() {
+        let validators = pairs(4);
+        let signers = validators[..3].iter().map(Some).collect::<Vec<_>>();
+        assert!(verify_error(justification(&validators, &signers))
+            .contains("Validator set size mismatch: 3 signatures, 4 validators"));
+    }
+
+    #[test]
+    fn rejects_mmr_root_mismatch() {
+        let validators = pairs(4);
+        let all = validators.iter().map(Some).collect::<Vec<_>>();
+        let mut justification = justification(&validators, &all);
+        justification.leaf_proof.leaf.parent_number_and_hash.0 = 9;
+        assert!(verify_error(justification).contains("MMR root mismatch"));
+    }
+
+    #[test]
+    fn threshold_is_more_than_two_thirds() {
+        for (num_validators, threshold) in [(1, 1), (3, 3), (4, 3), (7, 5), (10, 7), (100, 67)] {
+            assert_eq!(
+                Justification::signatures_threshold(num_validators),
+                threshold
+            );
+        }
+    }
+}
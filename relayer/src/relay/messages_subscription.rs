@@ -78,6 +78,37 @@ pub async fn load_digest<S: SenderConfig>(
     Ok(digest)
 }
 
+/// Block of the MMR leaf with the digest of the commitment. The commitment can
+/// be proven by any BEEFY commitment at or after this block. Returns `None`
+/// if the leaf is not produced yet.
+pub async fn commitment_leaf_block<S: SenderConfig>(
+    sender: &SubUnsignedClient<S>,
+    network_id: GenericNetworkId,
+    batch_nonce: u64,
+) -> AnyResult<Option<BlockNumber<S>>> {
+    let offchain_data = sender
+        .commitment_with_nonce(network_id, batch_nonce, BlockNumberOrHash::Finalized)
+        .await?;
+    let digest = load_digest(
+        sender,
+        network_id,
+        offchain_data.block_number,
+        offchain_data.commitment.hash(),
+    )
+    .await?;
+    let digest_hash = Keccak256::hash_of(&digest);
+    let best_block = sender.block_number(()).await?;
+    let leaf_proof = find_leaf_proof_with_digest(
+        sender,
+        digest_hash,
+        offchain_data.block_number,
+        50,
+        best_block,
+    )
+    .await?;
+    Ok(leaf_proof.map(|leaf_proof| leaf_proof.leaf.parent_number_and_hash.0 + 1u32.into()))
+}
+
 pub async fn load_commitment_with_proof<S: SenderConfig>(
     sender: &SubUnsignedClient<S>,
     network_id: GenericNetworkId,
@@ -129,18 +160,35 @@ pub async fn load_commitment_with_proof<S: SenderConfig>(
     })
 }
 
-async fn leaf_proof_with_digest<S: SenderConfig>(
+pub async fn leaf_proof_with_digest<S: SenderConfig>(
     sender: &SubUnsignedClient<S>,
     digest_hash: H256,
     start_leaf: BlockNumber<S>,
     count: u32,
     at: BlockNumber<S>,
 ) -> AnyResult<LeafProof<S>> {
+    find_leaf_proof_with_digest(sender, digest_hash, start_leaf, count, at)
+        .await?
+        .ok_or(anyhow::anyhow!("leaf proof not found"))
+}
+
+/// Searches the leaf with the digest among `count` leaves from `start_leaf`.
+/// Returns `None` if the leaf can be produced after block `at`.
+pub async fn find_leaf_proof_with_digest<S: SenderConfig>(
+    sender: &SubUnsignedClient<S>,
+    digest_hash: H256,
+    start_leaf: BlockNumber<S>,
+    count: u32,
+    at: BlockNumber<S>,
+) -> AnyResult<Option<LeafProof<S>>> {
     for i in 0..count {
         let leaf = start_leaf + i.into();
+        if leaf > at {
+            return Ok(None);
+        }
         let leaf_proof = sender.mmr_generate_proof(leaf, at).await?;
         if leaf_proof.leaf.leaf_extra.digest_hash == digest_hash {
-            return Ok(leaf_proof);
+            return Ok(Some(leaf_proof));
         }
     }
     return Err(anyhow::anyhow!("leaf proof not found"));
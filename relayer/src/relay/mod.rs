@@ -32,10 +32,16 @@
 #![allow(clippy::all)]
 
 pub mod beefy_syncer;
+pub mod checkpoint;
 pub mod evm;
+#[cfg(test)]
+pub mod fakes;
 pub mod justification;
 pub mod messages_subscription;
 pub mod multisig_messages;
 pub mod parachain;
 pub mod parachain_messages;
+pub mod pipeline;
+pub mod retry;
 pub mod ton;
+pub mod traits;
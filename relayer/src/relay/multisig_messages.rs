@@ -33,45 +33,55 @@
 
 use std::collections::BTreeSet;
 
+use crate::health::LoopHeartbeat;
+use crate::history::{EntryKind, HistoryEntry};
+use crate::metrics::RelayMetrics;
 use crate::prelude::*;
-use crate::relay::messages_subscription::load_digest;
-use crate::substrate::{BlockNumberOrHash, OtherParams};
-use bridge_types::types::AuxiliaryDigest;
+use crate::relay::pipeline::submit_in_order;
+use crate::relay::retry::RetryBackoff;
+use crate::relay::traits::{
+    has_enough_approvals, peer_approvals, pending_nonces, ApprovalStore, ExtrinsicSubmitter,
+    InboundChannelReader, MultisigProof, OutboundChannelReader, PendingSubmission,
+};
+use crate::substrate::UnboundedGenericCommitment;
 use bridge_types::{GenericNetworkId, SubNetworkId, H256};
+use futures::StreamExt;
 use sp_core::ecdsa;
 use sp_runtime::traits::Keccak256;
 
-pub struct RelayBuilder<S: SenderConfig, R: ReceiverConfig> {
-    sender: Option<SubUnsignedClient<S>>,
-    receiver: Option<SubUnsignedClient<R>>,
+pub struct RelayBuilder<S, R> {
+    sender: Option<S>,
+    receiver: Option<R>,
     signer: Option<ecdsa::Pair>,
+    pipeline_window: usize,
 }
 
-impl<S: SenderConfig, R: ReceiverConfig> Default for RelayBuilder<S, R> {
+impl<S, R> Default for RelayBuilder<S, R> {
     fn default() -> Self {
         Self {
             sender: None,
             receiver: None,
             signer: None,
+            pipeline_window: 1,
         }
     }
 }
 
 impl<S, R> RelayBuilder<S, R>
 where
-    S: SenderConfig,
-    R: ReceiverConfig,
+    S: OutboundChannelReader + ApprovalStore,
+    R: InboundChannelReader + ExtrinsicSubmitter<MultisigProof>,
 {
     pub fn new() -> Self {
         Default::default()
     }
 
-    pub fn with_sender_client(mut self, sender: SubUnsignedClient<S>) -> Self {
+    pub fn with_sender_client(mut self, sender: S) -> Self {
         self.sender = Some(sender);
         self
     }
 
-    pub fn with_receiver_client(mut self, receiver: SubUnsignedClient<R>) -> Self {
+    pub fn with_receiver_client(mut self, receiver: R) -> Self {
         self.receiver = Some(receiver);
         self
     }
@@ -81,17 +91,24 @@ where
         self
     }
 
+    /// Number of commitments approved ahead and kept in the transaction pool
+    /// at the same time.
+    pub fn with_pipeline_window(mut self, pipeline_window: usize) -> Self {
+        self.pipeline_window = pipeline_window;
+        self
+    }
+
     pub async fn build(self) -> AnyResult<Relay<S, R>> {
         let sender = self.sender.expect("sender client is needed");
         let receiver = self.receiver.expect("receiver client is needed");
         let signer = self.signer.expect("signer is needed");
-        let sender_network_id = sender.constant_fetch_or_default(&S::network_id())?;
+        let sender_network_id = OutboundChannelReader::network_id(&sender)?;
 
         let GenericNetworkId::Sub(sender_network_id) = sender_network_id else {
             return Err(anyhow::anyhow!("Error! Sender is NOT a Substrate Network!"));
         };
 
-        let receiver_network_id = receiver.constant_fetch_or_default(&R::network_id())?;
+        let receiver_network_id = InboundChannelReader::network_id(&receiver)?;
 
         let GenericNetworkId::Sub(receiver_network_id) = receiver_network_id else {
             return Err(anyhow::anyhow!(
@@ -100,9 +117,15 @@ where
         };
 
         Ok(Relay {
+            metrics: RelayMetrics::new(
+                GenericNetworkId::Sub(sender_network_id),
+                GenericNetworkId::Sub(receiver_network_id),
+            ),
+            heartbeat: LoopHeartbeat::register(sender_network_id, receiver_network_id),
             sender,
             receiver,
             signer,
+            pipeline_window: self.pipeline_window,
             receiver_network_id,
             sender_network_id,
         })
@@ -110,154 +133,432 @@ where
 }
 
 #[derive(Clone)]
-pub struct Relay<S: SenderConfig, R: ReceiverConfig> {
-    sender: SubUnsignedClient<S>,
-    receiver: SubUnsignedClient<R>,
+pub struct Relay<S, R> {
+    sender: S,
+    receiver: R,
     signer: ecdsa::Pair,
+    pipeline_window: usize,
     receiver_network_id: SubNetworkId,
     sender_network_id: SubNetworkId,
+    metrics: RelayMetrics,
+    heartbeat: LoopHeartbeat,
 }
 
 impl<S, R> Relay<S, R>
 where
-    S: SenderConfig,
-    R: ReceiverConfig,
-    OtherParams<R>: Default,
-    OtherParams<S>: Default,
+    S: OutboundChannelReader + ApprovalStore,
+    R: InboundChannelReader + ExtrinsicSubmitter<MultisigProof>,
 {
     async fn inbound_channel_nonce(&self) -> AnyResult<u64> {
-        let storage = R::substrate_bridge_inbound_nonce(self.sender_network_id);
-        let nonce = self.receiver.storage_fetch_or_default(&storage, ()).await?;
-        Ok(nonce)
+        self.receiver
+            .inbound_nonce(self.sender_network_id.into())
+            .await
     }
 
     async fn outbound_channel_nonce(&self) -> AnyResult<u64> {
-        let nonce = self
-            .sender
-            .storage_fetch_or_default(
-                &S::bridge_outbound_nonce(self.receiver_network_id.into()),
-                BlockNumberOrHash::Finalized,
-            )
-            .await?;
-        Ok(nonce)
+        self.sender
+            .outbound_nonce(self.receiver_network_id.into())
+            .await
     }
 
     async fn approvals(&self, message: H256) -> AnyResult<Vec<ecdsa::Signature>> {
         let peers = self.receiver_peers().await?;
         let approvals = self
             .sender
-            .storage_fetch_or_default(&S::approvals(self.receiver_network_id.into(), message), ())
+            .approvals(self.receiver_network_id.into(), message)
             .await?;
-        let mut acceptable_approvals = vec![];
-        for approval in approvals {
-            let public = approval
-                .1
-                .recover_prehashed(&message.0)
-                .ok_or(anyhow!("Wrong signature in data signer pallet"))?;
-            if peers.contains(&public) {
-                acceptable_approvals.push(approval.1);
-            }
-        }
-        Ok(acceptable_approvals)
+        peer_approvals(approvals, &peers, message)
     }
 
     async fn sender_peers(&self) -> AnyResult<BTreeSet<ecdsa::Public>> {
-        let peers = self
-            .sender
-            .storage_fetch(&S::peers(self.receiver_network_id.into()), ())
-            .await?
-            .unwrap_or_default();
-        Ok(peers)
+        self.sender
+            .approval_peers(self.receiver_network_id.into())
+            .await
     }
 
     async fn receiver_peers(&self) -> AnyResult<BTreeSet<ecdsa::Public>> {
-        let peers = self
-            .receiver
-            .storage_fetch(&R::peers(self.sender_network_id.into()), ())
+        self.receiver.peers(self.sender_network_id.into()).await
+    }
+
+    fn history_entry(&self, kind: EntryKind, nonce: u64, commitment_hash: H256) -> HistoryEntry {
+        HistoryEntry::new(kind, self.sender_network_id, self.receiver_network_id)
+            .with_nonce(nonce)
+            .with_commitment_hash(commitment_hash)
+    }
+
+    /// Approves the commitment and submits it once enough approvals are collected.
+    async fn relay_commitment(&self, nonce: u64) -> AnyResult<()> {
+        let Some((commitment, proof)) = self.prepare_commitment(nonce).await? else {
+            return Ok(());
+        };
+        if let Err(err) = self
+            .broadcast_commitment(nonce, commitment, proof)
             .await?
-            .unwrap_or_default()
-            .into_iter()
-            .collect();
-        Ok(peers)
+            .await
+        {
+            error!(
+                "Failed to submit messages, probably another relayer already submitted it: {:?}",
+                err
+            );
+        }
+        Ok(())
+    }
+
+    /// Approves the commitment, `None` if there are still not enough approvals to submit it.
+    async fn prepare_commitment(
+        &self,
+        nonce: u64,
+    ) -> AnyResult<Option<(UnboundedGenericCommitment, MultisigProof)>> {
+        let offchain_data = self
+            .sender
+            .commitment(self.receiver_network_id.into(), nonce)
+            .await?;
+        let commitment_hash = offchain_data.commitment.hash();
+        let digest = self
+            .sender
+            .digest(
+                self.receiver_network_id.into(),
+                offchain_data.block_number,
+                commitment_hash,
+            )
+            .await?;
+        let digest_hash = Keccak256::hash_of(&digest);
+        trace!("Digest hash: {}", digest_hash);
+        let peers = self.receiver_peers().await?;
+        let approvals = self.approvals(digest_hash).await?;
+        let is_already_approved = approvals
+            .iter()
+            .filter_map(|approval| approval.recover_prehashed(&digest_hash.0))
+            .any(|public| self.signer.public() == public);
+        if !has_enough_approvals(&approvals, &peers) && !is_already_approved {
+            let signature = self.signer.sign_prehashed(&digest_hash.0);
+            let res = self
+                .sender
+                .submit_approval(self.receiver_network_id.into(), digest_hash, signature)
+                .await;
+            self.history_entry(EntryKind::Approval, nonce, commitment_hash)
+                .record_extrinsic(&res);
+            res?;
+            self.metrics.approval_sent();
+        }
+        let approvals = self.approvals(digest_hash).await?;
+        if !has_enough_approvals(&approvals, &peers) {
+            info!("Still not enough signatures, probably another relayer will submit commitment");
+            return Ok(None);
+        }
+        Ok(Some((
+            offchain_data.commitment,
+            MultisigProof { digest, approvals },
+        )))
+    }
+
+    /// Submits the commitment, returned future records the result once it is included.
+    async fn broadcast_commitment(
+        &self,
+        nonce: u64,
+        commitment: UnboundedGenericCommitment,
+        proof: MultisigProof,
+    ) -> AnyResult<PendingSubmission> {
+        let history = self.history_entry(EntryKind::InboundCommitment, nonce, commitment.hash());
+        let metrics = self.metrics.clone();
+        let res = self
+            .receiver
+            .broadcast_messages_commitment(self.sender_network_id.into(), commitment, proof)
+            .await;
+        let pending = match res {
+            Ok(pending) => pending,
+            Err(err) => {
+                history.with_error(&err).record();
+                metrics.commitment_failed();
+                return Err(err);
+            }
+        };
+        Ok(Box::pin(async move {
+            let res = pending.await;
+            history.record_extrinsic(&res);
+            match res {
+                Ok(_) => metrics.commitment_submitted(),
+                Err(_) => metrics.commitment_failed(),
+            }
+            res
+        }))
+    }
+
+    async fn relay_commitments(&self) -> AnyResult<()> {
+        let inbound_nonce = self.inbound_channel_nonce().await?;
+        let outbound_nonce = self.outbound_channel_nonce().await?;
+        self.metrics.set_nonces(inbound_nonce, outbound_nonce);
+        // Commitments before the one which failed to prepare are still submitted
+        let prepare_error = std::sync::Mutex::new(None);
+        let prepared = futures::stream::iter(pending_nonces(inbound_nonce, outbound_nonce))
+            .map(|nonce| {
+                let prepare_error = &prepare_error;
+                async move {
+                    match self.prepare_commitment(nonce).await {
+                        Ok(prepared) => Ok(prepared.map(|prepared| (nonce, prepared))),
+                        Err(err) => {
+                            prepare_error.lock().expect("poisoned").get_or_insert(err);
+                            Ok(None)
+                        }
+                    }
+                }
+            })
+            .buffered(self.pipeline_window.max(1));
+        let (_, res) = submit_in_order(
+            prepared,
+            self.pipeline_window,
+            |nonce, (commitment, proof)| self.broadcast_commitment(nonce, commitment, proof),
+        )
+        .await;
+        if let Err(err) = res {
+            error!(
+                "Failed to submit messages, probably another relayer already submitted it: {:?}",
+                err
+            );
+        }
+        match prepare_error.into_inner().expect("poisoned") {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
     }
 
     pub async fn run(self) -> AnyResult<()> {
+        let mut backoff = RetryBackoff::new(self.sender.average_block_time());
         loop {
             let public = self.signer.public();
-            let peers = self.sender_peers().await?;
+            let peers = match self.sender_peers().await {
+                Ok(peers) => peers,
+                Err(err) => {
+                    backoff.handle(Err(err)).await?;
+                    continue;
+                }
+            };
             if !peers.contains(&public) {
                 info!("Peer is not in trusted list, waiting...");
-                tokio::time::sleep(S::average_block_time()).await;
+                self.heartbeat.beat();
+                tokio::time::sleep(self.sender.average_block_time()).await;
             } else {
                 break;
             }
         }
-        let mut interval = tokio::time::interval(S::average_block_time());
+        let mut interval = tokio::time::interval(self.sender.average_block_time());
         loop {
             interval.tick().await;
-            let inbound_nonce = self.inbound_channel_nonce().await?;
-            let outbound_nonce = self.outbound_channel_nonce().await?;
-            if inbound_nonce >= outbound_nonce {
-                if inbound_nonce > outbound_nonce {
-                    error!(
-                        "Inbound channel nonce is higher than outbound channel nonce: {} > {}",
-                        inbound_nonce, outbound_nonce
-                    );
-                }
-                continue;
-            }
-            for nonce in (inbound_nonce + 1)..=outbound_nonce {
-                let offchain_data = self
-                    .sender
-                    .commitment_with_nonce(
-                        self.receiver_network_id.into(),
-                        nonce,
-                        BlockNumberOrHash::Finalized,
-                    )
-                    .await?;
-                let commitment_hash = offchain_data.commitment.hash();
-                let digest: AuxiliaryDigest = load_digest(
-                    &self.sender,
-                    self.receiver_network_id.into(),
-                    offchain_data.block_number,
-                    commitment_hash,
-                )
-                .await?;
-                let digest_hash = Keccak256::hash_of(&digest);
-                trace!("Digest hash: {}", digest_hash);
-                let peers = self.receiver_peers().await?;
-                let approvals = self.approvals(digest_hash).await?;
-                let is_already_approved = approvals
-                    .iter()
-                    .filter_map(|approval| approval.recover_prehashed(&digest_hash.0))
-                    .any(|public| self.signer.public() == public);
-                if (approvals.len() as u32) < bridge_types::utils::threshold(peers.len() as u32)
-                    && !is_already_approved
-                {
-                    let signature = self.signer.sign_prehashed(&digest_hash.0);
-                    let call = S::submit_signature(
-                        self.receiver_network_id.into(),
-                        digest_hash,
-                        signature,
-                    );
-                    self.sender.submit_unsigned_extrinsic(&call).await?;
-                }
-                let approvals = self.approvals(digest_hash).await?;
-                if (approvals.len() as u32) < bridge_types::utils::threshold(peers.len() as u32) {
-                    info!(
-                    "Still not enough signatures, probably another relayer will submit commitment"
-                );
-                    continue;
-                }
-                let call = R::submit_messages_commitment(
-                    self.sender_network_id.into(),
-                    offchain_data.commitment,
-                    R::multisig_proof(digest, approvals),
-                );
-                if let Err(err) = self.receiver.submit_unsigned_extrinsic(&call).await {
-                    error!("Failed to submit messages, probably another relayer already submitted it: {:?}", err);
-                }
-            }
+            self.heartbeat.beat();
+            backoff.handle(self.relay_commitments().await).await?;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relay::fakes::{commitment, digest, wait_until, FakeChain};
+
+    const SORA: GenericNetworkId = GenericNetworkId::Sub(SubNetworkId::Mainnet);
+    const PARACHAIN: GenericNetworkId = GenericNetworkId::Sub(SubNetworkId::Kusama);
+
+    fn pair(seed: u8) -> ecdsa::Pair {
+        ecdsa::Pair::from_seed(&[seed; 32])
+    }
+
+    /// Message which peers approve for the commitment with the given nonce.
+    fn message(nonce: u64) -> H256 {
+        Keccak256::hash_of(&digest(PARACHAIN, commitment(nonce).hash()))
+    }
+
+    /// Chains trusting given peers, the first peer runs the relay.
+    async fn setup(peers: &[ecdsa::Pair]) -> (FakeChain, FakeChain, Relay<FakeChain, FakeChain>) {
+        setup_with_window(peers, 1).await
+    }
+
+    async fn setup_with_window(
+        peers: &[ecdsa::Pair],
+        pipeline_window: usize,
+    ) -> (FakeChain, FakeChain, Relay<FakeChain, FakeChain>) {
+        let sender = FakeChain::new(SORA);
+        let receiver = FakeChain::new(PARACHAIN);
+        let public = peers
+            .iter()
+            .map(|peer| peer.public())
+            .collect::<BTreeSet<_>>();
+        sender.state().approval_peers = public.clone();
+        receiver.state().peers = public;
+        let relay = RelayBuilder::new()
+            .with_sender_client(sender.clone())
+            .with_receiver_client(receiver.clone())
+            .with_signer(peers[0].clone())
+            .with_pipeline_window(pipeline_window)
+            .build()
+            .await
+            .unwrap();
+        (sender, receiver, relay)
+    }
+
+    #[tokio::test]
+    async fn relays_pending_commitments_in_order() {
+        let (sender, receiver, relay) = setup(&[pair(1)]).await;
+        for block in 1..=3 {
+            sender.send_commitment(block);
+        }
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1, 2, 3]);
+        assert!(receiver.state().rejected.is_empty());
+        assert_eq!(sender.state().approvals[&message(2)].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn relays_only_commitments_after_inbound_nonce() {
+        let (sender, receiver, relay) = setup(&[pair(1)]).await;
+        for block in 1..=5 {
+            sender.send_commitment(block);
+        }
+        receiver.state().inbound_nonce = 2;
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![3, 4, 5]);
+        assert!(!sender.state().approvals.contains_key(&message(2)));
+    }
+
+    #[tokio::test]
+    async fn fails_on_missing_commitment() {
+        let (sender, receiver, relay) = setup(&[pair(1)]).await;
+        for block in 1..=3 {
+            sender.send_commitment(block);
+        }
+        sender.state().outbound.remove(&2);
+        assert!(relay.relay_commitments().await.is_err());
+        // Commitments after the gap are not relayed out of order
+        assert_eq!(receiver.state().accepted, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn does_nothing_when_inbound_nonce_is_ahead() {
+        let (sender, receiver, relay) = setup(&[pair(1)]).await;
+        sender.send_commitment(1);
+        receiver.state().inbound_nonce = 5;
+        relay.relay_commitments().await.unwrap();
+        assert!(sender.state().approvals.is_empty());
+        assert!(receiver.state().accepted.is_empty());
+        assert!(receiver.state().rejected.is_empty());
+    }
+
+    #[tokio::test]
+    async fn waits_for_enough_approvals() {
+        let peers = [pair(1), pair(2), pair(3), pair(4)];
+        let (sender, receiver, relay) = setup(&peers).await;
+        sender.send_commitment(1);
+        // Approval from a key which is not a peer is not counted
+        sender
+            .state()
+            .approvals
+            .insert(message(1), vec![pair(5).sign_prehashed(&message(1).0)]);
+
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(sender.state().approvals[&message(1)].len(), 2);
+        assert!(receiver.state().accepted.is_empty());
+
+        // Approval is not sent twice
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(sender.state().approvals[&message(1)].len(), 2);
+        assert!(receiver.state().accepted.is_empty());
+
+        sender
+            .state()
+            .approvals
+            .get_mut(&message(1))
+            .unwrap()
+            .push(peers[1].sign_prehashed(&message(1).0));
+        relay.relay_commitments().await.unwrap();
+        assert!(receiver.state().accepted.is_empty());
+
+        sender
+            .state()
+            .approvals
+            .get_mut(&message(1))
+            .unwrap()
+            .push(peers[2].sign_prehashed(&message(1).0));
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1]);
+        assert_eq!(sender.state().approvals[&message(1)].len(), 4);
+    }
+
+    #[tokio::test]
+    async fn tolerates_duplicate_submission() {
+        let (sender, receiver, relay) = setup(&[pair(1)]).await;
+        sender.send_commitment(1);
+        relay.relay_commitment(1).await.unwrap();
+        // Commitment is already submitted, e.g. by another relayer
+        relay.relay_commitment(1).await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1]);
+        assert_eq!(receiver.state().rejected, vec![1]);
+        assert_eq!(sender.state().approvals[&message(1)].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn relays_backlog_with_pipeline_window() {
+        let (sender, receiver, relay) = setup_with_window(&[pair(1)], 3).await;
+        for block in 1..=5 {
+            sender.send_commitment(block);
+        }
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1, 2, 3, 4, 5]);
+        assert!(receiver.state().rejected.is_empty());
+        assert_eq!(receiver.state().max_pool_size, 3);
+    }
+
+    #[tokio::test]
+    async fn pipeline_resubmits_commitments_rejected_by_strict_pool() {
+        let (sender, receiver, relay) = setup_with_window(&[pair(1)], 3).await;
+        receiver.state().strict_pool = true;
+        for block in 1..=3 {
+            sender.send_commitment(block);
+        }
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1, 2, 3]);
+        assert_eq!(receiver.state().rejected, vec![2, 3]);
+        assert_eq!(receiver.state().max_pool_size, 1);
+    }
+
+    #[tokio::test]
+    async fn pipeline_approves_ahead_but_submits_in_order() {
+        let peers = [pair(1), pair(2)];
+        let (sender, receiver, relay) = setup_with_window(&peers, 3).await;
+        for block in 1..=3 {
+            sender.send_commitment(block);
+        }
+        // Second peer approved only the first and the last commitments
+        for nonce in [1, 3] {
+            sender.state().approvals.insert(
+                message(nonce),
+                vec![peers[1].sign_prehashed(&message(nonce).0)],
+            );
+        }
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1]);
+        assert!(receiver.state().rejected.is_empty());
+        assert_eq!(sender.state().approvals[&message(2)].len(), 1);
+        assert_eq!(sender.state().approvals[&message(3)].len(), 2);
+
+        sender
+            .state()
+            .approvals
+            .get_mut(&message(2))
+            .unwrap()
+            .push(peers[1].sign_prehashed(&message(2).0));
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn keeps_running_after_transport_error() {
+        let (sender, receiver, relay) = setup(&[pair(1)]).await;
+        sender.send_commitment(1);
+        sender.state().transport_failures = 1;
+        let relay = tokio::spawn(relay.run());
+        wait_until(|| !receiver.state().accepted.is_empty()).await;
+        assert!(!relay.is_finished());
+        relay.abort();
+        assert_eq!(sender.state().transport_failures, 0);
+        assert_eq!(receiver.state().accepted, vec![1]);
+    }
+}
@@ -36,19 +36,38 @@ use std::sync::Arc;
 
 use super::beefy_syncer::BeefySyncer;
 use super::justification::*;
+use crate::health::LoopHeartbeat;
+use crate::history::{EntryKind, HistoryEntry};
+use crate::metrics::RelayMetrics;
 use crate::prelude::*;
-use crate::substrate::OtherParams;
+use crate::substrate::beefy_subscription::{get_commitment_from_block, ValidatorSetBoundaries};
+use crate::substrate::{BlockNumberOrHash, OtherParams, PendingExtrinsic, SubmittedExtrinsic};
 use bridge_common::bitfield::BitField;
 use bridge_types::{GenericNetworkId, SubNetworkId};
-use futures::stream::StreamExt;
+use futures::stream::{FuturesOrdered, StreamExt};
 use sp_runtime::traits::UniqueSaturatedInto;
 use subxt::rpc_params;
 use subxt::tx::TxPayload;
 
+/// Marks the BEEFY block as sent once the commitment is included. Dry run
+/// doesn't change the receiver, so the syncer keeps waiting for the block.
+fn advance_syncer(syncer: &BeefySyncer, submitted: &SubmittedExtrinsic, block_number: u64) -> bool {
+    if submitted.dry_run {
+        info!(
+            "Dry run: BEEFY commitment for block {} is not sent",
+            block_number
+        );
+        return false;
+    }
+    syncer.update_latest_sent(block_number);
+    true
+}
+
 pub struct RelayBuilder<S: SenderConfig, R: ReceiverConfig> {
     sender: Option<SubUnsignedClient<S>>,
     receiver: Option<SubSignedClient<R>>,
     syncer: Option<BeefySyncer>,
+    subscribe_justifications: bool,
 }
 
 impl<S: SenderConfig, R: ReceiverConfig> Default for RelayBuilder<S, R> {
@@ -57,6 +76,7 @@ impl<S: SenderConfig, R: ReceiverConfig> Default for RelayBuilder<S, R> {
             sender: None,
             receiver: None,
             syncer: None,
+            subscribe_justifications: false,
         }
     }
 }
@@ -85,6 +105,12 @@ where
         self
     }
 
+    /// Receive justifications from `beefy_subscribeJustifications` instead of scanning blocks.
+    pub fn with_justification_subscription(mut self, subscribe_justifications: bool) -> Self {
+        self.subscribe_justifications = subscribe_justifications;
+        self
+    }
+
     pub async fn build(self) -> AnyResult<Relay<S, R>> {
         let sender = self.sender.expect("sender client is needed");
         let receiver = self.receiver.expect("receiver client is needed");
@@ -95,17 +121,33 @@ where
             return Err(anyhow::anyhow!("Error! Sender is NOT a Substrate Network!"));
         };
 
+        let receiver_network_id = receiver.constant_fetch_or_default(&R::network_id())?;
+
         let latest_beefy_block = sender
             .storage_fetch_or_default(&R::latest_beefy_block(sender_network_id), ())
             .await?;
         syncer.update_latest_sent(latest_beefy_block);
+        let metrics = RelayMetrics::new(
+            GenericNetworkId::Sub(sender_network_id),
+            receiver_network_id,
+        );
+        metrics.set_latest_beefy_block(latest_beefy_block);
         Ok(Relay {
+            metrics,
             sender,
             receiver,
             successful_sent: Default::default(),
             failed_to_sent: Default::default(),
             syncer,
             sender_network_id,
+            receiver_network_id,
+            subscribe_justifications: self.subscribe_justifications,
+            vset_boundaries: Default::default(),
+            heartbeat: LoopHeartbeat::register_kind(
+                "BEEFY",
+                sender_network_id,
+                receiver_network_id,
+            ),
         })
     }
 }
@@ -118,6 +160,11 @@ pub struct Relay<S: SenderConfig, R: ReceiverConfig> {
     failed_to_sent: Arc<AtomicU64>,
     syncer: BeefySyncer,
     sender_network_id: SubNetworkId,
+    receiver_network_id: GenericNetworkId,
+    metrics: RelayMetrics,
+    subscribe_justifications: bool,
+    vset_boundaries: ValidatorSetBoundaries,
+    heartbeat: LoopHeartbeat,
 }
 
 impl<S, R> Relay<S, R>
@@ -176,63 +223,224 @@ where
         Ok(call)
     }
 
+    /// Sends commitment and waits until it is finalized on the receiver.
     pub async fn send_commitment(self, justification: BeefyJustification<S>) -> AnyResult<()> {
+        let block_number: u64 = justification.commitment.block_number.into();
+        let pending = self.broadcast_commitment(&justification).await?;
+        self.wait_commitment(pending, block_number, true).await
+    }
+
+    /// Submits commitment to the receiver without waiting for its inclusion,
+    /// so the next commitment can be submitted with the next nonce right away.
+    async fn broadcast_commitment(
+        &self,
+        justification: &BeefyJustification<S>,
+    ) -> AnyResult<PendingExtrinsic<R>> {
         debug!("New justification: {:?}", justification);
-        let call = self.submit_signature_commitment(&justification).await?;
-        self.receiver.submit_extrinsic(&call).await?;
-        self.syncer
-            .update_latest_sent(justification.commitment.block_number.into());
+        justification.verify()?;
+        let call = self.submit_signature_commitment(justification).await?;
+        let res = self.receiver.broadcast_extrinsic(&call).await;
+        if let Err(err) = &res {
+            let block_number: u64 = justification.commitment.block_number.into();
+            HistoryEntry::new(
+                EntryKind::BeefyCommitment,
+                self.sender_network_id,
+                self.receiver_network_id,
+            )
+            .with_block_number(block_number)
+            .with_error(err)
+            .record();
+        }
+        res
+    }
+
+    async fn wait_commitment(
+        &self,
+        pending: PendingExtrinsic<R>,
+        block_number: u64,
+        finalized: bool,
+    ) -> AnyResult<()> {
+        let res = if finalized {
+            pending.wait_finalized().await
+        } else {
+            pending.wait().await
+        };
+        HistoryEntry::new(
+            EntryKind::BeefyCommitment,
+            self.sender_network_id,
+            self.receiver_network_id,
+        )
+        .with_block_number(block_number)
+        .record_extrinsic(&res);
+        if advance_syncer(&self.syncer, &res?, block_number) {
+            self.metrics.set_latest_beefy_block(block_number);
+        }
+        Ok(())
+    }
+
+    /// Sends mandatory commitments of all validator sets changed since the
+    /// latest BEEFY block known to the receiver, one after another. Each one
+    /// is finalized before the next, so the light client can't lose a set.
+    async fn catch_up(&self) -> AnyResult<()> {
+        let latest_sent = self.syncer.latest_sent();
+        let finalized = self
+            .sender
+            .block_number(BlockNumberOrHash::Finalized)
+            .await?;
+        let boundaries = self
+            .vset_boundaries
+            .scan(&self.sender, latest_sent.unique_saturated_into(), finalized)
+            .await?;
+        if boundaries.is_empty() {
+            return Ok(());
+        }
+        let total = boundaries.len();
+        info!(
+            "Catching up {} validator set changes after BEEFY block {}",
+            total, latest_sent
+        );
+        for (i, (vset_id, block)) in boundaries.into_iter().enumerate() {
+            self.heartbeat.beat();
+            let block_number: u64 = block.into();
+            let justification = get_commitment_from_block(&self.sender, block, true)
+                .await?
+                .ok_or(anyhow!(
+                    "Mandatory commitment for validator set {} not found at block {}",
+                    vset_id,
+                    block_number
+                ))?;
+            self.clone()
+                .send_commitment(justification)
+                .await
+                .context(format!(
+                    "send mandatory commitment for validator set {}",
+                    vset_id
+                ))?;
+            self.successful_sent.fetch_add(1, Ordering::Relaxed);
+            self.metrics.commitment_submitted();
+            info!(
+                "Caught up validator set {} at block {} ({}/{})",
+                vset_id,
+                block_number,
+                i + 1,
+                total
+            );
+        }
         Ok(())
     }
 
     pub async fn run(&self, ignore_unneeded_commitments: bool) -> AnyResult<()> {
+        self.catch_up().await?;
         let mut beefy_sub = crate::substrate::beefy_subscription::subscribe_beefy_justifications(
             self.sender.clone(),
             self.syncer.latest_sent(),
+            self.subscribe_justifications,
+            self.vset_boundaries.clone(),
         )
         .await?;
+        // Commitments are broadcast with sequential nonces, so the next one
+        // is submitted while the previous ones are still in the pool.
+        let mut in_flight = FuturesOrdered::new();
         let mut first_attempt_failed = false;
-        while let Some(justification) = beefy_sub.next().await.transpose()? {
-            let latest_requested = self.syncer.latest_requested();
-            let latest_sent = self.syncer.latest_sent();
-            let is_mandatory = justification.is_mandatory;
-            let should_send = !ignore_unneeded_commitments
-                || is_mandatory
-                || (latest_requested < justification.commitment.block_number.into()
-                    && latest_sent < latest_requested);
-
-            if should_send {
-                // TODO: Better async message handler
-                if let Err(_) = self
-                    .clone()
-                    .send_commitment(justification)
-                    .await
-                    .map_err(|e| {
-                        warn!("Send commitment error: {}", e);
-                    })
-                {
-                    if first_attempt_failed || is_mandatory {
-                        return Err(anyhow::anyhow!(
-                            "Unable to send commitment, possibly BEEFY state is broken"
-                        ));
+        loop {
+            let (is_mandatory, res) = tokio::select! {
+                Some(sent) = in_flight.next() => sent,
+                justification = beefy_sub.next() => {
+                    let Some(justification) = justification.transpose()? else {
+                        break;
+                    };
+                    self.heartbeat.beat();
+                    let is_mandatory = justification.is_mandatory;
+                    let should_send = !ignore_unneeded_commitments
+                        || is_mandatory
+                        || self
+                            .syncer
+                            .is_requested(justification.commitment.block_number.into());
+                    if !should_send {
+                        info!(
+                            "Skip BEEFY commitment because there is no messages: {:?}",
+                            justification
+                        );
+                        continue;
+                    }
+                    let block_number: u64 = justification.commitment.block_number.into();
+                    match self.broadcast_commitment(&justification).await {
+                        Ok(pending) => {
+                            let relay = self.clone();
+                            in_flight.push_back(async move {
+                                let res = relay.wait_commitment(pending, block_number, false).await;
+                                (is_mandatory, res)
+                            });
+                            continue;
+                        }
+                        Err(err) => (is_mandatory, Err(err)),
                     }
-                    first_attempt_failed = true;
-                } else {
-                    first_attempt_failed = false;
                 }
-                info!(
-                    "failed: {}, successfull: {}",
-                    self.failed_to_sent.load(Ordering::Relaxed),
-                    self.successful_sent.load(Ordering::Relaxed)
-                );
-            } else {
-                info!(
-                    "Skip BEEFY commitment because there is no messages: {:?}",
-                    justification
-                );
-            }
+            };
+            self.handle_sent(is_mandatory, res, &mut first_attempt_failed)?;
+        }
+        while let Some((is_mandatory, res)) = in_flight.next().await {
+            self.handle_sent(is_mandatory, res, &mut first_attempt_failed)?;
         }
 
         Ok(())
     }
+
+    fn handle_sent(
+        &self,
+        is_mandatory: bool,
+        res: AnyResult<()>,
+        first_attempt_failed: &mut bool,
+    ) -> AnyResult<()> {
+        if let Err(e) = res {
+            warn!("Send commitment error: {}", e);
+            self.failed_to_sent.fetch_add(1, Ordering::Relaxed);
+            self.metrics.commitment_failed();
+            if *first_attempt_failed || is_mandatory {
+                return Err(anyhow::anyhow!(
+                    "Unable to send commitment, possibly BEEFY state is broken"
+                ));
+            }
+            *first_attempt_failed = true;
+        } else {
+            self.successful_sent.fetch_add(1, Ordering::Relaxed);
+            self.metrics.commitment_submitted();
+            *first_attempt_failed = false;
+        }
+        info!(
+            "failed: {}, successfull: {}",
+            self.failed_to_sent.load(Ordering::Relaxed),
+            self.successful_sent.load(Ordering::Relaxed)
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn included_commitment_advances_syncer() {
+        let syncer = BeefySyncer::new();
+        syncer.update_latest_sent(10);
+        let submitted = SubmittedExtrinsic {
+            block_hash: Some(Default::default()),
+            ..Default::default()
+        };
+        assert!(advance_syncer(&syncer, &submitted, 20));
+        assert_eq!(syncer.latest_sent(), 20);
+    }
+
+    #[test]
+    fn dry_run_keeps_syncer_state() {
+        let syncer = BeefySyncer::new();
+        syncer.update_latest_sent(10);
+        let submitted = SubmittedExtrinsic {
+            dry_run: true,
+            ..Default::default()
+        };
+        assert!(!advance_syncer(&syncer, &submitted, 20));
+        assert_eq!(syncer.latest_sent(), 10);
+    }
 }
@@ -34,41 +34,53 @@
 use std::collections::BTreeMap;
 
 use super::beefy_syncer::BeefySyncer;
+use crate::health::LoopHeartbeat;
+use crate::history::{EntryKind, HistoryEntry};
+use crate::metrics::RelayMetrics;
 use crate::prelude::*;
-use crate::substrate::{BlockNumber, BlockNumberOrHash, OtherParams};
+use crate::relay::pipeline::submit_in_order;
+use crate::relay::retry::RetryBackoff;
+use crate::relay::traits::{
+    pending_nonces, ExtrinsicSubmitter, InboundChannelReader, MessageProofReader,
+    OutboundChannelReader, PendingSubmission,
+};
+use crate::substrate::UnboundedGenericCommitment;
 use bridge_types::{GenericNetworkId, SubNetworkId};
+use futures::StreamExt;
 
-pub struct RelayBuilder<S: SenderConfig, R: ReceiverConfig> {
-    sender: Option<SubUnsignedClient<S>>,
-    receiver: Option<SubUnsignedClient<R>>,
+pub struct RelayBuilder<S, R> {
+    sender: Option<S>,
+    receiver: Option<R>,
     syncer: Option<BeefySyncer>,
+    pipeline_window: usize,
 }
 
-impl<S: SenderConfig, R: ReceiverConfig> Default for RelayBuilder<S, R> {
+impl<S, R> Default for RelayBuilder<S, R> {
     fn default() -> Self {
         Self {
             sender: None,
             receiver: None,
             syncer: None,
+            pipeline_window: 1,
         }
     }
 }
 
 impl<S, R> RelayBuilder<S, R>
 where
-    S: SenderConfig,
-    R: ReceiverConfig,
+    S: OutboundChannelReader + MessageProofReader,
+    R: InboundChannelReader + ExtrinsicSubmitter<S::Proof>,
 {
     pub fn new() -> Self {
         Default::default()
     }
 
-    pub fn with_sender_client(mut self, sender: SubUnsignedClient<S>) -> Self {
+    pub fn with_sender_client(mut self, sender: S) -> Self {
         self.sender = Some(sender);
         self
     }
 
-    pub fn with_receiver_client(mut self, receiver: SubUnsignedClient<R>) -> Self {
+    pub fn with_receiver_client(mut self, receiver: R) -> Self {
         self.receiver = Some(receiver);
         self
     }
@@ -78,17 +90,24 @@ where
         self
     }
 
+    /// Number of commitments with proofs loaded ahead and kept in the
+    /// transaction pool at the same time.
+    pub fn with_pipeline_window(mut self, pipeline_window: usize) -> Self {
+        self.pipeline_window = pipeline_window;
+        self
+    }
+
     pub async fn build(self) -> AnyResult<Relay<S, R>> {
         let sender = self.sender.expect("sender client is needed");
         let receiver = self.receiver.expect("receiver client is needed");
         let syncer = self.syncer.expect("syncer is needed");
-        let sender_network_id = sender.constant_fetch_or_default(&S::network_id())?;
+        let sender_network_id = OutboundChannelReader::network_id(&sender)?;
 
         let GenericNetworkId::Sub(sender_network_id) = sender_network_id else {
             return Err(anyhow::anyhow!("Error! Sender is NOT a Substrate Network!"));
         };
 
-        let receiver_network_id = receiver.constant_fetch_or_default(&R::network_id())?;
+        let receiver_network_id = InboundChannelReader::network_id(&receiver)?;
 
         let GenericNetworkId::Sub(receiver_network_id) = receiver_network_id else {
             return Err(anyhow::anyhow!(
@@ -97,10 +116,16 @@ where
         };
 
         Ok(Relay {
+            metrics: RelayMetrics::new(
+                GenericNetworkId::Sub(sender_network_id),
+                GenericNetworkId::Sub(receiver_network_id),
+            ),
+            heartbeat: LoopHeartbeat::register(sender_network_id, receiver_network_id),
             sender,
             receiver,
             syncer,
-            commitment_blocks: Default::default(),
+            beefy_blocks: Default::default(),
+            pipeline_window: self.pipeline_window,
             receiver_network_id,
             sender_network_id,
         })
@@ -108,111 +133,415 @@ where
 }
 
 #[derive(Clone)]
-pub struct Relay<S: SenderConfig, R: ReceiverConfig> {
-    sender: SubUnsignedClient<S>,
-    receiver: SubUnsignedClient<R>,
-    commitment_blocks: BTreeMap<u64, BlockNumber<S>>,
+pub struct Relay<S, R> {
+    sender: S,
+    receiver: R,
+    beefy_blocks: BTreeMap<u64, u64>,
+    pipeline_window: usize,
     syncer: BeefySyncer,
     receiver_network_id: SubNetworkId,
     sender_network_id: SubNetworkId,
+    metrics: RelayMetrics,
+    heartbeat: LoopHeartbeat,
 }
 
 impl<S, R> Relay<S, R>
 where
-    S: SenderConfig,
-    R: ReceiverConfig,
-    OtherParams<R>: Default,
+    S: OutboundChannelReader + MessageProofReader,
+    R: InboundChannelReader + ExtrinsicSubmitter<S::Proof>,
 {
     async fn send_commitment(&self, batch_nonce: u64) -> AnyResult<()> {
+        let inbound_channel_nonce = self.inbound_channel_nonce().await?;
+        let Some((commitment, proof)) = self
+            .prepare_commitment(batch_nonce, inbound_channel_nonce)
+            .await?
+        else {
+            return Ok(());
+        };
+        self.broadcast_commitment(batch_nonce, commitment, proof)
+            .await?
+            .await?;
+        Ok(())
+    }
+
+    /// Loads the commitment with proof, `None` if it is already sent.
+    async fn prepare_commitment(
+        &self,
+        batch_nonce: u64,
+        inbound_channel_nonce: u64,
+    ) -> AnyResult<Option<(UnboundedGenericCommitment, S::Proof)>> {
         info!("Sending channel commitment with nonce {:?}", batch_nonce);
         let latest_sent = self.syncer.latest_sent();
-        let commitment = super::messages_subscription::load_commitment_with_proof(
-            &self.sender,
-            self.receiver_network_id.into(),
-            batch_nonce,
-            latest_sent as u32,
-        )
-        .await?;
-        let inbound_channel_nonce = self.inbound_channel_nonce().await?;
-        if commitment.offchain_data.commitment.nonce() <= inbound_channel_nonce {
+        let (commitment, proof) = self
+            .sender
+            .commitment_with_proof(
+                self.receiver_network_id.into(),
+                batch_nonce,
+                latest_sent as u32,
+            )
+            .await?;
+        if commitment.nonce() <= inbound_channel_nonce {
             info!("Channel commitment is already sent");
-            return Ok(());
+            return Ok(None);
         }
+        Ok(Some((commitment, proof)))
+    }
 
-        let payload = R::submit_messages_commitment(
-            self.sender_network_id,
-            commitment.offchain_data.commitment,
-            R::beefy_proof(beefy_light_client::SubstrateBridgeMessageProof {
-                proof: commitment.proof,
-                leaf: commitment.leaf,
-                digest: commitment.digest,
-            }),
-        );
-
+    /// Submits the commitment, returned future records the result once it is included.
+    async fn broadcast_commitment(
+        &self,
+        batch_nonce: u64,
+        commitment: UnboundedGenericCommitment,
+        proof: S::Proof,
+    ) -> AnyResult<PendingSubmission> {
         info!("Sending channel commitment");
-        self.receiver.submit_unsigned_extrinsic(&payload).await?;
-        Ok(())
+        let commitment_hash = commitment.hash();
+        let history = HistoryEntry::new(
+            EntryKind::InboundCommitment,
+            self.sender_network_id,
+            self.receiver_network_id,
+        )
+        .with_nonce(batch_nonce)
+        .with_commitment_hash(commitment_hash);
+        let metrics = self.metrics.clone();
+        let res = self
+            .receiver
+            .broadcast_messages_commitment(self.sender_network_id.into(), commitment, proof)
+            .await;
+        let pending = match res {
+            Ok(pending) => pending,
+            Err(err) => {
+                history.with_error(&err).record();
+                metrics.commitment_failed();
+                return Err(err);
+            }
+        };
+        Ok(Box::pin(async move {
+            let res = pending.await;
+            history.record_extrinsic(&res);
+            match res {
+                Ok(_) => metrics.commitment_submitted(),
+                Err(_) => metrics.commitment_failed(),
+            }
+            res
+        }))
     }
 
     async fn inbound_channel_nonce(&self) -> AnyResult<u64> {
-        let storage = R::substrate_bridge_inbound_nonce(self.sender_network_id);
-        let nonce = self.receiver.storage_fetch_or_default(&storage, ()).await?;
-        Ok(nonce)
+        self.receiver
+            .inbound_nonce(self.sender_network_id.into())
+            .await
     }
 
     async fn outbound_channel_nonce(&self) -> AnyResult<u64> {
-        let nonce = self
-            .sender
-            .storage_fetch_or_default(
-                &S::bridge_outbound_nonce(self.receiver_network_id.into()),
-                BlockNumberOrHash::Finalized,
-            )
-            .await?;
-        Ok(nonce)
+        self.sender
+            .outbound_nonce(self.receiver_network_id.into())
+            .await
+    }
+
+    /// Sends commitments which blocks are already known to the light client
+    /// on the receiving network.
+    async fn relay_commitments(&mut self) -> AnyResult<()> {
+        let inbound_nonce = self.inbound_channel_nonce().await?;
+        let outbound_nonce = self.outbound_channel_nonce().await?;
+        self.metrics.set_nonces(inbound_nonce, outbound_nonce);
+        let latest_sent = self.syncer.latest_sent();
+        let mut ready = vec![];
+        // Commitments before the one which failed to load are still sent
+        let mut load_error = None;
+        for nonce in pending_nonces(inbound_nonce, outbound_nonce) {
+            let beefy_block = match self.beefy_blocks.get(&nonce) {
+                Some(beefy_block) => *beefy_block,
+                None => match self
+                    .sender
+                    .commitment_beefy_block(self.receiver_network_id.into(), nonce)
+                    .await
+                {
+                    Ok(Some(beefy_block)) => {
+                        self.beefy_blocks.insert(nonce, beefy_block);
+                        beefy_block
+                    }
+                    Ok(None) => {
+                        debug!("MMR leaf of commitment {} is not produced yet", nonce);
+                        break;
+                    }
+                    Err(err) => {
+                        load_error = Some(err);
+                        break;
+                    }
+                },
+            };
+            if beefy_block > latest_sent {
+                debug!("Waiting for BEEFY block {:?}", beefy_block);
+                self.syncer.update_latest_requested(beefy_block);
+                break;
+            }
+            ready.push(nonce);
+        }
+        let relay = &*self;
+        let prepared = futures::stream::iter(ready)
+            .map(|nonce| async move {
+                let prepared = relay.prepare_commitment(nonce, inbound_nonce).await?;
+                Ok(prepared.map(|prepared| (nonce, prepared)))
+            })
+            .buffered(relay.pipeline_window.max(1));
+        let (last_included, res) = submit_in_order(
+            prepared,
+            relay.pipeline_window,
+            |nonce, (commitment, proof)| relay.broadcast_commitment(nonce, commitment, proof),
+        )
+        .await;
+        if let Some(last_included) = last_included {
+            self.beefy_blocks = self.beefy_blocks.split_off(&(last_included + 1));
+        }
+        if let Err(err) = res.and(load_error.map_or(Ok(()), Err)) {
+            return Err(anyhow!("Error sending message commitment: {:?}", err));
+        }
+        Ok(())
     }
 
     pub async fn run(mut self) -> AnyResult<()> {
-        let mut interval = tokio::time::interval(S::average_block_time());
+        let mut interval = tokio::time::interval(self.sender.average_block_time());
+        let mut backoff = RetryBackoff::new(self.sender.average_block_time());
         loop {
             interval.tick().await;
-            let inbound_nonce = self.inbound_channel_nonce().await?;
-            let outbound_nonce = self.outbound_channel_nonce().await?;
-            if inbound_nonce >= outbound_nonce {
-                if inbound_nonce > outbound_nonce {
-                    error!(
-                        "Inbound channel nonce is higher than outbound channel nonce: {} > {}",
-                        inbound_nonce, outbound_nonce
-                    );
-                }
-                continue;
-            }
-            for nonce in (inbound_nonce + 1)..=outbound_nonce {
-                let block_number = match self.commitment_blocks.entry(nonce) {
-                    std::collections::btree_map::Entry::Vacant(v) => {
-                        let offchain_data = self
-                            .sender
-                            .commitment_with_nonce(
-                                self.receiver_network_id.into(),
-                                nonce,
-                                BlockNumberOrHash::Finalized,
-                            )
-                            .await?;
-                        v.insert(offchain_data.block_number);
-                        offchain_data.block_number
-                    }
-                    std::collections::btree_map::Entry::Occupied(v) => v.get().clone(),
-                };
-                let latest_sent = self.syncer.latest_sent();
-                if Into::<u64>::into(block_number) > latest_sent {
-                    debug!("Waiting for BEEFY block {:?}", block_number);
-                    break;
-                }
-                self.send_commitment(nonce).await?;
-                if let Err(err) = self.send_commitment(nonce).await {
-                    return Err(anyhow!("Error sending message commitment: {:?}", err));
-                }
-                self.commitment_blocks.remove(&nonce);
+            self.heartbeat.beat();
+            backoff.handle(self.relay_commitments().await).await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::relay::fakes::{wait_until, FakeChain};
+
+    async fn setup(syncer: &BeefySyncer) -> (FakeChain, FakeChain, Relay<FakeChain, FakeChain>) {
+        setup_with_window(syncer, 1).await
+    }
+
+    async fn setup_with_window(
+        syncer: &BeefySyncer,
+        pipeline_window: usize,
+    ) -> (FakeChain, FakeChain, Relay<FakeChain, FakeChain>) {
+        let sender = FakeChain::new(GenericNetworkId::Sub(SubNetworkId::Mainnet));
+        let receiver = FakeChain::new(GenericNetworkId::Sub(SubNetworkId::Kusama));
+        let relay = RelayBuilder::new()
+            .with_sender_client(sender.clone())
+            .with_receiver_client(receiver.clone())
+            .with_syncer(syncer.clone())
+            .with_pipeline_window(pipeline_window)
+            .build()
+            .await
+            .unwrap();
+        (sender, receiver, relay)
+    }
+
+    #[tokio::test]
+    async fn waits_for_beefy_block() {
+        let syncer = BeefySyncer::new();
+        let (sender, receiver, mut relay) = setup(&syncer).await;
+        sender.send_commitment(5);
+        sender.send_commitment(10);
+        relay.relay_commitments().await.unwrap();
+        assert!(receiver.state().accepted.is_empty());
+
+        syncer.update_latest_sent(7);
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1]);
+
+        // Commitment is proven by the leaf of the next block
+        syncer.update_latest_sent(10);
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1]);
+
+        syncer.update_latest_sent(11);
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1, 2]);
+        assert!(relay.beefy_blocks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn requests_beefy_block_of_pending_commitment() {
+        let syncer = BeefySyncer::new();
+        let (sender, receiver, mut relay) = setup(&syncer).await;
+        sender.send_commitment(5);
+        sender.send_commitment(10);
+        syncer.update_latest_sent(3);
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(syncer.latest_requested(), 6);
+        assert!(receiver.state().accepted.is_empty());
+
+        syncer.update_latest_sent(6);
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1]);
+        assert_eq!(syncer.latest_requested(), 11);
+    }
+
+    #[tokio::test]
+    async fn delivers_messages_with_requested_beefy_commitments() {
+        let syncer = BeefySyncer::new();
+        let (sender, receiver, mut relay) = setup(&syncer).await;
+        sender.send_commitment(4);
+        sender.send_commitment(5);
+        sender.send_commitment(10);
+        let mut sent_beefy_blocks = vec![];
+        // BEEFY justifications arrive every second block and only requested ones are sent
+        for beefy_block in (2..=20).step_by(2) {
+            relay.relay_commitments().await.unwrap();
+            if syncer.is_requested(beefy_block) {
+                syncer.update_latest_sent(beefy_block);
+                sent_beefy_blocks.push(beefy_block);
             }
         }
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1, 2, 3]);
+        assert_eq!(sent_beefy_blocks, vec![6, 12]);
+    }
+
+    #[tokio::test]
+    async fn relays_only_commitments_after_inbound_nonce() {
+        let syncer = BeefySyncer::new();
+        let (sender, receiver, mut relay) = setup(&syncer).await;
+        for block in 1..=4 {
+            sender.send_commitment(block);
+        }
+        receiver.state().inbound_nonce = 2;
+        syncer.update_latest_sent(5);
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![3, 4]);
+    }
+
+    #[tokio::test]
+    async fn does_nothing_when_inbound_nonce_is_ahead() {
+        let syncer = BeefySyncer::new();
+        let (sender, receiver, mut relay) = setup(&syncer).await;
+        sender.send_commitment(1);
+        receiver.state().inbound_nonce = 3;
+        syncer.update_latest_sent(2);
+        relay.relay_commitments().await.unwrap();
+        assert!(receiver.state().accepted.is_empty());
+        assert!(receiver.state().rejected.is_empty());
+    }
+
+    #[tokio::test]
+    async fn skips_already_sent_commitment() {
+        let syncer = BeefySyncer::new();
+        let (sender, receiver, relay) = setup(&syncer).await;
+        sender.send_commitment(1);
+        syncer.update_latest_sent(2);
+        relay.send_commitment(1).await.unwrap();
+        relay.send_commitment(1).await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1]);
+        assert!(receiver.state().rejected.is_empty());
+    }
+
+    #[tokio::test]
+    async fn fails_on_missing_commitment() {
+        let syncer = BeefySyncer::new();
+        let (sender, receiver, mut relay) = setup(&syncer).await;
+        for block in 1..=3 {
+            sender.send_commitment(block);
+        }
+        sender.state().outbound.remove(&2);
+        syncer.update_latest_sent(3);
+        assert!(relay.relay_commitments().await.is_err());
+        assert_eq!(receiver.state().accepted, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn relays_backlog_with_pipeline_window() {
+        let syncer = BeefySyncer::new();
+        let (sender, receiver, mut relay) = setup_with_window(&syncer, 3).await;
+        for block in 1..=5 {
+            sender.send_commitment(block);
+        }
+        syncer.update_latest_sent(5);
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1, 2, 3, 4]);
+        assert_eq!(relay.beefy_blocks.keys().collect::<Vec<_>>(), vec![&5]);
+        assert_eq!(receiver.state().max_pool_size, 3);
+
+        syncer.update_latest_sent(6);
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1, 2, 3, 4, 5]);
+        assert!(receiver.state().rejected.is_empty());
+    }
+
+    #[tokio::test]
+    async fn pipeline_resubmits_commitments_rejected_by_strict_pool() {
+        let syncer = BeefySyncer::new();
+        let (sender, receiver, mut relay) = setup_with_window(&syncer, 3).await;
+        receiver.state().strict_pool = true;
+        for block in 1..=3 {
+            sender.send_commitment(block);
+        }
+        syncer.update_latest_sent(4);
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1, 2, 3]);
+        assert_eq!(receiver.state().rejected, vec![2, 3]);
+        assert!(relay.beefy_blocks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn prunes_beefy_blocks_of_included_commitments_on_failure() {
+        let syncer = BeefySyncer::new();
+        let (sender, receiver, mut relay) = setup_with_window(&syncer, 2).await;
+        for block in 1..=3 {
+            sender.send_commitment(block);
+        }
+        syncer.update_latest_sent(4);
+        // Block of the commitment is known, but the commitment can't be loaded
+        relay.beefy_blocks.insert(2, 3);
+        sender.state().outbound.remove(&2);
+        assert!(relay.relay_commitments().await.is_err());
+        assert_eq!(receiver.state().accepted, vec![1]);
+        assert_eq!(relay.beefy_blocks.keys().collect::<Vec<_>>(), vec![&2, &3]);
+    }
+
+    #[tokio::test]
+    async fn pipeline_stops_at_missing_commitment() {
+        let syncer = BeefySyncer::new();
+        let (sender, receiver, mut relay) = setup_with_window(&syncer, 4).await;
+        for block in 1..=4 {
+            sender.send_commitment(block);
+        }
+        sender.state().outbound.remove(&3);
+        syncer.update_latest_sent(5);
+        assert!(relay.relay_commitments().await.is_err());
+        assert_eq!(receiver.state().accepted, vec![1, 2]);
+        assert!(receiver.state().rejected.is_empty());
+    }
+
+    #[tokio::test]
+    async fn waits_for_mmr_leaf_of_commitment() {
+        let syncer = BeefySyncer::new();
+        let (sender, receiver, mut relay) = setup(&syncer).await;
+        sender.send_commitment(3);
+        sender.send_commitment(5);
+        sender.state().best_block = Some(5);
+        syncer.update_latest_sent(10);
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1]);
+        assert!(relay.beefy_blocks.is_empty());
+
+        sender.state().best_block = Some(6);
+        relay.relay_commitments().await.unwrap();
+        assert_eq!(receiver.state().accepted, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn keeps_running_after_transport_error() {
+        let syncer = BeefySyncer::new();
+        let (sender, receiver, relay) = setup(&syncer).await;
+        sender.send_commitment(5);
+        syncer.update_latest_sent(6);
+        sender.state().transport_failures = 1;
+        let relay = tokio::spawn(relay.run());
+        wait_until(|| !receiver.state().accepted.is_empty()).await;
+        assert!(!relay.is_finished());
+        relay.abort();
+        assert_eq!(sender.state().transport_failures, 0);
+        assert_eq!(receiver.state().accepted, vec![1]);
     }
 }
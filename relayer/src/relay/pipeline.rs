@@ -0,0 +1,281 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Submission of commitments which are prepared ahead and kept in the
+//! transaction pool while the previous ones are waiting for inclusion.
+
+use std::collections::VecDeque;
+
+use crate::prelude::*;
+use crate::relay::traits::PendingSubmission;
+use futures::{Future, Stream, StreamExt};
+
+/// Submits prepared commitments in nonce order, keeping up to `window` of
+/// them in the transaction pool at the same time.
+///
+/// `prepared` yields commitments in nonce order, `None` for a commitment
+/// which can't be submitted yet. Commitments after it are still prepared,
+/// which lets relays send approvals ahead, but not submitted. Each
+/// commitment is submitted right after the previous one is accepted to the
+/// pool, without waiting for its inclusion, and the pool includes them in
+/// nonce order. A receiver which checks the nonce against its current state
+/// rejects commitments sent ahead, so a rejected commitment is submitted
+/// once more after the ones in the pool are included. After the first
+/// failure nothing else is submitted and the next round starts from the
+/// inbound nonce again.
+///
+/// Returns the nonce of the last included commitment together with the
+/// result, so the progress made before a failure is not lost.
+pub async fn submit_in_order<T, P, F, Fut>(
+    prepared: P,
+    window: usize,
+    submit: F,
+) -> (Option<u64>, AnyResult<()>)
+where
+    T: Clone,
+    P: Stream<Item = AnyResult<Option<(u64, T)>>>,
+    F: Fn(u64, T) -> Fut,
+    Fut: Future<Output = AnyResult<PendingSubmission>>,
+{
+    futures::pin_mut!(prepared);
+    let window = window.max(1);
+    let mut in_flight = VecDeque::new();
+    let mut last_included = None;
+    let mut stopped = false;
+    let mut res = Ok(());
+    while let Some(item) = prepared.next().await {
+        let (nonce, item) = match item {
+            Ok(Some(item)) if !stopped => item,
+            Ok(_) => {
+                stopped = true;
+                continue;
+            }
+            Err(err) => {
+                res = Err(err);
+                break;
+            }
+        };
+        if let Err(err) = include(&mut in_flight, window - 1, &mut last_included).await {
+            res = Err(err);
+            break;
+        }
+        let pending = match submit(nonce, item.clone()).await {
+            Ok(pending) => pending,
+            Err(err) if in_flight.is_empty() => {
+                res = Err(err);
+                break;
+            }
+            Err(err) => {
+                debug!(
+                    "Commitment {} is rejected while previous ones are in the pool, retrying after their inclusion: {:?}",
+                    nonce, err
+                );
+                if let Err(err) = include(&mut in_flight, 0, &mut last_included).await {
+                    res = Err(err);
+                    break;
+                }
+                match submit(nonce, item).await {
+                    Ok(pending) => pending,
+                    Err(err) => {
+                        res = Err(err);
+                        break;
+                    }
+                }
+            }
+        };
+        in_flight.push_back((nonce, pending));
+    }
+    // Commitments which are already in the pool are waited even after a failure
+    let included = include(&mut in_flight, 0, &mut last_included).await;
+    (last_included, res.and(included))
+}
+
+/// Waits for inclusion of the oldest submitted commitments until at most
+/// `keep` of them are left in the pool.
+async fn include(
+    in_flight: &mut VecDeque<(u64, PendingSubmission)>,
+    keep: usize,
+    last_included: &mut Option<u64>,
+) -> AnyResult<()> {
+    while in_flight.len() > keep {
+        let (nonce, pending) = in_flight.pop_front().expect("pool is not empty");
+        if let Err(err) = pending.await {
+            // Commitments after the failed one can't be included anymore
+            in_flight.clear();
+            return Err(err);
+        }
+        *last_included = Some(nonce);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::substrate::SubmittedExtrinsic;
+    use std::sync::{Arc, Mutex};
+
+    fn prepared(nonces: Vec<Option<u64>>) -> impl Stream<Item = AnyResult<Option<(u64, ())>>> {
+        futures::stream::iter(
+            nonces
+                .into_iter()
+                .map(|nonce| Ok(nonce.map(|nonce| (nonce, ())))),
+        )
+    }
+
+    #[derive(Default)]
+    struct Receiver {
+        inbound_nonce: u64,
+        pool: Vec<u64>,
+        max_pool_size: usize,
+        accepted: Vec<u64>,
+        rejected: Vec<u64>,
+    }
+
+    /// Submits commitments to a receiver which checks the nonce against the
+    /// transaction pool (or only against its state if `strict`) and includes
+    /// the commitment once the pending future is awaited, failing on `fail_nonce`.
+    async fn run(
+        nonces: Vec<Option<u64>>,
+        window: usize,
+        strict: bool,
+        fail_nonce: Option<u64>,
+    ) -> (Option<u64>, AnyResult<()>, Receiver) {
+        let receiver = Arc::new(Mutex::new(Receiver::default()));
+        let (last_included, res) = submit_in_order(prepared(nonces), window, |nonce, _| {
+            let receiver = receiver.clone();
+            async move {
+                let mut state = receiver.lock().unwrap();
+                let expected = match state.pool.last() {
+                    Some(pooled) if !strict => pooled + 1,
+                    Some(_) => state.inbound_nonce,
+                    None => state.inbound_nonce + 1,
+                };
+                if nonce != expected {
+                    state.rejected.push(nonce);
+                    return Err(anyhow!("Invalid nonce {}", nonce));
+                }
+                state.pool.push(nonce);
+                state.max_pool_size = state.max_pool_size.max(state.pool.len());
+                drop(state);
+                let pending: PendingSubmission = Box::pin(async move {
+                    let mut state = receiver.lock().unwrap();
+                    state.pool.retain(|pooled| *pooled != nonce);
+                    if Some(nonce) == fail_nonce || nonce != state.inbound_nonce + 1 {
+                        return Err(anyhow!("Commitment {} failed", nonce));
+                    }
+                    state.inbound_nonce = nonce;
+                    state.accepted.push(nonce);
+                    Ok(SubmittedExtrinsic::default())
+                });
+                Ok(pending)
+            }
+        })
+        .await;
+        let receiver = std::mem::take(&mut *receiver.lock().unwrap());
+        (last_included, res, receiver)
+    }
+
+    #[tokio::test]
+    async fn submits_after_previous_is_included() {
+        let nonces = (1..=5).map(Some).collect();
+        let (last_included, res, receiver) = run(nonces, 1, false, None).await;
+        res.unwrap();
+        assert_eq!(last_included, Some(5));
+        assert_eq!(receiver.accepted, vec![1, 2, 3, 4, 5]);
+        assert_eq!(receiver.max_pool_size, 1);
+    }
+
+    #[tokio::test]
+    async fn keeps_window_of_commitments_in_pool() {
+        let nonces = (1..=5).map(Some).collect();
+        let (last_included, res, receiver) = run(nonces, 3, false, None).await;
+        res.unwrap();
+        assert_eq!(last_included, Some(5));
+        assert_eq!(receiver.accepted, vec![1, 2, 3, 4, 5]);
+        assert!(receiver.rejected.is_empty());
+        assert_eq!(receiver.max_pool_size, 3);
+    }
+
+    #[tokio::test]
+    async fn resubmits_commitment_rejected_ahead_of_inclusion() {
+        let nonces = (1..=3).map(Some).collect();
+        let (last_included, res, receiver) = run(nonces, 3, true, None).await;
+        res.unwrap();
+        assert_eq!(last_included, Some(3));
+        assert_eq!(receiver.accepted, vec![1, 2, 3]);
+        assert_eq!(receiver.rejected, vec![2, 3]);
+        assert_eq!(receiver.max_pool_size, 1);
+    }
+
+    #[tokio::test]
+    async fn stops_at_commitment_which_is_not_ready() {
+        let (last_included, res, receiver) =
+            run(vec![Some(1), None, Some(3)], 3, false, None).await;
+        res.unwrap();
+        assert_eq!(last_included, Some(1));
+        assert_eq!(receiver.accepted, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn reports_first_failure_with_progress() {
+        let nonces = (1..=5).map(Some).collect();
+        let (last_included, res, receiver) = run(nonces, 1, false, Some(2)).await;
+        assert!(res.unwrap_err().to_string().contains("Commitment 2 failed"));
+        assert_eq!(last_included, Some(1));
+        assert_eq!(receiver.accepted, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn reports_failure_in_pool_with_progress() {
+        let nonces = (1..=5).map(Some).collect();
+        let (last_included, res, receiver) = run(nonces, 3, false, Some(2)).await;
+        assert!(res.unwrap_err().to_string().contains("Commitment 2 failed"));
+        assert_eq!(last_included, Some(1));
+        assert_eq!(receiver.accepted, vec![1]);
+    }
+
+    #[tokio::test]
+    async fn reports_prepare_failure_with_progress() {
+        let prepared = futures::stream::iter(vec![
+            Ok(Some((1, ()))),
+            Ok(Some((2, ()))),
+            Err(anyhow!("Commitment 3 not found")),
+            Ok(Some((4, ()))),
+        ]);
+        let (last_included, res) = submit_in_order(prepared, 3, |_, _| async {
+            let pending: PendingSubmission = Box::pin(async { Ok(SubmittedExtrinsic::default()) });
+            Ok(pending)
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(last_included, Some(2));
+    }
+}
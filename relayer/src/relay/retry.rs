@@ -0,0 +1,173 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Retries of relay iterations which failed because the node connection was lost.
+
+use std::time::Duration;
+
+use crate::ethereum::{EthWallet, UnsignedClientInner};
+use crate::prelude::*;
+use ethers::middleware::signer::SignerMiddlewareError;
+use ethers::providers::{ProviderError, RpcError};
+
+/// Upper bound for the delay between retries.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// Returns true if the error is caused by the lost connection to the node,
+/// so the request will succeed once the client reconnects.
+pub fn is_transport_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        if let Some(err) = cause.downcast_ref::<jsonrpsee::core::Error>() {
+            return is_jsonrpsee_transport_error(err);
+        }
+        if let Some(err) = cause.downcast_ref::<subxt::error::RpcError>() {
+            return match err {
+                subxt::error::RpcError::ClientError(err) => err
+                    .downcast_ref::<jsonrpsee::core::Error>()
+                    .map_or(false, is_jsonrpsee_transport_error),
+                subxt::error::RpcError::SubscriptionDropped => true,
+            };
+        }
+        if let Some(err) = cause.downcast_ref::<ProviderError>() {
+            return is_provider_transport_error(err);
+        }
+        if let Some(SignerMiddlewareError::MiddlewareError(err)) =
+            cause.downcast_ref::<SignerMiddlewareError<UnsignedClientInner, EthWallet>>()
+        {
+            return is_provider_transport_error(err);
+        }
+        false
+    })
+}
+
+fn is_jsonrpsee_transport_error(err: &jsonrpsee::core::Error) -> bool {
+    matches!(
+        err,
+        jsonrpsee::core::Error::Transport(_)
+            | jsonrpsee::core::Error::RestartNeeded(_)
+            | jsonrpsee::core::Error::RequestTimeout
+    )
+}
+
+/// Errors which are not responses of the node are caused by the transport.
+fn is_provider_transport_error(err: &ProviderError) -> bool {
+    match err {
+        ProviderError::JsonRpcClientError(err) => {
+            err.as_error_response().is_none() && err.as_serde_error().is_none()
+        }
+        ProviderError::HTTPError(_) => true,
+        _ => false,
+    }
+}
+
+/// Exponential backoff of relay iterations failed with transport errors.
+pub struct RetryBackoff {
+    min_delay: Duration,
+    delay: Duration,
+}
+
+impl RetryBackoff {
+    pub fn new(min_delay: Duration) -> Self {
+        Self {
+            min_delay,
+            delay: min_delay,
+        }
+    }
+
+    /// Logs transport error and waits before the next attempt, other errors
+    /// are returned and stop the relay.
+    pub async fn handle(&mut self, res: AnyResult<()>) -> AnyResult<()> {
+        match res {
+            Ok(()) => {
+                self.delay = self.min_delay;
+                Ok(())
+            }
+            Err(err) if is_transport_error(&err) => {
+                warn!(
+                    "Connection to the node is lost, retrying in {:?}: {:?}",
+                    self.delay, err
+                );
+                tokio::time::sleep(self.delay).await;
+                self.delay = (self.delay * 2).min(MAX_RETRY_DELAY);
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn restart_needed() -> anyhow::Error {
+        jsonrpsee::core::Error::RestartNeeded("node restarted".to_string()).into()
+    }
+
+    #[test]
+    fn detects_jsonrpsee_transport_errors() {
+        assert!(is_transport_error(&restart_needed()));
+        assert!(is_transport_error(
+            &restart_needed().context("fetch storage")
+        ));
+        let err = jsonrpsee::core::Error::Transport(anyhow!("connection reset"));
+        assert!(is_transport_error(&err.into()));
+        let err = jsonrpsee::core::Error::Custom("invalid params".to_string());
+        assert!(!is_transport_error(&err.into()));
+    }
+
+    #[test]
+    fn detects_subxt_transport_errors() {
+        let err = subxt::Error::Rpc(subxt::error::RpcError::ClientError(Box::new(
+            jsonrpsee::core::Error::RestartNeeded("node restarted".to_string()),
+        )));
+        assert!(is_transport_error(&err.into()));
+        let err = subxt::Error::Rpc(subxt::error::RpcError::SubscriptionDropped);
+        assert!(is_transport_error(&err.into()));
+        let err = subxt::Error::Other("storage not found".to_string());
+        assert!(!is_transport_error(&err.into()));
+    }
+
+    #[test]
+    fn other_errors_are_not_retried() {
+        assert!(!is_transport_error(&anyhow!("Invalid nonce")));
+    }
+
+    #[tokio::test]
+    async fn backoff_grows_and_resets() {
+        let mut backoff = RetryBackoff::new(Duration::from_millis(1));
+        backoff.handle(Err(restart_needed())).await.unwrap();
+        backoff.handle(Err(restart_needed())).await.unwrap();
+        assert_eq!(backoff.delay, Duration::from_millis(4));
+        backoff.handle(Ok(())).await.unwrap();
+        assert_eq!(backoff.delay, Duration::from_millis(1));
+        assert!(backoff.handle(Err(anyhow!("Invalid nonce"))).await.is_err());
+    }
+}
@@ -31,7 +31,10 @@
 use std::collections::BTreeMap;
 
 use crate::{
+    health::LoopHeartbeat,
+    metrics::RelayMetrics,
     prelude::*,
+    relay::traits::{InboundChannelReader, InboundCommitmentSubmitter},
     substrate::{MaxU32, UnboundedGenericCommitment},
     ton::{types::StackEntry, TonClient},
 };
@@ -45,16 +48,30 @@ use toner::{
     ton::MsgAddress,
 };
 
-#[derive(Default)]
-pub struct RelayBuilder {
-    sub: Option<SubUnsignedClient<MainnetConfig>>,
+pub struct RelayBuilder<S = SubUnsignedClient<MainnetConfig>> {
+    sub: Option<S>,
     ton: Option<TonClient>,
     signer: Option<ecdsa::Pair>,
     ton_network_id: Option<GenericNetworkId>,
     channel: Option<MsgAddress>,
 }
 
-impl RelayBuilder {
+impl<S> Default for RelayBuilder<S> {
+    fn default() -> Self {
+        Self {
+            sub: None,
+            ton: None,
+            signer: None,
+            ton_network_id: None,
+            channel: None,
+        }
+    }
+}
+
+impl<S> RelayBuilder<S>
+where
+    S: InboundChannelReader + InboundCommitmentSubmitter,
+{
     pub fn new() -> Self {
         Self::default()
     }
@@ -79,20 +96,16 @@ impl RelayBuilder {
         self
     }
 
-    pub fn with_sub_client(mut self, sub: SubUnsignedClient<MainnetConfig>) -> Self {
+    pub fn with_sub_client(mut self, sub: S) -> Self {
         self.sub = Some(sub);
         self
     }
 
-    pub async fn build(self) -> AnyResult<Relay> {
+    pub async fn build(self) -> AnyResult<Relay<S>> {
         let sub = self
             .sub
             .ok_or(anyhow!("Internal error: Substrate client is not provided"))?;
-        let sub_network_id = sub.constant_fetch_or_default(
-            &runtime::constants()
-                .bridge_inbound_channel()
-                .this_network_id(),
-        )?;
+        let sub_network_id = sub.network_id()?;
         let ton = self
             .ton
             .ok_or(anyhow!("Internal error: TON client is not provided"))?;
@@ -106,6 +119,8 @@ impl RelayBuilder {
             .channel
             .ok_or(anyhow!("Internal error: Channel address is not provided"))?;
         Ok(Relay {
+            metrics: RelayMetrics::new(ton_network_id, sub_network_id),
+            heartbeat: LoopHeartbeat::register(ton_network_id, sub_network_id),
             sub,
             ton,
             signer,
@@ -116,16 +131,21 @@ impl RelayBuilder {
     }
 }
 
-pub struct Relay {
+pub struct Relay<S = SubUnsignedClient<MainnetConfig>> {
     ton: TonClient,
     channel: MsgAddress,
-    sub: SubUnsignedClient<MainnetConfig>,
+    sub: S,
     sub_network_id: GenericNetworkId,
     ton_network_id: GenericNetworkId,
     signer: ecdsa::Pair,
+    metrics: RelayMetrics,
+    heartbeat: LoopHeartbeat,
 }
 
-impl Relay {
+impl<S> Relay<S>
+where
+    S: InboundChannelReader + InboundCommitmentSubmitter,
+{
     pub async fn ton_nonce(&self) -> AnyResult<u64> {
         let res = self
             .ton
@@ -193,28 +213,52 @@ impl Relay {
     }
 
     pub async fn sub_nonce(&self) -> AnyResult<u64> {
-        let sub_nonce = self
-            .sub
-            .storage_fetch_or_default(
-                &runtime::storage()
-                    .bridge_inbound_channel()
-                    .channel_nonces(&self.ton_network_id),
-                (),
-            )
-            .await?;
-        Ok(sub_nonce)
+        self.sub.inbound_nonce(self.ton_network_id).await
     }
 
     async fn send(&self, commitment: Commitment<MaxU32>) -> AnyResult<()> {
         let commitment = UnboundedGenericCommitment::TON(commitment);
-        self.sub
+        let res = self
+            .sub
             .submit_inbound_commitment(
                 self.signer.clone(),
                 self.ton_network_id,
                 self.sub_network_id,
                 commitment,
             )
-            .await?;
+            .await;
+        match res {
+            Ok(status) => {
+                self.metrics.inbound_commitment(status);
+                Ok(())
+            }
+            Err(err) => {
+                self.metrics.commitment_failed();
+                Err(err)
+            }
+        }
+    }
+
+    /// Sends to SORA all messages which are sent from TON channel but not yet
+    /// accepted by SORA.
+    async fn relay_messages(&self) -> AnyResult<()> {
+        let mut sub_nonce = self.sub_nonce().await?;
+        let ton_nonce = self.ton_nonce().await?;
+        info!("Nonces - TON: {}, SORA: {}", ton_nonce, sub_nonce);
+        self.metrics.set_nonces(sub_nonce, ton_nonce);
+        if ton_nonce > sub_nonce {
+            let mut found_messages = BTreeMap::new();
+            for message in self.messages().await? {
+                found_messages.insert(message.nonce(), message);
+            }
+            while sub_nonce < ton_nonce {
+                sub_nonce += 1;
+                let message = found_messages.remove(&sub_nonce).ok_or(anyhow!(
+                    "Internal error: Message with nonce {sub_nonce} not found"
+                ))?;
+                self.send(message).await?;
+            }
+        }
         Ok(())
     }
 
@@ -222,22 +266,190 @@ impl Relay {
         let mut interval = tokio::time::interval(std::time::Duration::from_secs(10));
         loop {
             interval.tick().await;
-            let mut sub_nonce = self.sub_nonce().await?;
-            let ton_nonce = self.ton_nonce().await?;
-            info!("Nonces - TON: {}, SORA: {}", ton_nonce, sub_nonce);
-            if ton_nonce > sub_nonce {
-                let mut found_messages = BTreeMap::new();
-                for message in self.messages().await? {
-                    found_messages.insert(message.nonce(), message);
-                }
-                while sub_nonce < ton_nonce {
-                    sub_nonce += 1;
-                    let message = found_messages.remove(&sub_nonce).ok_or(anyhow!(
-                        "Internal error: Message with nonce {sub_nonce} not found"
-                    ))?;
-                    self.send(message).await?;
-                }
+            self.heartbeat.beat();
+            self.relay_messages().await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::substrate::InboundCommitmentStatus;
+    use crate::ton::contracts::ton_app::SendTon;
+    use crate::ton::mock::{to_boc, MockTonApi};
+    use bridge_types::SubNetworkId;
+    use std::sync::{Arc, Mutex};
+    use toner::tlb::{ser::CellSerializeExt, Cell};
+
+    const CHANNEL: MsgAddress = MsgAddress {
+        workchain_id: 0,
+        address: [1; 32],
+    };
+    const APP: MsgAddress = MsgAddress {
+        workchain_id: 0,
+        address: [2; 32],
+    };
+
+    /// SORA inbound channel which accepts commitments in nonce order.
+    #[derive(Clone, Default)]
+    struct FakeSora {
+        nonce: Arc<Mutex<u64>>,
+        submitted: Arc<Mutex<Vec<bridge_types::ton::InboundCommitment<MaxU32>>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl InboundChannelReader for FakeSora {
+        fn network_id(&self) -> AnyResult<GenericNetworkId> {
+            Ok(GenericNetworkId::Sub(SubNetworkId::Mainnet))
+        }
+
+        async fn inbound_nonce(&self, _source: GenericNetworkId) -> AnyResult<u64> {
+            Ok(*self.nonce.lock().unwrap())
+        }
+
+        async fn peers(
+            &self,
+            _source: GenericNetworkId,
+        ) -> AnyResult<std::collections::BTreeSet<ecdsa::Public>> {
+            Ok(Default::default())
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl InboundCommitmentSubmitter for FakeSora {
+        async fn submit_inbound_commitment(
+            &self,
+            _signer: ecdsa::Pair,
+            _source: GenericNetworkId,
+            _target: GenericNetworkId,
+            commitment: UnboundedGenericCommitment,
+        ) -> AnyResult<InboundCommitmentStatus> {
+            let UnboundedGenericCommitment::TON(Commitment::Inbound(commitment)) = commitment
+            else {
+                return Err(anyhow!("Unexpected commitment"));
+            };
+            let mut nonce = self.nonce.lock().unwrap();
+            if commitment.nonce != *nonce + 1 {
+                return Err(anyhow!("Wrong nonce {}", commitment.nonce));
             }
+            *nonce += 1;
+            self.submitted.lock().unwrap().push(commitment);
+            Ok(InboundCommitmentStatus {
+                approval_sent: true,
+                commitment_submitted: true,
+            })
+        }
+    }
+
+    impl FakeSora {
+        fn submitted_nonces(&self) -> Vec<u64> {
+            self.submitted
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|commitment| commitment.nonce)
+                .collect()
+        }
+    }
+
+    fn payload(amount: u64) -> Cell {
+        SendTon {
+            receiver: [3; 32].into(),
+            amount: amount.into(),
+        }
+        .to_cell()
+        .unwrap()
+    }
+
+    async fn relay(api: &MockTonApi, sora: &FakeSora) -> Relay<FakeSora> {
+        RelayBuilder::new()
+            .with_sub_client(sora.clone())
+            .with_ton_client(api.client().unwrap())
+            .with_channel(CHANNEL)
+            .with_ton_network_id(TonNetworkId::Testnet)
+            .with_signer(ecdsa::Pair::from_seed(&[4; 32]))
+            .build()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn relays_messages_in_nonce_order() {
+        let api = MockTonApi::start().await.unwrap();
+        for nonce in 1..=3 {
+            api.push_outbound_message(CHANNEL, APP, nonce, payload(nonce * 100))
+                .unwrap();
+        }
+        let sora = FakeSora::default();
+        relay(&api, &sora).await.relay_messages().await.unwrap();
+
+        assert_eq!(sora.submitted_nonces(), vec![1, 2, 3]);
+        let submitted = sora.submitted.lock().unwrap();
+        let first = &submitted[0];
+        assert_eq!(
+            first.payload.to_vec(),
+            payload(100).data.as_raw_slice().to_vec()
+        );
+        assert_eq!(
+            first.source,
+            bridge_types::ton::TonAddress::new(0, APP.address.into())
+        );
+        assert_eq!(
+            first.channel,
+            bridge_types::ton::TonAddress::new(0, CHANNEL.address.into())
+        );
+    }
+
+    #[tokio::test]
+    async fn skips_messages_accepted_by_sora() {
+        let api = MockTonApi::start().await.unwrap();
+        for nonce in 1..=3 {
+            api.push_outbound_message(CHANNEL, APP, nonce, payload(nonce))
+                .unwrap();
         }
+        let sora = FakeSora::default();
+        *sora.nonce.lock().unwrap() = 2;
+        let relay = relay(&api, &sora).await;
+        relay.relay_messages().await.unwrap();
+        assert_eq!(sora.submitted_nonces(), vec![3]);
+
+        // Nothing to do when nonces are equal
+        relay.relay_messages().await.unwrap();
+        assert_eq!(sora.submitted_nonces(), vec![3]);
+    }
+
+    #[tokio::test]
+    async fn ignores_other_messages() {
+        let api = MockTonApi::start().await.unwrap();
+        api.push_outbound_message(CHANNEL, APP, 1, payload(1))
+            .unwrap();
+        // Internal message from the channel
+        api.push_message(CHANNEL, Some(APP), to_boc(&payload(2)).unwrap());
+        // External message which is not an outbound message
+        api.push_message(CHANNEL, None, to_boc(&payload(3)).unwrap());
+        let sora = FakeSora::default();
+        relay(&api, &sora).await.relay_messages().await.unwrap();
+        assert_eq!(sora.submitted_nonces(), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn fails_on_missing_message() {
+        let api = MockTonApi::start().await.unwrap();
+        api.push_outbound_message(CHANNEL, APP, 1, payload(1))
+            .unwrap();
+        api.set_get_method(CHANNEL, "outboundNonce", 0, vec![2u64.into()]);
+        let sora = FakeSora::default();
+        let err = relay(&api, &sora).await.relay_messages().await.unwrap_err();
+        assert!(err.to_string().contains("nonce 2 not found"), "{err}");
+        assert_eq!(sora.submitted_nonces(), vec![1]);
+    }
+
+    #[tokio::test]
+    async fn fails_on_wrong_channel_contract() {
+        let api = MockTonApi::start().await.unwrap();
+        let sora = FakeSora::default();
+        assert!(relay(&api, &sora).await.ton_nonce().await.is_err());
+        assert!(sora.submitted_nonces().is_empty());
     }
 }
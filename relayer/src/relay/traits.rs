@@ -0,0 +1,667 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Operations relays need from chain clients. Relays use them instead of
+//! concrete clients, so they can be driven by in-memory fakes in tests.
+
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use crate::ethereum::logs::PagedClient;
+use crate::prelude::*;
+use crate::relay::messages_subscription::{
+    commitment_leaf_block, load_commitment_with_proof, load_digest,
+};
+use crate::substrate::{
+    BlockNumber, BlockNumberOrHash, InboundCommitmentStatus, MaxU32, SubmittedExtrinsic,
+    UnboundedGenericCommitment,
+};
+use bridge_types::evm::OutboundCommitment;
+use bridge_types::types::AuxiliaryDigest;
+use bridge_types::{EVMChainId, GenericNetworkId, H160, H256, U256};
+use ethereum_gen::channel_handler::{BatchDispatchedFilter, MessageDispatchedFilter};
+use ethers::contract::LogMeta;
+use ethers::types::TransactionReceipt;
+use futures::future::BoxFuture;
+use sp_core::ecdsa;
+
+/// Commitment with the number of the block it was committed in.
+#[derive(Clone, Debug)]
+pub struct CommitmentWithBlock {
+    pub block_number: u64,
+    pub commitment: UnboundedGenericCommitment,
+}
+
+/// Proof of the commitment approved by bridge peers.
+#[derive(Clone, Debug)]
+pub struct MultisigProof {
+    pub digest: AuxiliaryDigest,
+    pub approvals: Vec<ecdsa::Signature>,
+}
+
+/// Reads state of the outbound channel on the sending network.
+#[async_trait::async_trait]
+pub trait OutboundChannelReader: Send + Sync {
+    /// Id of the network which hosts the outbound channel.
+    fn network_id(&self) -> AnyResult<GenericNetworkId>;
+
+    fn average_block_time(&self) -> Duration;
+
+    /// Nonce of the latest finalized commitment sent to `target`.
+    async fn outbound_nonce(&self, target: GenericNetworkId) -> AnyResult<u64>;
+
+    /// Finalized commitment sent to `target` with the given nonce.
+    async fn commitment(
+        &self,
+        target: GenericNetworkId,
+        nonce: u64,
+    ) -> AnyResult<CommitmentWithBlock>;
+
+    /// Auxiliary digest of the block, which contains the commitment.
+    async fn digest(
+        &self,
+        target: GenericNetworkId,
+        block_number: u64,
+        commitment_hash: H256,
+    ) -> AnyResult<AuxiliaryDigest>;
+}
+
+/// Reads commitments together with proofs checked by the light client on
+/// the receiving network.
+#[async_trait::async_trait]
+pub trait MessageProofReader: Send + Sync {
+    type Proof: Clone + Send + 'static;
+
+    async fn commitment_with_proof(
+        &self,
+        target: GenericNetworkId,
+        nonce: u64,
+        latest_beefy_block: u32,
+    ) -> AnyResult<(UnboundedGenericCommitment, Self::Proof)>;
+
+    /// First block which BEEFY commitment can prove the commitment with `nonce`,
+    /// `None` if the MMR leaf with the commitment is not produced yet.
+    async fn commitment_beefy_block(
+        &self,
+        target: GenericNetworkId,
+        nonce: u64,
+    ) -> AnyResult<Option<u64>>;
+}
+
+/// Approvals of outbound commitments collected on the sending network.
+#[async_trait::async_trait]
+pub trait ApprovalStore: Send + Sync {
+    /// Peers which are allowed to approve commitments sent to `target`.
+    async fn approval_peers(&self, target: GenericNetworkId) -> AnyResult<BTreeSet<ecdsa::Public>>;
+
+    /// All stored approvals of the message, including ones from unknown peers.
+    async fn approvals(
+        &self,
+        target: GenericNetworkId,
+        message: H256,
+    ) -> AnyResult<Vec<ecdsa::Signature>>;
+
+    async fn submit_approval(
+        &self,
+        target: GenericNetworkId,
+        message: H256,
+        signature: ecdsa::Signature,
+    ) -> AnyResult<SubmittedExtrinsic>;
+}
+
+/// Reads state of the inbound channel on the receiving network.
+#[async_trait::async_trait]
+pub trait InboundChannelReader: Send + Sync {
+    /// Id of the network which hosts the inbound channel.
+    fn network_id(&self) -> AnyResult<GenericNetworkId>;
+
+    /// Nonce of the latest commitment from `source` accepted by the channel.
+    async fn inbound_nonce(&self, source: GenericNetworkId) -> AnyResult<u64>;
+
+    /// Peers whose approvals are accepted for commitments from `source`.
+    async fn peers(&self, source: GenericNetworkId) -> AnyResult<BTreeSet<ecdsa::Public>>;
+}
+
+/// Commitment which is submitted but not yet included in a block.
+pub type PendingSubmission = BoxFuture<'static, AnyResult<SubmittedExtrinsic>>;
+
+/// Submits commitments with proofs of type `P` to the inbound channel.
+#[async_trait::async_trait]
+pub trait ExtrinsicSubmitter<P: Send + 'static>: Send + Sync {
+    async fn submit_messages_commitment(
+        &self,
+        source: GenericNetworkId,
+        commitment: UnboundedGenericCommitment,
+        proof: P,
+    ) -> AnyResult<SubmittedExtrinsic>;
+
+    /// Submits the commitment and returns without waiting for its inclusion,
+    /// so the next commitment can be submitted right away.
+    async fn broadcast_messages_commitment(
+        &self,
+        source: GenericNetworkId,
+        commitment: UnboundedGenericCommitment,
+        proof: P,
+    ) -> AnyResult<PendingSubmission> {
+        let res = self
+            .submit_messages_commitment(source, commitment, proof)
+            .await;
+        Ok(Box::pin(futures::future::ready(res)))
+    }
+}
+
+/// Approves and submits commitments to the inbound channel.
+#[async_trait::async_trait]
+pub trait InboundCommitmentSubmitter: Send + Sync {
+    async fn submit_inbound_commitment(
+        &self,
+        signer: ecdsa::Pair,
+        source: GenericNetworkId,
+        target: GenericNetworkId,
+        commitment: UnboundedGenericCommitment,
+    ) -> AnyResult<InboundCommitmentStatus>;
+}
+
+/// Reads state of the inbound channel for EVM networks on SORA.
+#[async_trait::async_trait]
+pub trait EvmInboundChannelReader: Send + Sync {
+    /// Address of the channel contract registered for the network.
+    async fn evm_channel_address(&self, chain_id: EVMChainId) -> AnyResult<Option<H160>>;
+
+    /// Nonce of the latest batch status reported from `source`.
+    async fn reported_nonce(&self, source: GenericNetworkId) -> AnyResult<u64>;
+
+    /// EVM block number of the latest base fee update.
+    async fn base_fee_block(&self, chain_id: EVMChainId) -> AnyResult<Option<u64>>;
+
+    /// Base fee of the network used to charge fees for outbound messages.
+    async fn base_fee(&self, chain_id: EVMChainId) -> AnyResult<Option<U256>>;
+}
+
+/// Submits batches of SORA outbound commitments to the channel contract.
+#[async_trait::async_trait]
+pub trait EvmBatchSubmitter: Send + Sync {
+    async fn chain_id(&self) -> AnyResult<EVMChainId>;
+
+    /// Nonce of the latest batch accepted by the channel.
+    async fn batch_nonce(&self) -> AnyResult<u64>;
+
+    /// False if there is no account to send batches from.
+    fn can_submit(&self) -> bool;
+
+    /// Price of the gas used to estimate the cost of batch submission.
+    async fn gas_price(&self) -> AnyResult<U256>;
+
+    /// Checks that the channel accepts the batch and returns the gas it needs.
+    async fn estimate_batch(
+        &self,
+        commitment: &OutboundCommitment<MaxU32, MaxU32>,
+        approvals: Vec<ecdsa::Signature>,
+    ) -> AnyResult<U256>;
+
+    /// Sends the batch and waits for its inclusion, `None` if it is not sent
+    /// in dry run mode.
+    async fn submit_batch(
+        &self,
+        commitment: &OutboundCommitment<MaxU32, MaxU32>,
+        approvals: Vec<ecdsa::Signature>,
+        estimated_gas: U256,
+    ) -> AnyResult<Option<TransactionReceipt>>;
+}
+
+/// Reads the channel contract and blocks of EVM network.
+#[async_trait::async_trait]
+pub trait EvmChannelReader: Send + Sync {
+    async fn chain_id(&self) -> AnyResult<EVMChainId>;
+
+    async fn finalized_block_number(&self) -> AnyResult<u64>;
+
+    async fn base_fee(&self, block_number: u64) -> AnyResult<U256>;
+
+    /// Nonce of the latest message sent by the channel.
+    async fn message_nonce(&self, channel: H160) -> AnyResult<u64>;
+
+    /// Messages sent by the channel from `from_block` to `to_block` inclusive.
+    async fn message_dispatched(
+        &mut self,
+        channel: H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> AnyResult<Vec<(MessageDispatchedFilter, LogMeta)>>;
+
+    /// Batches dispatched by the channel from `from_block` to `to_block` inclusive.
+    async fn batch_dispatched(
+        &mut self,
+        channel: H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> AnyResult<Vec<(BatchDispatchedFilter, LogMeta)>>;
+
+    /// Blocks where the channel was reset from `from_block` to `to_block` inclusive.
+    async fn reset_blocks(
+        &mut self,
+        channel: H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> AnyResult<Vec<u64>>;
+}
+
+/// Returns nonces of the commitments which are sent but not yet accepted.
+pub fn pending_nonces(inbound_nonce: u64, outbound_nonce: u64) -> std::ops::RangeInclusive<u64> {
+    if inbound_nonce > outbound_nonce {
+        error!(
+            "Inbound channel nonce is higher than outbound channel nonce: {} > {}",
+            inbound_nonce, outbound_nonce
+        );
+    }
+    (inbound_nonce + 1)..=outbound_nonce
+}
+
+/// Returns approvals made by the given peers. Fails on malformed signatures.
+pub fn peer_approvals(
+    approvals: Vec<ecdsa::Signature>,
+    peers: &BTreeSet<ecdsa::Public>,
+    message: H256,
+) -> AnyResult<Vec<ecdsa::Signature>> {
+    let mut acceptable_approvals = vec![];
+    for approval in approvals {
+        let public = approval
+            .recover_prehashed(&message.0)
+            .ok_or(anyhow!("Wrong signature in data signer pallet"))?;
+        if peers.contains(&public) {
+            acceptable_approvals.push(approval);
+        }
+    }
+    Ok(acceptable_approvals)
+}
+
+pub fn has_enough_approvals(
+    approvals: &[ecdsa::Signature],
+    peers: &BTreeSet<ecdsa::Public>,
+) -> bool {
+    (approvals.len() as u32) >= bridge_types::utils::threshold(peers.len() as u32)
+}
+
+#[async_trait::async_trait]
+impl<S: SenderConfig> OutboundChannelReader for SubUnsignedClient<S> {
+    fn network_id(&self) -> AnyResult<GenericNetworkId> {
+        self.constant_fetch_or_default(&S::network_id())
+    }
+
+    fn average_block_time(&self) -> Duration {
+        S::average_block_time()
+    }
+
+    async fn outbound_nonce(&self, target: GenericNetworkId) -> AnyResult<u64> {
+        self.storage_fetch_or_default(
+            &S::bridge_outbound_nonce(target),
+            BlockNumberOrHash::Finalized,
+        )
+        .await
+    }
+
+    async fn commitment(
+        &self,
+        target: GenericNetworkId,
+        nonce: u64,
+    ) -> AnyResult<CommitmentWithBlock> {
+        let offchain_data = self
+            .commitment_with_nonce(target, nonce, BlockNumberOrHash::Finalized)
+            .await?;
+        Ok(CommitmentWithBlock {
+            block_number: offchain_data.block_number.into(),
+            commitment: offchain_data.commitment,
+        })
+    }
+
+    async fn digest(
+        &self,
+        target: GenericNetworkId,
+        block_number: u64,
+        commitment_hash: H256,
+    ) -> AnyResult<AuxiliaryDigest> {
+        let block_number: BlockNumber<S> = block_number
+            .try_into()
+            .map_err(|_| anyhow!("Block number {} is too big", block_number))?;
+        load_digest(self, target, block_number, commitment_hash).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: SenderConfig> MessageProofReader for SubUnsignedClient<S> {
+    type Proof = beefy_light_client::SubstrateBridgeMessageProof;
+
+    async fn commitment_with_proof(
+        &self,
+        target: GenericNetworkId,
+        nonce: u64,
+        latest_beefy_block: u32,
+    ) -> AnyResult<(UnboundedGenericCommitment, Self::Proof)> {
+        let commitment =
+            load_commitment_with_proof(self, target, nonce, latest_beefy_block).await?;
+        let proof = beefy_light_client::SubstrateBridgeMessageProof {
+            proof: commitment.proof,
+            leaf: commitment.leaf,
+            digest: commitment.digest,
+        };
+        Ok((commitment.offchain_data.commitment, proof))
+    }
+
+    async fn commitment_beefy_block(
+        &self,
+        target: GenericNetworkId,
+        nonce: u64,
+    ) -> AnyResult<Option<u64>> {
+        let block_number = commitment_leaf_block(self, target, nonce).await?;
+        Ok(block_number.map(Into::into))
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: SenderConfig> ApprovalStore for SubUnsignedClient<S> {
+    async fn approval_peers(&self, target: GenericNetworkId) -> AnyResult<BTreeSet<ecdsa::Public>> {
+        let peers = self
+            .storage_fetch(&S::peers(target), ())
+            .await?
+            .unwrap_or_default();
+        Ok(peers)
+    }
+
+    async fn approvals(
+        &self,
+        target: GenericNetworkId,
+        message: H256,
+    ) -> AnyResult<Vec<ecdsa::Signature>> {
+        let approvals = self
+            .storage_fetch_or_default(&S::approvals(target, message), ())
+            .await?;
+        Ok(approvals.into_values().collect())
+    }
+
+    async fn submit_approval(
+        &self,
+        target: GenericNetworkId,
+        message: H256,
+        signature: ecdsa::Signature,
+    ) -> AnyResult<SubmittedExtrinsic> {
+        let call = S::submit_signature(target, message, signature);
+        self.submit_unsigned_extrinsic(&call).await
+    }
+}
+
+#[async_trait::async_trait]
+impl<R: ReceiverConfig> InboundChannelReader for SubUnsignedClient<R> {
+    fn network_id(&self) -> AnyResult<GenericNetworkId> {
+        self.constant_fetch_or_default(&R::network_id())
+    }
+
+    async fn inbound_nonce(&self, source: GenericNetworkId) -> AnyResult<u64> {
+        self.storage_fetch_or_default(&R::bridge_inbound_nonce(source)?, ())
+            .await
+    }
+
+    async fn peers(&self, source: GenericNetworkId) -> AnyResult<BTreeSet<ecdsa::Public>> {
+        let peers = self
+            .storage_fetch(&R::peers(source), ())
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        Ok(peers)
+    }
+}
+
+#[async_trait::async_trait]
+impl<R: ReceiverConfig> ExtrinsicSubmitter<MultisigProof> for SubUnsignedClient<R> {
+    async fn submit_messages_commitment(
+        &self,
+        source: GenericNetworkId,
+        commitment: UnboundedGenericCommitment,
+        proof: MultisigProof,
+    ) -> AnyResult<SubmittedExtrinsic> {
+        let GenericNetworkId::Sub(source) = source else {
+            return Err(anyhow!("Substrate source network is expected"));
+        };
+        let call = R::submit_messages_commitment(
+            source,
+            commitment,
+            R::multisig_proof(proof.digest, proof.approvals),
+        );
+        self.submit_unsigned_extrinsic(&call).await
+    }
+
+    async fn broadcast_messages_commitment(
+        &self,
+        source: GenericNetworkId,
+        commitment: UnboundedGenericCommitment,
+        proof: MultisigProof,
+    ) -> AnyResult<PendingSubmission> {
+        let GenericNetworkId::Sub(source) = source else {
+            return Err(anyhow!("Substrate source network is expected"));
+        };
+        let call = R::submit_messages_commitment(
+            source,
+            commitment,
+            R::multisig_proof(proof.digest, proof.approvals),
+        );
+        let pending = self.broadcast_unsigned_extrinsic(&call).await?;
+        Ok(Box::pin(pending.wait()))
+    }
+}
+
+#[async_trait::async_trait]
+impl<R: ReceiverConfig> ExtrinsicSubmitter<beefy_light_client::SubstrateBridgeMessageProof>
+    for SubUnsignedClient<R>
+{
+    async fn submit_messages_commitment(
+        &self,
+        source: GenericNetworkId,
+        commitment: UnboundedGenericCommitment,
+        proof: beefy_light_client::SubstrateBridgeMessageProof,
+    ) -> AnyResult<SubmittedExtrinsic> {
+        let GenericNetworkId::Sub(source) = source else {
+            return Err(anyhow!("Substrate source network is expected"));
+        };
+        let call = R::submit_messages_commitment(source, commitment, R::beefy_proof(proof));
+        self.submit_unsigned_extrinsic(&call).await
+    }
+
+    async fn broadcast_messages_commitment(
+        &self,
+        source: GenericNetworkId,
+        commitment: UnboundedGenericCommitment,
+        proof: beefy_light_client::SubstrateBridgeMessageProof,
+    ) -> AnyResult<PendingSubmission> {
+        let GenericNetworkId::Sub(source) = source else {
+            return Err(anyhow!("Substrate source network is expected"));
+        };
+        let call = R::submit_messages_commitment(source, commitment, R::beefy_proof(proof));
+        let pending = self.broadcast_unsigned_extrinsic(&call).await?;
+        Ok(Box::pin(pending.wait()))
+    }
+}
+
+#[async_trait::async_trait]
+impl InboundCommitmentSubmitter for SubUnsignedClient<MainnetConfig> {
+    async fn submit_inbound_commitment(
+        &self,
+        signer: ecdsa::Pair,
+        source: GenericNetworkId,
+        target: GenericNetworkId,
+        commitment: UnboundedGenericCommitment,
+    ) -> AnyResult<InboundCommitmentStatus> {
+        SubUnsignedClient::<MainnetConfig>::submit_inbound_commitment(
+            self, signer, source, target, commitment,
+        )
+        .await
+    }
+}
+
+#[async_trait::async_trait]
+impl EvmInboundChannelReader for SubUnsignedClient<MainnetConfig> {
+    async fn evm_channel_address(&self, chain_id: EVMChainId) -> AnyResult<Option<H160>> {
+        self.storage_fetch(
+            &runtime::storage()
+                .bridge_inbound_channel()
+                .evm_channel_addresses(&chain_id),
+            (),
+        )
+        .await
+    }
+
+    async fn reported_nonce(&self, source: GenericNetworkId) -> AnyResult<u64> {
+        self.storage_fetch_or_default(
+            &runtime::storage()
+                .bridge_inbound_channel()
+                .reported_channel_nonces(&source),
+            (),
+        )
+        .await
+    }
+
+    async fn base_fee_block(&self, chain_id: EVMChainId) -> AnyResult<Option<u64>> {
+        let base_fee = self
+            .storage_fetch(
+                &runtime::storage().evm_fungible_app().base_fees(chain_id),
+                (),
+            )
+            .await?;
+        Ok(base_fee.map(|base_fee| base_fee.evm_block_number))
+    }
+
+    async fn base_fee(&self, chain_id: EVMChainId) -> AnyResult<Option<U256>> {
+        let base_fee = self
+            .storage_fetch(
+                &runtime::storage().evm_fungible_app().base_fees(chain_id),
+                BlockNumberOrHash::Finalized,
+            )
+            .await?;
+        Ok(base_fee.map(|base_fee| base_fee.base_fee))
+    }
+}
+
+#[async_trait::async_trait]
+impl EvmChannelReader for PagedClient {
+    async fn chain_id(&self) -> AnyResult<EVMChainId> {
+        self.eth().chainid().await
+    }
+
+    async fn finalized_block_number(&self) -> AnyResult<u64> {
+        self.eth().get_finalized_block_number().await
+    }
+
+    async fn base_fee(&self, block_number: u64) -> AnyResult<U256> {
+        let block = self
+            .eth()
+            .get_block(block_number)
+            .await?
+            .ok_or(anyhow!("Block {} not found", block_number))?;
+        Ok(block.base_fee_per_gas.unwrap_or_default())
+    }
+
+    async fn message_nonce(&self, channel: H160) -> AnyResult<u64> {
+        let channel = ethereum_gen::ChannelHandler::new(channel, self.eth().inner());
+        let nonce = channel.message_nonce().call().await?;
+        Ok(nonce as u64)
+    }
+
+    async fn message_dispatched(
+        &mut self,
+        channel: H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> AnyResult<Vec<(MessageDispatchedFilter, LogMeta)>> {
+        let channel = ethereum_gen::ChannelHandler::new(channel, self.eth().inner());
+        self.logs()
+            .query_with_meta(channel.message_dispatched_filter(), from_block, to_block)
+            .await
+    }
+
+    async fn batch_dispatched(
+        &mut self,
+        channel: H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> AnyResult<Vec<(BatchDispatchedFilter, LogMeta)>> {
+        let channel = ethereum_gen::ChannelHandler::new(channel, self.eth().inner());
+        self.logs()
+            .query_with_meta(channel.batch_dispatched_filter(), from_block, to_block)
+            .await
+    }
+
+    async fn reset_blocks(
+        &mut self,
+        channel: H160,
+        from_block: u64,
+        to_block: u64,
+    ) -> AnyResult<Vec<u64>> {
+        let channel = ethereum_gen::ChannelHandler::new(channel, self.eth().inner());
+        let events: Vec<(ethereum_gen::channel_handler::ResetedFilter, LogMeta)> = self
+            .logs()
+            .query_with_meta(channel.reseted_filter(), from_block, to_block)
+            .await?;
+        Ok(events
+            .into_iter()
+            .map(|(_, meta)| meta.block_number.as_u64())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending_nonces_follow_inbound_nonce() {
+        assert_eq!(pending_nonces(2, 5).collect::<Vec<_>>(), vec![3, 4, 5]);
+        assert!(pending_nonces(5, 5).is_empty());
+        assert!(pending_nonces(6, 5).is_empty());
+    }
+
+    #[test]
+    fn counts_only_peer_approvals() {
+        let message = H256::repeat_byte(1);
+        let pairs = (1..=5)
+            .map(|seed| ecdsa::Pair::from_seed(&[seed; 32]))
+            .collect::<Vec<_>>();
+        let peers = pairs[..4].iter().map(|pair| pair.public()).collect();
+        let approvals = vec![
+            pairs[0].sign_prehashed(&message.0),
+            pairs[4].sign_prehashed(&message.0),
+            pairs[1].sign_prehashed(&message.0),
+        ];
+        let approvals = peer_approvals(approvals, &peers, message).unwrap();
+        assert_eq!(approvals.len(), 2);
+        assert!(!has_enough_approvals(&approvals, &peers));
+
+        let approvals = [approvals, vec![pairs[2].sign_prehashed(&message.0)]].concat();
+        assert!(has_enough_approvals(&approvals, &peers));
+    }
+}
@@ -1,13 +1,15 @@
+use std::collections::BTreeMap;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use crate::{prelude::*, relay::justification::BeefyJustification};
-use futures::Stream;
-use futures::StreamExt;
+use beefy_gadget_rpc::BeefyApiClient;
+use futures::{Future, Stream, StreamExt};
+use jsonrpsee::core::client::Subscription;
 use sp_beefy::VersionedFinalityProof;
-use sp_runtime::traits::UniqueSaturatedInto;
+use sp_runtime::traits::{AtLeast32BitUnsigned, UniqueSaturatedInto};
 
-use super::BlockNumber;
+use super::{BlockNumber, EncodedBeefyCommitment};
 
 const BEEFY_MIN_DELTA: u32 = 8;
 
@@ -49,26 +51,153 @@ pub async fn find_mandatory_commitment<T>(
 where
     T: SenderConfig,
 {
-    let finalized_head = sub.finalized_head().await?;
-    let high = sub.block_number(finalized_head).await?;
-    let low: BlockNumber<T> = 1u32.into();
-    let storage = T::current_validator_set();
-    let block = super::binary_search_first_occurrence(low, high, vset_id, |n| {
-        let storage = &storage;
-        let sub = &sub;
-        async move {
-            let vset = sub.storage_fetch_or_default(storage, n).await?;
-            Ok(Some(vset.id))
-        }
+    ValidatorSetBoundaries::default().find(sub, vset_id).await
+}
+
+async fn vset_id_at<T>(sub: &SubUnsignedClient<T>, block: BlockNumber<T>) -> AnyResult<u64>
+where
+    T: SenderConfig,
+{
+    Ok(sub
+        .storage_fetch_or_default(&T::current_validator_set(), block)
+        .await?
+        .id)
+}
+
+async fn first_block_of_vset<N, F, Fut>(
+    low: N,
+    high: N,
+    vset_id: u64,
+    vset_id_at: F,
+) -> AnyResult<Option<N>>
+where
+    N: AtLeast32BitUnsigned,
+    F: Fn(N) -> Fut,
+    Fut: Future<Output = AnyResult<u64>>,
+{
+    super::binary_search_first_occurrence(low, high, vset_id, |n| {
+        let vset_id = vset_id_at(n);
+        async move { Ok(Some(vset_id.await?)) }
     })
-    .await?;
-    Ok(block)
+    .await
+}
+
+/// First blocks of validator sets on the sender chain, where mandatory
+/// commitments are made. Found boundaries narrow down later searches.
+#[derive(Clone, Default)]
+pub struct ValidatorSetBoundaries(Arc<Mutex<BTreeMap<u64, u64>>>);
+
+impl ValidatorSetBoundaries {
+    fn get(&self, vset_id: u64) -> Option<u64> {
+        self.0.lock().expect("poisoned").get(&vset_id).copied()
+    }
+
+    fn insert(&self, vset_id: u64, block: u64) {
+        self.0.lock().expect("poisoned").insert(vset_id, block);
+    }
+
+    /// Block of the closest known validator set before `vset_id`.
+    fn lower_bound(&self, vset_id: u64) -> Option<u64> {
+        self.0
+            .lock()
+            .expect("poisoned")
+            .range(..vset_id)
+            .next_back()
+            .map(|(_, block)| *block)
+    }
+
+    /// Finds the first block of the validator set in finalized blocks.
+    pub async fn find<T>(
+        &self,
+        sub: &SubUnsignedClient<T>,
+        vset_id: u64,
+    ) -> AnyResult<Option<BlockNumber<T>>>
+    where
+        T: SenderConfig,
+    {
+        if let Some(block) = self.get(vset_id) {
+            return Ok(Some(block.unique_saturated_into()));
+        }
+        let finalized_head = sub.finalized_head().await?;
+        let high = sub.block_number(finalized_head).await?;
+        self.find_up_to(vset_id, high, |n| vset_id_at(sub, n)).await
+    }
+
+    /// Finds the first block of the validator set up to block `high`.
+    async fn find_up_to<N, F, Fut>(
+        &self,
+        vset_id: u64,
+        high: N,
+        vset_id_at: F,
+    ) -> AnyResult<Option<N>>
+    where
+        N: AtLeast32BitUnsigned + Copy + Into<u64>,
+        F: Fn(N) -> Fut,
+        Fut: Future<Output = AnyResult<u64>>,
+    {
+        if let Some(block) = self.get(vset_id) {
+            return Ok(Some(block.unique_saturated_into()));
+        }
+        let low = self
+            .lower_bound(vset_id)
+            .unwrap_or(1)
+            .unique_saturated_into();
+        let block = first_block_of_vset(low, high, vset_id, vset_id_at).await?;
+        if let Some(block) = block {
+            self.insert(vset_id, block.into());
+        }
+        Ok(block)
+    }
+
+    /// Finds first blocks of all validator sets which changed after `from` up
+    /// to `to` in a single pass, each search starts from the previous boundary.
+    /// If validator set id increased by more than one at a block, the block is
+    /// returned once with the latest id.
+    pub async fn scan<T>(
+        &self,
+        sub: &SubUnsignedClient<T>,
+        from: BlockNumber<T>,
+        to: BlockNumber<T>,
+    ) -> AnyResult<Vec<(u64, BlockNumber<T>)>>
+    where
+        T: SenderConfig,
+    {
+        self.scan_with(from, to, |n| vset_id_at(sub, n)).await
+    }
+
+    async fn scan_with<N, F, Fut>(&self, from: N, to: N, vset_id_at: F) -> AnyResult<Vec<(u64, N)>>
+    where
+        N: AtLeast32BitUnsigned + Copy + Into<u64>,
+        F: Fn(N) -> Fut,
+        Fut: Future<Output = AnyResult<u64>>,
+    {
+        let from_vset = vset_id_at(from).await?;
+        let to_vset = vset_id_at(to).await?;
+        let mut low = from;
+        let mut boundaries: Vec<(u64, N)> = vec![];
+        for vset_id in (from_vset + 1)..=to_vset {
+            let block = match self.get(vset_id) {
+                Some(block) => block.unique_saturated_into(),
+                None => first_block_of_vset(low, to, vset_id, &vset_id_at)
+                    .await?
+                    .ok_or(anyhow!("Validator set {} not found", vset_id))?,
+            };
+            self.insert(vset_id, block.into());
+            low = block;
+            match boundaries.last_mut() {
+                Some(last) if last.1 == block => last.0 = vset_id,
+                _ => boundaries.push((vset_id, block)),
+            }
+        }
+        Ok(boundaries)
+    }
 }
 
 pub fn mandatory_commitment_stream<T>(
     sub: SubUnsignedClient<T>,
     latest_commitment: Arc<AtomicU64>,
     current_vset_id: u64,
+    boundaries: ValidatorSetBoundaries,
 ) -> impl Stream<Item = AnyResult<BeefyJustification<T>>> + Unpin
 where
     T: SenderConfig + 'static,
@@ -77,6 +206,7 @@ where
         futures::stream::iter((current_vset_id + 1)..).then(move |i| {
             let sub = sub.clone();
             let latest_commitment = latest_commitment.clone();
+            let boundaries = boundaries.clone();
             async move {
                 loop {
                     let vset = sub
@@ -86,7 +216,7 @@ where
                         tokio::time::sleep(T::average_block_time()).await;
                         continue;
                     }
-                    let Some(block) = find_mandatory_commitment(&sub, i).await? else {
+                    let Some(block) = boundaries.find(&sub, i).await? else {
                         tokio::time::sleep(T::average_block_time()).await;
                         continue;
                     };
@@ -114,51 +244,210 @@ where
         .then(move |()| {
             let sub = sub.clone();
             let latest_commitment = latest_commitment.clone();
-            async move {
-                let latest_sent = latest_commitment.load(Ordering::Relaxed);
-                let vset_storage = T::current_validator_set();
-                let latest_sent_vset = sub
-                    .storage_fetch_or_default(&vset_storage, latest_sent)
-                    .await?
-                    .id;
-                let best_vset = sub.storage_fetch_or_default(&vset_storage, ()).await?.id;
-                if latest_sent_vset < best_vset {
-                    debug!("Waiting for mandatory commitment");
-                    tokio::time::sleep(T::average_block_time()).await;
-                    return Ok(None);
-                }
-                let best_block: u64 = sub.block_number(()).await?.into();
-                let possible_beefy_block =
-                    best_block - ((best_block - latest_sent) % BEEFY_MIN_DELTA as u64);
-                for i in 0..3 {
-                    let block_to_check =
-                        possible_beefy_block.saturating_sub(i * BEEFY_MIN_DELTA as u64);
-                    if block_to_check <= latest_sent {
-                        break;
-                    }
-                    let Some(justification) = get_commitment_from_block(
-                        &sub,
-                        block_to_check.unique_saturated_into(),
-                        false,
-                    )
-                    .await?
-                    else {
-                        continue;
-                    };
-                    latest_commitment.store(block_to_check, Ordering::Relaxed);
-                    return Ok(Some(justification));
-                }
-                tokio::time::sleep(T::average_block_time()).await;
-                Ok(None)
-            }
+            async move { poll_beefy_commitment(&sub, &latest_commitment).await }
         })
         .filter_map(|x| futures::future::ready(x.transpose()));
     Box::pin(stream)
 }
 
+async fn poll_beefy_commitment<T>(
+    sub: &SubUnsignedClient<T>,
+    latest_commitment: &AtomicU64,
+) -> AnyResult<Option<BeefyJustification<T>>>
+where
+    T: SenderConfig,
+{
+    let latest_sent = latest_commitment.load(Ordering::Relaxed);
+    let vset_storage = T::current_validator_set();
+    let latest_sent_vset = sub
+        .storage_fetch_or_default(&vset_storage, latest_sent)
+        .await?
+        .id;
+    let best_vset = sub.storage_fetch_or_default(&vset_storage, ()).await?.id;
+    if latest_sent_vset < best_vset {
+        debug!("Waiting for mandatory commitment");
+        tokio::time::sleep(T::average_block_time()).await;
+        return Ok(None);
+    }
+    let best_block: u64 = sub.block_number(()).await?.into();
+    let possible_beefy_block = best_block - ((best_block - latest_sent) % BEEFY_MIN_DELTA as u64);
+    for i in 0..3 {
+        let block_to_check = possible_beefy_block.saturating_sub(i * BEEFY_MIN_DELTA as u64);
+        if block_to_check <= latest_sent {
+            break;
+        }
+        let Some(justification) =
+            get_commitment_from_block(sub, block_to_check.unique_saturated_into(), false).await?
+        else {
+            continue;
+        };
+        latest_commitment.store(block_to_check, Ordering::Relaxed);
+        return Ok(Some(justification));
+    }
+    tokio::time::sleep(T::average_block_time()).await;
+    Ok(None)
+}
+
+type JustificationSubscription = Subscription<EncodedBeefyCommitment>;
+
+/// Maximum power of two applied to the resubscription delay.
+const MAX_RESUBSCRIBE_BACKOFF: u32 = 4;
+
+/// Subscription to BEEFY justifications. After a failure, e.g. when the node
+/// doesn't provide the RPC, it is retried with exponentially growing delay.
+#[derive(Default)]
+struct SubscriptionState {
+    subscription: Option<JustificationSubscription>,
+    failures: u32,
+    retry_at: Option<tokio::time::Instant>,
+}
+
+impl SubscriptionState {
+    fn failed(&mut self, block_time: std::time::Duration) -> std::time::Duration {
+        self.subscription = None;
+        self.failures += 1;
+        let delay = resubscribe_delay(block_time, self.failures);
+        self.retry_at = Some(tokio::time::Instant::now() + delay);
+        delay
+    }
+
+    fn should_subscribe(&self) -> bool {
+        self.subscription.is_none()
+            && self
+                .retry_at
+                .map_or(true, |retry_at| retry_at <= tokio::time::Instant::now())
+    }
+}
+
+/// Delay before the next subscription attempt after `failures` failed ones in a row.
+fn resubscribe_delay(block_time: std::time::Duration, failures: u32) -> std::time::Duration {
+    let backoff = failures.saturating_sub(1).min(MAX_RESUBSCRIBE_BACKOFF);
+    block_time * BEEFY_MIN_DELTA * 2u32.pow(backoff)
+}
+
+/// Streams justifications received from `beefy_subscribeJustifications`.
+/// Commitments of the next validator set are left to the mandatory commitments
+/// stream. Blocks are scanned as in [`beefy_commitment_stream`] when the
+/// subscription is unavailable or there are no notifications for a while.
+pub fn beefy_justification_subscription_stream<T>(
+    sub: SubUnsignedClient<T>,
+    latest_commitment: Arc<AtomicU64>,
+) -> impl Stream<Item = AnyResult<BeefyJustification<T>>> + Unpin
+where
+    T: SenderConfig + 'static,
+{
+    let stream = futures::stream::unfold(SubscriptionState::default(), move |state| {
+        let sub = sub.clone();
+        let latest_commitment = latest_commitment.clone();
+        async move {
+            let (res, state) = next_subscribed_commitment(&sub, &latest_commitment, state).await;
+            Some((res, state))
+        }
+    })
+    .filter_map(|x| futures::future::ready(x.transpose()));
+    Box::pin(stream)
+}
+
+async fn next_subscribed_commitment<T>(
+    sub: &SubUnsignedClient<T>,
+    latest_commitment: &AtomicU64,
+    mut state: SubscriptionState,
+) -> (AnyResult<Option<BeefyJustification<T>>>, SubscriptionState)
+where
+    T: SenderConfig,
+{
+    if state.should_subscribe() {
+        match sub.beefy().subscribe_justifications().await {
+            Ok(subscription) => state.subscription = Some(subscription),
+            Err(err) => {
+                let delay = state.failed(T::average_block_time());
+                warn!(
+                    "Failed to subscribe to BEEFY justifications, retrying in {:?}: {}",
+                    delay, err
+                );
+            }
+        }
+    }
+    let Some(mut subscription) = state.subscription.take() else {
+        return (poll_beefy_commitment(sub, latest_commitment).await, state);
+    };
+    let timeout = T::average_block_time() * BEEFY_MIN_DELTA * 2;
+    match tokio::time::timeout(timeout, subscription.next()).await {
+        Ok(Some(Ok(encoded))) => {
+            state.failures = 0;
+            state.subscription = Some(subscription);
+            (
+                subscribed_commitment(sub, latest_commitment, encoded).await,
+                state,
+            )
+        }
+        Ok(Some(Err(err))) => {
+            let delay = state.failed(T::average_block_time());
+            warn!(
+                "BEEFY justifications subscription error, resubscribing in {:?}: {}",
+                delay, err
+            );
+            (poll_beefy_commitment(sub, latest_commitment).await, state)
+        }
+        Ok(None) => {
+            let delay = state.failed(T::average_block_time());
+            warn!(
+                "BEEFY justifications subscription closed, resubscribing in {:?}",
+                delay
+            );
+            (poll_beefy_commitment(sub, latest_commitment).await, state)
+        }
+        Err(_) => {
+            debug!("No BEEFY justifications received in {:?}", timeout);
+            state.subscription = Some(subscription);
+            (poll_beefy_commitment(sub, latest_commitment).await, state)
+        }
+    }
+}
+
+async fn subscribed_commitment<T>(
+    sub: &SubUnsignedClient<T>,
+    latest_commitment: &AtomicU64,
+    encoded: EncodedBeefyCommitment,
+) -> AnyResult<Option<BeefyJustification<T>>>
+where
+    T: SenderConfig,
+{
+    let commitment = encoded.decode::<T>()?;
+    let VersionedFinalityProof::V1(signed_commitment) = &commitment;
+    let block = signed_commitment.commitment.block_number;
+    let block_number: u64 = block.into();
+    trace!("Received commitment for block {}", block_number);
+    let latest_sent = latest_commitment.load(Ordering::Relaxed);
+    if block_number <= latest_sent {
+        return Ok(None);
+    }
+    let vset_storage = T::current_validator_set();
+    let latest_sent_vset = sub
+        .storage_fetch_or_default(&vset_storage, latest_sent)
+        .await?
+        .id;
+    let vset = sub.storage_fetch_or_default(&vset_storage, block).await?.id;
+    if latest_sent_vset < vset {
+        debug!("Waiting for mandatory commitment");
+        return Ok(None);
+    }
+    let justification = match BeefyJustification::create(sub.clone(), commitment, false).await {
+        Ok(justification) => justification,
+        Err(err) => {
+            warn!("failed to create justification: {}", err);
+            return Ok(None);
+        }
+    };
+    latest_commitment.store(block_number, Ordering::Relaxed);
+    Ok(Some(justification))
+}
+
 pub async fn subscribe_beefy_justifications<T>(
     sub: SubUnsignedClient<T>,
     latest_sent: u64,
+    use_subscription: bool,
+    boundaries: ValidatorSetBoundaries,
 ) -> AnyResult<impl Stream<Item = AnyResult<BeefyJustification<T>>> + Unpin>
 where
     T: SenderConfig + 'static,
@@ -177,18 +466,153 @@ where
             .storage_fetch_or_default(&T::current_validator_set(), ())
             .await?
             .id;
-        let mandatory = find_mandatory_commitment(&sub, vset_id)
+        let mandatory = boundaries
+            .find(&sub, vset_id)
             .await?
             .expect("mandatory commitment should exist");
         mandatory.into()
     };
     let latest_commitment = Arc::new(AtomicU64::new(latest_commitment));
-    let mandatory_stream =
-        mandatory_commitment_stream(sub.clone(), latest_commitment.clone(), latest_sent_vset);
-    let beefy_stream = beefy_commitment_stream(sub.clone(), latest_commitment);
+    let mandatory_stream = mandatory_commitment_stream(
+        sub.clone(),
+        latest_commitment.clone(),
+        latest_sent_vset,
+        boundaries,
+    );
+    let beefy_stream = if use_subscription {
+        futures::future::Either::Left(beefy_justification_subscription_stream(
+            sub.clone(),
+            latest_commitment,
+        ))
+    } else {
+        futures::future::Either::Right(beefy_commitment_stream(sub.clone(), latest_commitment))
+    };
     // Always check mandatory commitments stream first
     let res = futures::stream::select_with_strategy(mandatory_stream, beefy_stream, |()| {
         futures::stream::PollNext::Left
     });
     Ok(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Validator set id at each block, the id jumps by 2 at blocks 7 and 15.
+    const VSETS: [u64; 21] = [
+        0, 0, 0, 1, 1, 1, 1, 3, 3, 3, 4, 4, 4, 4, 4, 6, 6, 6, 6, 6, 6,
+    ];
+
+    /// Reads validator set ids from [`VSETS`] and records the queried blocks.
+    fn vset_id_at(
+        queried: &Mutex<Vec<u32>>,
+    ) -> impl Fn(u32) -> futures::future::Ready<AnyResult<u64>> + '_ {
+        move |block| {
+            queried.lock().unwrap().push(block);
+            futures::future::ready(Ok(VSETS[block as usize]))
+        }
+    }
+
+    #[tokio::test]
+    async fn finds_first_block_of_vset() {
+        let queried = Mutex::new(vec![]);
+        let boundaries = ValidatorSetBoundaries::default();
+        let find = |vset_id| boundaries.find_up_to(vset_id, 20u32, vset_id_at(&queried));
+        assert_eq!(find(1).await.unwrap(), Some(3));
+        assert_eq!(find(3).await.unwrap(), Some(7));
+        assert_eq!(find(6).await.unwrap(), Some(15));
+        assert_eq!(find(7).await.unwrap(), None);
+        // Skipped validator set resolves to the block of the next one
+        assert_eq!(find(2).await.unwrap(), Some(7));
+    }
+
+    #[tokio::test]
+    async fn find_uses_known_boundaries() {
+        let queried = Mutex::new(vec![]);
+        let boundaries = ValidatorSetBoundaries::default();
+        boundaries
+            .find_up_to(3, 20u32, vset_id_at(&queried))
+            .await
+            .unwrap();
+        queried.lock().unwrap().clear();
+        assert_eq!(
+            boundaries
+                .find_up_to(3, 20u32, vset_id_at(&queried))
+                .await
+                .unwrap(),
+            Some(7)
+        );
+        assert!(queried.lock().unwrap().is_empty());
+        assert_eq!(
+            boundaries
+                .find_up_to(4, 20u32, vset_id_at(&queried))
+                .await
+                .unwrap(),
+            Some(10)
+        );
+        assert!(queried.lock().unwrap().iter().all(|block| *block >= 7));
+    }
+
+    #[tokio::test]
+    async fn scans_vset_changes_with_jumps() {
+        let queried = Mutex::new(vec![]);
+        let boundaries = ValidatorSetBoundaries::default();
+        let changes = boundaries
+            .scan_with(0u32, 20, vset_id_at(&queried))
+            .await
+            .unwrap();
+        assert_eq!(changes, vec![(1, 3), (3, 7), (4, 10), (6, 15)]);
+        for (vset_id, block) in [(1, 3), (2, 7), (3, 7), (4, 10), (5, 15), (6, 15)] {
+            assert_eq!(boundaries.get(vset_id), Some(block));
+        }
+    }
+
+    #[tokio::test]
+    async fn scan_starts_after_from_block() {
+        let queried = Mutex::new(vec![]);
+        let boundaries = ValidatorSetBoundaries::default();
+        let changes = boundaries
+            .scan_with(7u32, 12, vset_id_at(&queried))
+            .await
+            .unwrap();
+        assert_eq!(changes, vec![(4, 10)]);
+        assert!(boundaries.get(3).is_none());
+
+        queried.lock().unwrap().clear();
+        let changes = boundaries
+            .scan_with(3u32, 16, vset_id_at(&queried))
+            .await
+            .unwrap();
+        assert_eq!(changes, vec![(3, 7), (4, 10), (6, 15)]);
+        assert!(boundaries
+            .scan_with(15u32, 20, vset_id_at(&queried))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn resubscribe_delay_grows_up_to_limit() {
+        let block_time = Duration::from_secs(6);
+        let delays = (1..=7)
+            .map(|failures| resubscribe_delay(block_time, failures).as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, vec![48, 96, 192, 384, 768, 768, 768]);
+    }
+
+    #[tokio::test]
+    async fn subscribes_again_after_delay() {
+        let mut state = SubscriptionState::default();
+        assert!(state.should_subscribe());
+        let delay = state.failed(Duration::from_millis(5));
+        assert_eq!(delay, Duration::from_millis(40));
+        assert!(!state.should_subscribe());
+        tokio::time::sleep(delay).await;
+        assert!(state.should_subscribe());
+        assert_eq!(
+            state.failed(Duration::from_millis(5)),
+            Duration::from_millis(80)
+        );
+    }
+}
@@ -34,18 +34,27 @@ pub mod beefy_subscription;
 pub mod traits;
 pub mod types;
 
-use std::collections::BTreeSet;
+use std::num::NonZeroUsize;
 use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
+use std::time::Duration;
 
+use crate::endpoints::{spawn_health_check, Endpoints, EndpointsHealth};
+use crate::history::{EntryKind, HistoryEntry};
 use crate::prelude::*;
 use bridge_types::types::AuxiliaryDigest;
 use bridge_types::GenericNetworkId;
 use common::{AssetName, AssetSymbol, Balance, ContentSource, Description};
+use jsonrpsee::core::client::{BatchResponse, ClientT, Subscription, SubscriptionClientT};
+use jsonrpsee::core::params::BatchRequestBuilder;
+use jsonrpsee::core::traits::ToRpcParams;
+use jsonrpsee::core::{DeserializeOwned, Error as RpcError};
+use lru::LruCache;
 use mmr_rpc::MmrApiClient;
 use sp_core::{ecdsa, H256};
 use sp_mmr_primitives::{EncodableOpaqueLeaf, Proof};
 use sp_runtime::traits::AtLeast32BitUnsigned;
+use sp_runtime::transaction_validity::{TransactionSource, TransactionValidity};
 use std::sync::RwLock;
 pub use substrate_gen::runtime;
 use subxt::blocks::ExtrinsicEvents;
@@ -109,8 +118,166 @@ pub fn log_extrinsic_events<T: ConfigExt>(events: ExtrinsicEvents<T::Config>) {
     }
 }
 
+fn call_name<P: subxt::tx::TxPayload>(xt: &P) -> String {
+    xt.validation_details()
+        .map(|call| format!("{}::{}", call.pallet_name, call.call_name))
+        .unwrap_or("unknown call".to_string())
+}
+
+/// Delay before the first reconnection attempt.
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// Upper bound for the delay between reconnection attempts.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// WebSocket RPC client which transparently reconnects to the node.
+///
+/// Requests which were in flight when the connection was lost fail with
+/// the error returned by the dropped connection and can be retried, the
+/// next request waits until the connection is restored. When several
+/// endpoints are given, the client fails over to the next one and switches
+/// away from an endpoint which lags behind the others.
 #[derive(Debug, Clone)]
-pub struct ClonableClient(Arc<jsonrpsee::async_client::Client>);
+pub struct ClonableClient(Arc<ReconnectingClient>);
+
+#[derive(Debug)]
+struct ReconnectingClient {
+    endpoints: Endpoints<Uri>,
+    /// Index of the connected endpoint and its client.
+    client: RwLock<(usize, Arc<jsonrpsee::async_client::Client>)>,
+    reconnect: tokio::sync::Mutex<()>,
+    /// Connections to inactive endpoints kept between health checks.
+    health_clients: Vec<tokio::sync::Mutex<Option<Arc<jsonrpsee::async_client::Client>>>>,
+}
+
+impl ClonableClient {
+    pub async fn new(urls: Vec<Uri>) -> AnyResult<Self> {
+        let endpoints = Endpoints::new(urls)?;
+        let mut last_err = None;
+        for index in endpoints.failover_order() {
+            match Self::connect(endpoints.url(index)).await {
+                Ok(client) => {
+                    endpoints.set_active(index);
+                    let inner = Arc::new(ReconnectingClient {
+                        health_clients: (0..endpoints.len()).map(|_| Default::default()).collect(),
+                        endpoints,
+                        client: RwLock::new((index, Arc::new(client))),
+                        reconnect: Default::default(),
+                    });
+                    spawn_health_check(&inner);
+                    return Ok(Self(inner));
+                }
+                Err(err) => {
+                    warn!("Failed to connect to {}: {:?}", endpoints.url(index), err);
+                    last_err = Some(err);
+                }
+            }
+        }
+        Err(last_err.expect("endpoints are not empty"))
+    }
+
+    async fn connect(url: &Uri) -> AnyResult<jsonrpsee::async_client::Client> {
+        let (sender, receiver) =
+            jsonrpsee::client_transport::ws::WsTransportClientBuilder::default()
+                .build(url.clone())
+                .await?;
+        let client = jsonrpsee::async_client::ClientBuilder::default()
+            .max_notifs_per_subscription(4096)
+            .build_with_tokio(sender, receiver);
+        Ok(client)
+    }
+
+    /// Returns connected client, reconnecting with exponential backoff if the
+    /// connection was lost or another endpoint was selected.
+    pub async fn connected(&self) -> Arc<jsonrpsee::async_client::Client> {
+        let (index, client) = self.0.current();
+        if index == self.0.endpoints.active() && client.is_connected() {
+            return client;
+        }
+        let _guard = self.0.reconnect.lock().await;
+        // Connection could be restored while we were waiting for the lock
+        let (index, client) = self.0.current();
+        if index == self.0.endpoints.active() && client.is_connected() {
+            return client;
+        }
+        if !client.is_connected() {
+            warn!(
+                "Connection to {} lost, reconnecting",
+                self.0.endpoints.url(index)
+            );
+        }
+        let mut delay = MIN_RECONNECT_DELAY;
+        loop {
+            for index in self.0.endpoints.failover_order() {
+                let url = self.0.endpoints.url(index);
+                match Self::connect(url).await {
+                    Ok(client) => {
+                        let client = Arc::new(client);
+                        *self.0.client.write().expect("poisoned lock") = (index, client.clone());
+                        self.0.endpoints.set_active(index);
+                        info!("Connected to {}", url);
+                        return client;
+                    }
+                    Err(err) => {
+                        warn!("Failed to connect to {}: {:?}", url, err);
+                    }
+                }
+            }
+            warn!("All endpoints are unavailable, retrying in {:?}", delay);
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+        }
+    }
+}
+
+impl ReconnectingClient {
+    fn current(&self) -> (usize, Arc<jsonrpsee::async_client::Client>) {
+        self.client.read().expect("poisoned lock").clone()
+    }
+
+    /// Returns client for the health check of the given endpoint, reusing
+    /// the active connection or the one opened by the previous check.
+    async fn health_client(&self, index: usize) -> AnyResult<Arc<jsonrpsee::async_client::Client>> {
+        let (current, client) = self.current();
+        let mut health_client = self.health_clients[index].lock().await;
+        if current == index && client.is_connected() {
+            // Active endpoint doesn't need a separate connection
+            *health_client = None;
+            return Ok(client);
+        }
+        if let Some(client) = health_client.as_ref() {
+            if client.is_connected() {
+                return Ok(client.clone());
+            }
+        }
+        *health_client = None;
+        let client = Arc::new(ClonableClient::connect(self.endpoints.url(index)).await?);
+        *health_client = Some(client.clone());
+        Ok(client)
+    }
+}
+
+#[async_trait::async_trait]
+impl EndpointsHealth for ReconnectingClient {
+    type Url = Uri;
+
+    fn endpoints(&self) -> &Endpoints<Uri> {
+        &self.endpoints
+    }
+
+    async fn finalized_height(&self, index: usize) -> AnyResult<u64> {
+        let client = self.health_client(index).await?;
+        let hash: serde_json::Value = client
+            .request("chain_getFinalizedHead", jsonrpsee::core::rpc_params![])
+            .await?;
+        let header: serde_json::Value = client
+            .request("chain_getHeader", jsonrpsee::core::rpc_params![hash])
+            .await?;
+        let number = header["number"]
+            .as_str()
+            .ok_or(anyhow!("Header number not found"))?;
+        Ok(u64::from_str_radix(number.trim_start_matches("0x"), 16)?)
+    }
+}
 
 impl RpcClientT for ClonableClient {
     fn request_raw<'a>(
@@ -118,7 +285,7 @@ impl RpcClientT for ClonableClient {
         method: &'a str,
         params: Option<Box<jsonrpsee::core::JsonRawValue>>,
     ) -> subxt::rpc::RpcFuture<'a, Box<jsonrpsee::core::JsonRawValue>> {
-        self.0.request_raw(method, params)
+        Box::pin(async move { self.connected().await.request_raw(method, params).await })
     }
 
     fn subscribe_raw<'a>(
@@ -127,7 +294,178 @@ impl RpcClientT for ClonableClient {
         params: Option<Box<jsonrpsee::core::JsonRawValue>>,
         unsub: &'a str,
     ) -> subxt::rpc::RpcFuture<'a, subxt::rpc::RpcSubscription> {
-        self.0.subscribe_raw(sub, params, unsub)
+        Box::pin(async move {
+            self.connected()
+                .await
+                .subscribe_raw(sub, params, unsub)
+                .await
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl ClientT for ClonableClient {
+    async fn notification<Params>(&self, method: &str, params: Params) -> Result<(), RpcError>
+    where
+        Params: ToRpcParams + Send,
+    {
+        self.connected().await.notification(method, params).await
+    }
+
+    async fn request<R, Params>(&self, method: &str, params: Params) -> Result<R, RpcError>
+    where
+        R: DeserializeOwned,
+        Params: ToRpcParams + Send,
+    {
+        self.connected().await.request(method, params).await
+    }
+
+    async fn batch_request<'a, R>(
+        &self,
+        batch: BatchRequestBuilder<'a>,
+    ) -> Result<BatchResponse<'a, R>, RpcError>
+    where
+        R: DeserializeOwned + std::fmt::Debug + 'a,
+    {
+        self.connected().await.batch_request(batch).await
+    }
+}
+
+#[async_trait::async_trait]
+impl SubscriptionClientT for ClonableClient {
+    async fn subscribe<'a, Notif, Params>(
+        &self,
+        subscribe_method: &'a str,
+        params: Params,
+        unsubscribe_method: &'a str,
+    ) -> Result<Subscription<Notif>, RpcError>
+    where
+        Params: ToRpcParams + Send,
+        Notif: DeserializeOwned,
+    {
+        self.connected()
+            .await
+            .subscribe(subscribe_method, params, unsubscribe_method)
+            .await
+    }
+
+    async fn subscribe_to_method<'a, Notif>(
+        &self,
+        method: &'a str,
+    ) -> Result<Subscription<Notif>, RpcError>
+    where
+        Notif: DeserializeOwned,
+    {
+        self.connected().await.subscribe_to_method(method).await
+    }
+}
+
+/// Number of commitment blocks remembered for each network.
+const COMMITMENT_BLOCKS_CACHE_SIZE: usize = 1024;
+
+/// Blocks where commitments with given nonces were created. Clones of the
+/// client share the cache, so relays reading the same sender don't search
+/// for the same commitment twice.
+#[derive(Debug, Clone)]
+struct CommitmentBlocks<N>(Arc<std::sync::Mutex<Vec<(GenericNetworkId, LruCache<u64, N>)>>>);
+
+impl<N> Default for CommitmentBlocks<N> {
+    fn default() -> Self {
+        Self(Default::default())
+    }
+}
+
+impl<N: Copy + Ord> CommitmentBlocks<N> {
+    fn get(&self, network_id: GenericNetworkId, nonce: u64) -> Option<N> {
+        let mut networks = self.0.lock().expect("poisoned");
+        networks
+            .iter_mut()
+            .find(|(id, _)| *id == network_id)
+            .and_then(|(_, cache)| cache.get(&nonce).copied())
+    }
+
+    fn put(&self, network_id: GenericNetworkId, nonce: u64, block: N) {
+        let mut networks = self.0.lock().expect("poisoned");
+        let index = match networks.iter().position(|(id, _)| *id == network_id) {
+            Some(index) => index,
+            None => {
+                let size = NonZeroUsize::new(COMMITMENT_BLOCKS_CACHE_SIZE).expect("not zero");
+                networks.push((network_id, LruCache::new(size)));
+                networks.len() - 1
+            }
+        };
+        networks[index].1.put(nonce, block);
+    }
+
+    /// Closest known blocks of commitments before and after the nonce.
+    fn bounds(&self, network_id: GenericNetworkId, nonce: u64) -> (Option<N>, Option<N>) {
+        let networks = self.0.lock().expect("poisoned");
+        let Some((_, cache)) = networks.iter().find(|(id, _)| *id == network_id) else {
+            return (None, None);
+        };
+        let before = cache
+            .iter()
+            .filter(|(cached, _)| **cached < nonce)
+            .map(|(_, block)| *block)
+            .max();
+        let after = cache
+            .iter()
+            .filter(|(cached, _)| **cached > nonce)
+            .map(|(_, block)| *block)
+            .min();
+        (before, after)
+    }
+}
+
+impl<N: AtLeast32BitUnsigned + Copy> CommitmentBlocks<N> {
+    /// Searches the first block up to `latest` with commitment nonce not less than
+    /// `nonce`, narrowing the range with known blocks of other commitments.
+    ///
+    /// Without known earlier commitment the range is bounded by galloping back
+    /// from `latest`, so pruned nodes are not asked for the state of old blocks.
+    async fn search<F, Fut>(
+        &self,
+        network_id: GenericNetworkId,
+        nonce: u64,
+        latest: N,
+        nonce_at: F,
+    ) -> AnyResult<Option<N>>
+    where
+        F: Fn(N) -> Fut,
+        Fut: futures::Future<Output = AnyResult<Option<u64>>>,
+    {
+        let (before, after) = self.bounds(network_id, nonce);
+        let mut high = after.map_or(latest, |block| block.min(latest));
+        let low = match before {
+            Some(block) => block + 1u32.into(),
+            None => {
+                let mut step: N = 1u32.into();
+                loop {
+                    if high <= step {
+                        break 1u32.into();
+                    }
+                    let block = high - step;
+                    match nonce_at(block).await? {
+                        Some(found) if found >= nonce => {
+                            high = block;
+                            step = step * 2u32.into();
+                        }
+                        _ => break block + 1u32.into(),
+                    }
+                }
+            }
+        };
+        binary_search_first_occurrence(low, high, nonce, nonce_at).await
+    }
+}
+
+/// Aborts the background task once the last clone of its owner is dropped.
+#[derive(Debug)]
+struct TaskGuard(tokio::task::JoinHandle<()>);
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        self.0.abort();
     }
 }
 
@@ -135,25 +473,59 @@ impl RpcClientT for ClonableClient {
 pub struct UnsignedClient<T: ConfigExt> {
     api: ApiInner<T>,
     client: ClonableClient,
+    commitment_blocks: CommitmentBlocks<BlockNumber<T>>,
+    _runtime_updater: Arc<TaskGuard>,
 }
 
 impl<T: ConfigExt> UnsignedClient<T> {
     pub async fn new(url: impl Into<String>) -> AnyResult<Self> {
-        let url: Uri = url.into().parse()?;
-        let (sender, receiver) =
-            jsonrpsee::client_transport::ws::WsTransportClientBuilder::default()
-                .build(url)
-                .await?;
-        let client = jsonrpsee::async_client::ClientBuilder::default()
-            .max_notifs_per_subscription(4096)
-            .build_with_tokio(sender, receiver);
-        let client = ClonableClient(Arc::new(client));
-        let api = ApiInner::<T>::from_rpc_client(client.clone().0).await?;
-        Ok(Self { api, client })
+        Self::with_endpoints(vec![url.into()]).await
+    }
+
+    /// Creates client which fails over between redundant endpoints.
+    pub async fn with_endpoints(urls: Vec<String>) -> AnyResult<Self> {
+        let urls = urls
+            .into_iter()
+            .map(|url| url.parse())
+            .collect::<Result<Vec<Uri>, _>>()?;
+        let client = ClonableClient::new(urls).await?;
+        let api = ApiInner::<T>::from_rpc_client(Arc::new(client.clone())).await?;
+        let runtime_updater = Self::spawn_runtime_updater(api.clone(), client.clone());
+        Ok(Self {
+            api,
+            client,
+            commitment_blocks: Default::default(),
+            _runtime_updater: Arc::new(runtime_updater),
+        })
     }
 
-    pub fn rpc(&self) -> &jsonrpsee::async_client::Client {
-        &self.client.0
+    /// Keeps metadata and runtime version up to date after runtime upgrades
+    /// and reconnects, because the node could be upgraded while it was down.
+    /// The updater stops when all clones of the client are dropped.
+    fn spawn_runtime_updater(api: ApiInner<T>, client: ClonableClient) -> TaskGuard {
+        TaskGuard(tokio::spawn(async move {
+            loop {
+                if let Err(err) = api.subscribe_to_updates().perform_runtime_updates().await {
+                    debug!("Runtime updates subscription closed: {:?}", err);
+                }
+                client.connected().await;
+                match ApiInner::<T>::from_rpc_client(Arc::new(client.clone())).await {
+                    Ok(fresh) => {
+                        api.set_metadata(fresh.metadata());
+                        api.set_runtime_version(fresh.runtime_version());
+                        debug!("Runtime version: {:?}", fresh.runtime_version());
+                    }
+                    Err(err) => {
+                        warn!("Failed to refresh runtime metadata: {:?}", err);
+                    }
+                }
+                tokio::time::sleep(MIN_RECONNECT_DELAY).await;
+            }
+        }))
+    }
+
+    pub fn rpc(&self) -> &ClonableClient {
+        &self.client
     }
 
     pub fn mmr(&self) -> &impl mmr_rpc::MmrApiClient<BlockHash<T>, BlockNumber<T>, MmrHash> {
@@ -231,6 +603,8 @@ impl<T: ConfigExt> UnsignedClient<T> {
         Ok(commitment)
     }
 
+    /// Finds commitment by nonce with binary search over blocks, because
+    /// nonce in `latest_commitment` storage grows with block number.
     pub async fn commitment_with_nonce<N: Into<BlockNumberOrHash>>(
         &self,
         network_id: GenericNetworkId,
@@ -240,18 +614,49 @@ impl<T: ConfigExt> UnsignedClient<T> {
     where
         T: SenderConfig,
     {
-        let mut current_block = at.into();
-        loop {
-            let commitment = self.latest_commitment(network_id, current_block).await?;
-            match commitment.commitment.nonce().cmp(&nonce) {
-                std::cmp::Ordering::Equal => return Ok(commitment),
-                std::cmp::Ordering::Greater => {}
-                std::cmp::Ordering::Less => return Err(anyhow!("Commitment nonce too low")),
+        let at = self.block_number(at).await?;
+        if let Some(block) = self.commitment_blocks.get(network_id, nonce) {
+            if block <= at {
+                let commitment = self.latest_commitment(network_id, block).await?;
+                if commitment.commitment.nonce() == nonce {
+                    return Ok(commitment);
+                }
             }
-            current_block = BlockNumberOrHash::Number(
-                Into::<u64>::into(commitment.block_number).saturating_sub(1),
-            );
         }
+        let latest = self.latest_commitment(network_id, at).await?;
+        match latest.commitment.nonce().cmp(&nonce) {
+            std::cmp::Ordering::Equal => {
+                self.commitment_blocks
+                    .put(network_id, nonce, latest.block_number);
+                return Ok(latest);
+            }
+            std::cmp::Ordering::Greater => {}
+            std::cmp::Ordering::Less => return Err(anyhow!("Commitment nonce too low")),
+        }
+        let address = T::latest_commitment(network_id);
+        let block = self
+            .commitment_blocks
+            .search(network_id, nonce, latest.block_number, |n| {
+                let address = &address;
+                async move {
+                    let commitment = self.storage_fetch(address, n).await?;
+                    Ok(commitment.map(|commitment| commitment.commitment.nonce()))
+                }
+            })
+            .await?
+            .ok_or(anyhow!("Commitment {} not found", nonce))?;
+        let commitment = self.latest_commitment(network_id, block).await?;
+        if commitment.commitment.nonce() != nonce {
+            return Err(anyhow!(
+                "Commitment {} not found, block {:?} has commitment {}",
+                nonce,
+                block,
+                commitment.commitment.nonce()
+            ));
+        }
+        self.commitment_blocks
+            .put(network_id, nonce, commitment.block_number);
+        Ok(commitment)
     }
 
     pub async fn beefy_start_block(&self) -> AnyResult<u64> {
@@ -434,6 +839,42 @@ impl<T: ConfigExt> UnsignedClient<T> {
         Ok(res)
     }
 
+    /// Returns raw keys of all entries of the storage map at the finalized block.
+    pub async fn storage_keys<Address: StorageAddress>(
+        &self,
+        address: &Address,
+    ) -> AnyResult<Vec<Vec<u8>>> {
+        const PAGE_SIZE: usize = 512;
+        let hash = self.finalized_head().await?;
+        let mut prefix = sp_core::twox_128(address.pallet_name().as_bytes()).to_vec();
+        prefix.extend(sp_core::twox_128(address.entry_name().as_bytes()));
+        let mut keys: Vec<Vec<u8>> = vec![];
+        loop {
+            let page: Vec<sp_core::Bytes> = self
+                .rpc()
+                .request(
+                    "state_getKeysPaged",
+                    jsonrpsee::core::rpc_params![
+                        sp_core::Bytes(prefix.clone()),
+                        PAGE_SIZE,
+                        keys.last().cloned().map(sp_core::Bytes),
+                        hash
+                    ],
+                )
+                .await
+                .context(format!(
+                    "Fetch storage keys {}::{}",
+                    address.pallet_name(),
+                    address.entry_name()
+                ))?;
+            let page_len = page.len();
+            keys.extend(page.into_iter().map(|key| key.0));
+            if page_len < PAGE_SIZE {
+                return Ok(keys);
+            }
+        }
+    }
+
     pub fn constant_fetch_or_default<Address>(
         &self,
         address: &Address,
@@ -470,7 +911,15 @@ impl<T: ConfigExt> UnsignedClient<T> {
     pub async fn submit_unsigned_extrinsic<P: subxt::tx::TxPayload>(
         &self,
         xt: &P,
-    ) -> AnyResult<()> {
+    ) -> AnyResult<SubmittedExtrinsic> {
+        self.broadcast_unsigned_extrinsic(xt).await?.wait().await
+    }
+
+    /// Submits unsigned extrinsic without waiting for its inclusion.
+    pub async fn broadcast_unsigned_extrinsic<P: subxt::tx::TxPayload>(
+        &self,
+        xt: &P,
+    ) -> AnyResult<PendingExtrinsic<T>> {
         if let Some(validation) = xt.validation_details() {
             debug!(
                 "Submitting extrinsic: {}::{}",
@@ -479,40 +928,74 @@ impl<T: ConfigExt> UnsignedClient<T> {
         } else {
             debug!("Submitting extrinsic without validation data");
         }
+        let call = call_name(xt);
         let xt = Unvalidated(xt);
-        let res = self
-            .api()
-            .tx()
-            .create_unsigned(&xt)?
+        let xt = self.api().tx().create_unsigned(&xt)?;
+        if crate::dry_run::is_enabled() {
+            let submitted = self.dry_run_extrinsic(&call, xt.encoded()).await?;
+            return Ok(PendingExtrinsic::DryRun(submitted));
+        }
+        let progress = xt
             .submit_and_watch()
             .await
             .map_err(|e| {
                 debug!("submit then watch error: {:?}", e);
                 e
             })
-            .context("sign and submit then watch")?
-            .wait_for_in_block()
-            .await
-            .map_err(|e| {
-                debug!("wait for in block error: {:?}", e);
-                e
-            })
-            .context("wait for in block")?
-            .wait_for_success()
-            .await
-            .map_err(|e| {
-                debug!("wait for success error: {:?}", e);
-                e
-            })
-            .context("wait for success")?;
-        log_extrinsic_events::<T>(res);
-        Ok(())
+            .context("sign and submit then watch")?;
+        Ok(PendingExtrinsic::Broadcast(progress, None))
+    }
+
+    /// Validates encoded extrinsic against the finalized state and logs it instead of submitting.
+    pub async fn dry_run_extrinsic(
+        &self,
+        call: &str,
+        encoded: &[u8],
+    ) -> AnyResult<SubmittedExtrinsic> {
+        let hash = H256(sp_core::hashing::blake2_256(encoded));
+        let at = self.finalized_head().await?;
+        let mut params = TransactionSource::External.encode();
+        params.extend_from_slice(encoded);
+        params.extend(at.encode());
+        let res: sp_core::Bytes = self
+            .rpc()
+            .request(
+                "state_call",
+                jsonrpsee::core::rpc_params![
+                    "TaggedTransactionQueue_validate_transaction",
+                    sp_core::Bytes(params),
+                    at
+                ],
+            )
+            .await?;
+        match TransactionValidity::decode(&mut &res[..])? {
+            Ok(validity) => {
+                info!(
+                    "Dry run: extrinsic {} {:?} is valid (priority {}), not submitted: 0x{}",
+                    call,
+                    hash,
+                    validity.priority,
+                    hex::encode(encoded)
+                );
+                Ok(SubmittedExtrinsic {
+                    extrinsic_hash: hash,
+                    block_hash: None,
+                    dry_run: true,
+                })
+            }
+            Err(err) => Err(anyhow!(
+                "Dry run: extrinsic {} {:?} is invalid: {:?}",
+                call,
+                hash,
+                err
+            )),
+        }
     }
 
     pub async fn submit_concurrent_unsigned_extrinsic<P: subxt::tx::TxPayload>(
         &self,
         xt: &P,
-    ) -> AnyResult<bool> {
+    ) -> AnyResult<Option<SubmittedExtrinsic>> {
         let result = self.submit_unsigned_extrinsic(xt).await;
         match result {
             Err(e) => {
@@ -521,12 +1004,137 @@ impl<T: ConfigExt> UnsignedClient<T> {
                     return Err(e);
                 };
                 if Self::is_transaction_imported_or_banned(subxt_error) {
-                    Ok(false)
+                    Ok(None)
                 } else {
                     Err(e)
                 }
             }
-            Ok(()) => Ok(true),
+            Ok(submitted) => Ok(Some(submitted)),
+        }
+    }
+}
+
+/// Extrinsic which is submitted to the node but not yet included in a block.
+pub enum PendingExtrinsic<T: ConfigExt> {
+    /// Submitted extrinsic with the nonce manager of its signer, if it is signed.
+    Broadcast(
+        subxt::tx::TxProgress<T::Config, ApiInner<T>>,
+        Option<NonceManager<T>>,
+    ),
+    /// Extrinsic which is only validated in dry run mode.
+    DryRun(SubmittedExtrinsic),
+}
+
+impl<T: ConfigExt> PendingExtrinsic<T> {
+    /// Waits until the extrinsic is included in a block and succeeds.
+    pub async fn wait(self) -> AnyResult<SubmittedExtrinsic> {
+        self.wait_for(false).await
+    }
+
+    /// Waits until the block with the extrinsic is finalized.
+    pub async fn wait_finalized(self) -> AnyResult<SubmittedExtrinsic> {
+        self.wait_for(true).await
+    }
+
+    async fn wait_for(self, finalized: bool) -> AnyResult<SubmittedExtrinsic> {
+        let (progress, nonce) = match self {
+            Self::Broadcast(progress, nonce) => (progress, nonce),
+            Self::DryRun(submitted) => return Ok(submitted),
+        };
+        let res = if finalized {
+            progress
+                .wait_for_finalized()
+                .await
+                .map_err(|e| {
+                    debug!("wait for finalized error: {:?}", e);
+                    e
+                })
+                .context("wait for finalized")
+        } else {
+            progress
+                .wait_for_in_block()
+                .await
+                .map_err(|e| {
+                    debug!("wait for in block error: {:?}", e);
+                    e
+                })
+                .context("wait for in block")
+        };
+        let res = match res {
+            Ok(in_block) => in_block
+                .wait_for_success()
+                .await
+                .map_err(|e| {
+                    debug!("wait for success error: {:?}", e);
+                    e
+                })
+                .context("wait for success"),
+            Err(err) => {
+                // Extrinsic is dropped or invalid, so its nonce could be free again
+                if let Some(nonce) = nonce {
+                    nonce.reset().await;
+                }
+                Err(err)
+            }
+        }?;
+        let submitted = SubmittedExtrinsic::from_events::<T>(&res);
+        log_extrinsic_events::<T>(res);
+        Ok(submitted)
+    }
+}
+
+/// Hands out sequential nonces to extrinsics signed by one account, so
+/// several tasks can submit extrinsics at the same time. Nonce is loaded
+/// from the node again after an extrinsic fails to be submitted or is
+/// dropped from the pool.
+#[derive(Clone)]
+pub struct NonceManager<T: ConfigExt> {
+    nonce: Arc<tokio::sync::Mutex<Option<Index<T>>>>,
+}
+
+impl<T: ConfigExt> Default for NonceManager<T> {
+    fn default() -> Self {
+        Self {
+            nonce: Default::default(),
+        }
+    }
+}
+
+impl<T: ConfigExt> NonceManager<T> {
+    /// Forgets the local nonce, the next extrinsic uses the one from the node.
+    pub async fn reset(&self) {
+        *self.nonce.lock().await = None;
+    }
+
+    pub async fn set(&self, index: Index<T>) {
+        *self.nonce.lock().await = Some(index);
+    }
+
+    /// Submits extrinsic with the next nonce, loading it with `load` if it is
+    /// unknown. Nonce stays locked until the extrinsic is in the pool, so
+    /// extrinsics reach the pool in nonce order. Nonce is forgotten if the
+    /// submission fails.
+    pub async fn submit_with_next<R, L, LF, F, FF>(&self, load: L, submit: F) -> AnyResult<R>
+    where
+        L: FnOnce() -> LF,
+        LF: futures::Future<Output = AnyResult<Index<T>>>,
+        F: FnOnce(Index<T>) -> FF,
+        FF: futures::Future<Output = AnyResult<R>>,
+    {
+        let mut nonce = self.nonce.lock().await;
+        let index = match *nonce {
+            Some(index) => index,
+            None => load().await?,
+        };
+        match submit(index).await {
+            Ok(res) => {
+                *nonce = Some(index + 1u32.into());
+                Ok(res)
+            }
+            Err(err) => {
+                *nonce = None;
+                Err(err)
+            }
         }
     }
 }
@@ -535,7 +1143,7 @@ impl<T: ConfigExt> UnsignedClient<T> {
 pub struct SignedClient<T: ConfigExt> {
     inner: UnsignedClient<T>,
     key: PairSigner<T>,
-    nonce: Arc<RwLock<Option<Index<T>>>>,
+    nonce: NonceManager<T>,
 }
 
 impl<T: ConfigExt> SignedClient<T> {
@@ -543,7 +1151,7 @@ impl<T: ConfigExt> SignedClient<T> {
         let res = Self {
             inner: client,
             key,
-            nonce: Arc::new(RwLock::new(None)),
+            nonce: Default::default(),
         };
         res.load_nonce().await?;
         Ok(res)
@@ -553,12 +1161,25 @@ impl<T: ConfigExt> SignedClient<T> {
         self.key.account_id().clone()
     }
 
-    pub async fn submit_extrinsic<P: subxt::tx::TxPayload>(&self, xt: &P) -> AnyResult<()>
+    pub async fn submit_extrinsic<P: subxt::tx::TxPayload>(
+        &self,
+        xt: &P,
+    ) -> AnyResult<SubmittedExtrinsic>
     where
-        <<<T as ConfigExt>::Config as subxt::Config>::ExtrinsicParams as subxt::tx::ExtrinsicParams<
-            <<T as ConfigExt>::Config as subxt::Config>::Index,
-            <<T as ConfigExt>::Config as subxt::Config>::Hash,
-        >>::OtherParams: Default,
+        OtherParams<T>: Default,
+    {
+        self.broadcast_extrinsic(xt).await?.wait().await
+    }
+
+    /// Signs extrinsic with the next account nonce and submits it without
+    /// waiting for its inclusion, so several extrinsics can be in the pool at
+    /// the same time.
+    pub async fn broadcast_extrinsic<P: subxt::tx::TxPayload>(
+        &self,
+        xt: &P,
+    ) -> AnyResult<PendingExtrinsic<T>>
+    where
+        OtherParams<T>: Default,
     {
         if let Some(validation) = xt.validation_details() {
             debug!(
@@ -569,35 +1190,54 @@ impl<T: ConfigExt> SignedClient<T> {
             debug!("Submitting extrinsic without validation data");
         }
         // Metadata validation often works incorrectly, so we turn it off for now
+        let call = call_name(xt);
         let xt = Unvalidated(xt);
-        let res = self
-            .api()
-            .tx()
-            .sign_and_submit_then_watch_default(&xt, self)
-            .await
-            .map_err(|e| {
-                error!("sign and submit then watch error: {:?}", e);
-                e
-            })
-            .context("sign and submit then watch")?
-            .wait_for_in_block()
-            .await
-            .context("wait for in block")?
-            .wait_for_success()
-            .await
-            .context("wait for success")?;
-        log_extrinsic_events::<T>(res);
-        Ok(())
+        if crate::dry_run::is_enabled() {
+            let xt = self
+                .api()
+                .tx()
+                .create_signed(&xt, self, Default::default())
+                .await?;
+            let submitted = self.dry_run_extrinsic(&call, xt.encoded()).await?;
+            return Ok(PendingExtrinsic::DryRun(submitted));
+        }
+        let progress = self
+            .nonce
+            .submit_with_next(
+                || self.next_index(),
+                |index| async move {
+                    let xt = self
+                        .api()
+                        .tx()
+                        .create_signed_with_nonce(&xt, self, index, Default::default())
+                        .context("sign extrinsic")?;
+                    xt.submit_and_watch().await.map_err(|e| {
+                        error!("sign and submit then watch error: {:?}", e);
+                        anyhow::Error::from(e).context("sign and submit then watch")
+                    })
+                },
+            )
+            .await?;
+        Ok(PendingExtrinsic::Broadcast(
+            progress,
+            Some(self.nonce.clone()),
+        ))
     }
 
-    pub async fn load_nonce(&self) -> AnyResult<()> {
-        let nonce = self
+    /// Next account nonce known to the node, including extrinsics in the pool.
+    async fn next_index(&self) -> AnyResult<Index<T>> {
+        let index = self
             .inner
             .api()
             .rpc()
             .system_account_next_index(&self.key.account_id())
             .await?;
-        self.set_nonce(nonce);
+        Ok(index)
+    }
+
+    pub async fn load_nonce(&self) -> AnyResult<()> {
+        let index = self.next_index().await?;
+        self.set_nonce(index).await;
         Ok(())
     }
 
@@ -609,9 +1249,8 @@ impl<T: ConfigExt> SignedClient<T> {
         &self.inner.api()
     }
 
-    pub fn set_nonce(&self, index: Index<T>) {
-        let mut nonce = self.nonce.write().expect("poisoned");
-        *nonce = Some(index);
+    pub async fn set_nonce(&self, index: Index<T>) {
+        self.nonce.set(index).await;
     }
 }
 
@@ -643,6 +1282,13 @@ impl<T: ConfigExt> Signer<T::Config> for SignedClient<T> {
     }
 }
 
+/// Actions performed by this relayer while submitting inbound commitment
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InboundCommitmentStatus {
+    pub approval_sent: bool,
+    pub commitment_submitted: bool,
+}
+
 impl UnsignedClient<MainnetConfig> {
     pub async fn submit_inbound_commitment(
         &self,
@@ -650,11 +1296,25 @@ impl UnsignedClient<MainnetConfig> {
         sender: GenericNetworkId,
         receiver: GenericNetworkId,
         commitment: UnboundedGenericCommitment,
-    ) -> AnyResult<()> {
+    ) -> AnyResult<InboundCommitmentStatus> {
         info!("Submit commitment {commitment:?}");
-        let message =
-            sp_runtime::traits::Keccak256::hash_of(&(sender, receiver, commitment.hash()));
-        self.approve_message(signer, sender, message).await?;
+        let mut status = InboundCommitmentStatus::default();
+        let nonce = commitment.nonce();
+        let commitment_hash = commitment.hash();
+        let history_entry = |kind| {
+            HistoryEntry::new(kind, sender, receiver)
+                .with_nonce(nonce)
+                .with_commitment_hash(commitment_hash)
+        };
+        let message = sp_runtime::traits::Keccak256::hash_of(&(sender, receiver, commitment_hash));
+        let approval = self
+            .approve_message(signer, sender, message)
+            .await
+            .transpose();
+        if let Some(res) = &approval {
+            history_entry(EntryKind::Approval).record_extrinsic(res);
+        }
+        status.approval_sent = approval.transpose()?.is_some();
         if self.should_send_commitment(&sender, message).await? {
             info!("Sending commitment");
             let approvals = self.bridge_approvals(&sender, message).await?;
@@ -663,112 +1323,203 @@ impl UnsignedClient<MainnetConfig> {
                     proof: approvals.try_into().unwrap(),
                 },
             );
-            let success = self
+            let history_entry = history_entry(EntryKind::InboundCommitment);
+            let res = self
                 .submit_concurrent_unsigned_extrinsic(
                     &runtime::tx()
                         .bridge_inbound_channel()
                         .submit(sender, commitment, proof),
                 )
-                .await?;
+                .await
+                .transpose();
+            if let Some(res) = &res {
+                history_entry.record_extrinsic(res);
+            }
+            let success = res.transpose()?.is_some();
             if success {
                 info!("Commitment submitted by this relayer");
             } else {
                 info!("Commitment will be submitted by another relayer");
             }
+            status.commitment_submitted = success;
         }
-        Ok(())
+        Ok(status)
     }
+}
 
-    pub async fn approve_message(
-        &self,
-        signer: ecdsa::Pair,
-        sender: GenericNetworkId,
-        message: H256,
-    ) -> AnyResult<()> {
-        if self
-            .should_send_approval(&sender, signer.public(), message)
-            .await?
-        {
-            info!("Sending approval");
-            let signature = signer.sign_prehashed(&message.0);
-            self.submit_unsigned_extrinsic(
-                &runtime::tx()
-                    .bridge_data_signer()
-                    .approve(sender, message, signature),
-            )
-            .await?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bridge_types::SubNetworkId;
+
+    const NETWORK: GenericNetworkId = GenericNetworkId::Sub(SubNetworkId::Kusama);
+    const OTHER_NETWORK: GenericNetworkId = GenericNetworkId::Sub(SubNetworkId::Liberland);
+
+    /// Latest commitment nonce at each block, nonce 3 is overwritten by 4 in block 7.
+    const NONCES: [u64; 10] = [0, 0, 1, 1, 2, 2, 2, 4, 5, 5];
+
+    async fn search(blocks: &CommitmentBlocks<u32>, nonce: u64) -> (Option<u32>, Vec<u32>) {
+        let queried = std::sync::Mutex::new(vec![]);
+        let block = blocks
+            .search(NETWORK, nonce, NONCES.len() as u32 - 1, |n| {
+                queried.lock().unwrap().push(n);
+                let nonce = NONCES.get(n as usize).copied();
+                async move { Ok(nonce) }
+            })
+            .await
+            .unwrap();
+        (block, queried.into_inner().unwrap())
+    }
+
+    #[test]
+    fn bounds_are_nearest_known_blocks() {
+        let blocks = CommitmentBlocks::default();
+        blocks.put(NETWORK, 2, 10u32);
+        blocks.put(NETWORK, 5, 30);
+        blocks.put(NETWORK, 8, 50);
+        blocks.put(OTHER_NETWORK, 4, 20);
+        assert_eq!(blocks.bounds(NETWORK, 5), (Some(10), Some(50)));
+        assert_eq!(blocks.bounds(NETWORK, 6), (Some(30), Some(50)));
+        assert_eq!(blocks.bounds(NETWORK, 1), (None, Some(10)));
+        assert_eq!(blocks.bounds(NETWORK, 9), (Some(50), None));
+        assert_eq!(blocks.bounds(OTHER_NETWORK, 4), (None, None));
+        assert_eq!(
+            blocks.bounds(GenericNetworkId::EVM(1u64.into()), 4),
+            (None, None)
+        );
+    }
+
+    #[test]
+    fn evicts_least_recently_used_blocks() {
+        let blocks = CommitmentBlocks::default();
+        for nonce in 0..COMMITMENT_BLOCKS_CACHE_SIZE as u64 {
+            blocks.put(NETWORK, nonce, nonce as u32);
         }
-        Ok(())
+        blocks.put(OTHER_NETWORK, 0, 100);
+        assert_eq!(blocks.get(NETWORK, 0), Some(0));
+        blocks.put(NETWORK, COMMITMENT_BLOCKS_CACHE_SIZE as u64, 2000);
+        assert_eq!(blocks.get(NETWORK, 0), Some(0));
+        assert_eq!(blocks.get(NETWORK, 1), None);
+        assert_eq!(
+            blocks.get(NETWORK, COMMITMENT_BLOCKS_CACHE_SIZE as u64),
+            Some(2000)
+        );
+        assert_eq!(blocks.get(OTHER_NETWORK, 0), Some(100));
     }
 
-    pub async fn should_send_approval(
-        &self,
-        network_id: &GenericNetworkId,
-        signer: ecdsa::Public,
-        message: H256,
-    ) -> AnyResult<bool> {
-        let peers = self.bridge_peers(network_id).await?;
-        let approvals = self.bridge_approvals(network_id, message).await?;
-        let is_already_approved = approvals
-            .iter()
-            .filter_map(|approval| approval.recover_prehashed(&message.0))
-            .any(|public| signer == public);
-        Ok(
-            (approvals.len() as u32) < bridge_types::utils::threshold(peers.len() as u32)
-                && !is_already_approved,
-        )
+    #[tokio::test]
+    async fn searches_first_block_with_nonce() {
+        let blocks = CommitmentBlocks::default();
+        assert_eq!(search(&blocks, 1).await.0, Some(2));
+        assert_eq!(search(&blocks, 2).await.0, Some(4));
+        assert_eq!(search(&blocks, 5).await.0, Some(8));
+        // Overwritten commitment resolves to the block of the next one
+        assert_eq!(search(&blocks, 3).await.0, Some(7));
+        assert_eq!(search(&blocks, 6).await.0, None);
     }
 
-    pub async fn should_send_commitment(
-        &self,
-        network_id: &GenericNetworkId,
-        message: H256,
-    ) -> AnyResult<bool> {
-        let peers = self.bridge_peers(network_id).await?;
-        let approvals = self.bridge_approvals(network_id, message).await?;
-        Ok((approvals.len() as u32) >= bridge_types::utils::threshold(peers.len() as u32))
+    #[tokio::test]
+    async fn search_gallops_back_from_latest_block() {
+        let blocks = CommitmentBlocks::default();
+        let (block, queried) = search(&blocks, 5).await;
+        assert_eq!(block, Some(8));
+        assert!(queried.iter().all(|n| *n >= 6), "{:?}", queried);
+        let (block, queried) = search(&blocks, 4).await;
+        assert_eq!(block, Some(7));
+        assert!(queried.iter().all(|n| *n >= 6), "{:?}", queried);
+        // Missing nonce is found to be missing by the latest blocks only
+        let (block, queried) = search(&blocks, 6).await;
+        assert_eq!(block, None);
+        assert!(queried.iter().all(|n| *n >= 8), "{:?}", queried);
     }
 
-    pub async fn bridge_approvals(
-        &self,
-        network_id: &GenericNetworkId,
-        message: H256,
-    ) -> AnyResult<Vec<ecdsa::Signature>> {
-        let peers = self.bridge_peers(network_id).await?;
-        let approvals = self
-            .storage_fetch_or_default(
-                &runtime::storage()
-                    .bridge_data_signer()
-                    .approvals(network_id, message),
-                (),
+    #[tokio::test]
+    async fn search_is_narrowed_by_known_blocks() {
+        let blocks = CommitmentBlocks::default();
+        blocks.put(NETWORK, 1, 2);
+        blocks.put(NETWORK, 4, 7);
+        let (block, queried) = search(&blocks, 2).await;
+        assert_eq!(block, Some(4));
+        assert!(queried.iter().all(|n| (3..=7).contains(n)));
+        // Cache of another network doesn't narrow the search
+        blocks.put(OTHER_NETWORK, 1, 8);
+        let (block, queried) = search(&blocks, 5).await;
+        assert_eq!(block, Some(8));
+        assert!(queried.iter().all(|n| (8..=9).contains(n)));
+    }
+
+    type Nonces = NonceManager<MainnetConfig>;
+
+    async fn submit(nonces: &Nonces, loaded: u32, fail: bool) -> AnyResult<u32> {
+        nonces
+            .submit_with_next(
+                || async move { Ok(loaded) },
+                |index| async move {
+                    if fail {
+                        Err(anyhow!("submit failed"))
+                    } else {
+                        Ok(index)
+                    }
+                },
             )
-            .await?;
-        let mut acceptable_approvals = vec![];
-        for approval in approvals {
-            let public = approval
-                .1
-                .recover_prehashed(&message.0)
-                .ok_or(anyhow!("Wrong signature in data signer pallet"))?;
-            if peers.contains(&public) {
-                acceptable_approvals.push(approval.1);
-            }
+            .await
+    }
+
+    #[tokio::test]
+    async fn nonce_manager_hands_out_sequential_nonces() {
+        let nonces = Nonces::default();
+        assert_eq!(submit(&nonces, 5, false).await.unwrap(), 5);
+        // Local nonce is used while it is known
+        assert_eq!(submit(&nonces, 0, false).await.unwrap(), 6);
+        assert_eq!(submit(&nonces, 0, false).await.unwrap(), 7);
+        nonces.set(10).await;
+        assert_eq!(submit(&nonces, 0, false).await.unwrap(), 10);
+    }
+
+    #[tokio::test]
+    async fn nonce_manager_serializes_concurrent_submissions() {
+        let nonces = Nonces::default();
+        let tasks = (0..10)
+            .map(|_| {
+                let nonces = nonces.clone();
+                tokio::spawn(async move { submit(&nonces, 3, false).await.unwrap() })
+            })
+            .collect::<Vec<_>>();
+        let mut handed_out = vec![];
+        for task in tasks {
+            handed_out.push(task.await.unwrap());
         }
-        Ok(acceptable_approvals)
+        handed_out.sort();
+        assert_eq!(handed_out, (3..13).collect::<Vec<_>>());
     }
 
-    pub async fn bridge_peers(
-        &self,
-        network_id: &GenericNetworkId,
-    ) -> AnyResult<BTreeSet<ecdsa::Public>> {
-        let peers = self
-            .storage_fetch(
-                &runtime::storage().multisig_verifier().peer_keys(network_id),
-                (),
+    #[tokio::test]
+    async fn nonce_manager_reloads_nonce_after_failed_submission() {
+        let nonces = Nonces::default();
+        assert_eq!(submit(&nonces, 5, false).await.unwrap(), 5);
+        assert!(submit(&nonces, 0, true).await.is_err());
+        assert_eq!(submit(&nonces, 4, false).await.unwrap(), 4);
+        assert_eq!(submit(&nonces, 0, false).await.unwrap(), 5);
+    }
+
+    #[tokio::test]
+    async fn nonce_manager_reloads_nonce_after_reset() {
+        let nonces = Nonces::default();
+        assert_eq!(submit(&nonces, 5, false).await.unwrap(), 5);
+        nonces.reset().await;
+        assert_eq!(submit(&nonces, 8, false).await.unwrap(), 8);
+    }
+
+    #[tokio::test]
+    async fn nonce_manager_keeps_nonce_if_load_fails() {
+        let nonces = Nonces::default();
+        let res = nonces
+            .submit_with_next(
+                || async { Err::<u32, _>(anyhow!("node is unavailable")) },
+                |index| async move { Ok(index) },
             )
-            .await?
-            .unwrap_or_default()
-            .into_iter()
-            .collect();
-        Ok(peers)
+            .await;
+        assert!(res.is_err());
+        assert_eq!(submit(&nonces, 2, false).await.unwrap(), 2);
     }
 }
@@ -38,7 +38,7 @@ pub struct MainnetConfig;
 #[derive(Clone, Copy, Debug)]
 pub struct LiberlandConfig;
 
-pub trait ConfigExt: Clone + core::fmt::Debug {
+pub trait ConfigExt: Clone + core::fmt::Debug + 'static {
     type Config: subxt::Config + Clone;
     type Event: Decode + core::fmt::Debug + Send + Sync + 'static;
     type BlockNumber: AtLeast32BitUnsigned
@@ -70,7 +70,7 @@ pub trait ConfigExt: Clone + core::fmt::Debug {
 }
 
 pub trait SenderConfig: ConfigExt + 'static {
-    type SubmitSignature: Encode;
+    type SubmitSignature: Encode + Send + Sync;
 
     fn current_validator_set() -> StaticStorageAddress<DecodeStaticType<ValidatorSet>, Yes, Yes, ()>;
 
@@ -114,7 +114,7 @@ pub trait SenderConfig: ConfigExt + 'static {
 
 pub trait ReceiverConfig: ConfigExt {
     type SubmitSignatureCommitment: Encode;
-    type SubmitMessagesCommitment: Encode;
+    type SubmitMessagesCommitment: Encode + Send + Sync;
     type MultiProof;
 
     fn submit_signature_commitment(
@@ -147,6 +147,12 @@ pub trait ReceiverConfig: ConfigExt {
         network_id: SubNetworkId,
     ) -> StaticStorageAddress<DecodeStaticType<u64>, Yes, Yes, Yes>;
 
+    /// Returns inbound channel nonce storage for messages from given network
+    /// or error if the runtime doesn't receive messages from such networks.
+    fn bridge_inbound_nonce(
+        network_id: GenericNetworkId,
+    ) -> AnyResult<StaticStorageAddress<DecodeStaticType<u64>, Yes, Yes, Yes>>;
+
     fn network_id() -> StaticConstantAddress<DecodeStaticType<bridge_types::GenericNetworkId>>;
 
     fn peers(
@@ -540,6 +546,23 @@ impl ReceiverConfig for MainnetConfig {
             .channel_nonces(network_id)
     }
 
+    fn bridge_inbound_nonce(
+        network_id: GenericNetworkId,
+    ) -> AnyResult<StaticStorageAddress<DecodeStaticType<u64>, Yes, Yes, Yes>> {
+        match network_id {
+            GenericNetworkId::Sub(network_id) => {
+                Ok(Self::substrate_bridge_inbound_nonce(network_id))
+            }
+            GenericNetworkId::EVMLegacy(_) => Err(anyhow!(
+                "Inbound nonce of {:?} is not supported",
+                network_id
+            )),
+            network_id => Ok(mainnet_runtime::storage()
+                .bridge_inbound_channel()
+                .channel_nonces(network_id)),
+        }
+    }
+
     fn network_id() -> StaticConstantAddress<DecodeStaticType<bridge_types::GenericNetworkId>> {
         mainnet_runtime::constants()
             .substrate_bridge_inbound_channel()
@@ -640,6 +663,20 @@ impl ReceiverConfig for ParachainConfig {
             .channel_nonces(network_id)
     }
 
+    fn bridge_inbound_nonce(
+        network_id: GenericNetworkId,
+    ) -> AnyResult<StaticStorageAddress<DecodeStaticType<u64>, Yes, Yes, Yes>> {
+        match network_id {
+            GenericNetworkId::Sub(network_id) => {
+                Ok(Self::substrate_bridge_inbound_nonce(network_id))
+            }
+            network_id => Err(anyhow!(
+                "Inbound nonce of {:?} is not supported",
+                network_id
+            )),
+        }
+    }
+
     fn network_id() -> StaticConstantAddress<DecodeStaticType<bridge_types::GenericNetworkId>> {
         parachain_runtime::constants()
             .substrate_bridge_inbound_channel()
@@ -723,6 +760,20 @@ impl ReceiverConfig for LiberlandConfig {
             .channel_nonces(network_id)
     }
 
+    fn bridge_inbound_nonce(
+        network_id: GenericNetworkId,
+    ) -> AnyResult<StaticStorageAddress<DecodeStaticType<u64>, Yes, Yes, Yes>> {
+        match network_id {
+            GenericNetworkId::Sub(network_id) => {
+                Ok(Self::substrate_bridge_inbound_nonce(network_id))
+            }
+            network_id => Err(anyhow!(
+                "Inbound nonce of {:?} is not supported",
+                network_id
+            )),
+        }
+    }
+
     fn network_id() -> StaticConstantAddress<DecodeStaticType<bridge_types::GenericNetworkId>> {
         liberland_runtime::constants()
             .substrate_bridge_inbound_channel()
@@ -38,6 +38,7 @@ pub use parachain_gen::parachain_runtime;
 use sp_core::Bytes;
 use sp_mmr_primitives::Proof;
 pub use substrate_gen::runtime as mainnet_runtime;
+use subxt::blocks::ExtrinsicEvents;
 use subxt::constants::ConstantAddress;
 use subxt::storage::StorageAddress;
 use subxt::tx::TxPayload;
@@ -118,6 +119,28 @@ impl From<H256> for BlockNumberOrHash {
     }
 }
 
+/// Extrinsic submitted by the relayer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SubmittedExtrinsic {
+    pub extrinsic_hash: H256,
+    /// Block which includes the extrinsic, `None` in dry run mode
+    pub block_hash: Option<H256>,
+    /// Extrinsic was only validated and not submitted
+    pub dry_run: bool,
+}
+
+impl SubmittedExtrinsic {
+    pub fn from_events<T: ConfigExt>(events: &ExtrinsicEvents<T::Config>) -> Self {
+        let extrinsic_hash: BlockHash<T> = events.extrinsic_hash().into();
+        let block_hash: BlockHash<T> = events.block_hash().into();
+        Self {
+            extrinsic_hash: H256::from_slice(extrinsic_hash.as_ref()),
+            block_hash: Some(H256::from_slice(block_hash.as_ref())),
+            dry_run: false,
+        }
+    }
+}
+
 pub struct Unvalidated<'a, P>(pub &'a P);
 
 impl<'a, P: TxPayload> TxPayload for Unvalidated<'a, P> {
@@ -0,0 +1,451 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! In-process server implementing the subset of toncenter HTTP API used by
+//! the relayer over a scripted in-memory state of TON accounts.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+
+use super::contracts::channel::OutboundMessage;
+use super::types::*;
+use super::TonClient;
+use crate::prelude::*;
+use ethers::types::I256;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use toner::{
+    tlb::{
+        ser::{CellSerialize, CellSerializeExt},
+        Cell,
+    },
+    ton::{
+        boc::{BagOfCells, BagOfCellsArgs},
+        MsgAddress,
+    },
+};
+
+/// Default number of transactions returned by `getTransactions`, same as toncenter.
+const DEFAULT_TRANSACTIONS_LIMIT: usize = 10;
+
+/// Exit code of TVM for unknown get method.
+const UNKNOWN_METHOD_EXIT_CODE: i64 = 11;
+
+#[derive(Default)]
+struct MockState {
+    /// Transactions of every account, newest first
+    transactions: HashMap<String, Vec<Transaction>>,
+    /// Results of get methods by account and method name
+    get_methods: HashMap<(String, String), (i64, Vec<I256>)>,
+    /// Last logical time used for transactions
+    lt: i64,
+    sent_bocs: Vec<Vec<u8>>,
+    /// Addresses and bodies of messages passed to `estimateFee`
+    estimated: Vec<(String, Vec<u8>)>,
+}
+
+/// Mock of TON HTTP API, stopped when dropped.
+pub struct MockTonApi {
+    state: Arc<Mutex<MockState>>,
+    url: Url,
+    _shutdown: tokio::sync::oneshot::Sender<()>,
+}
+
+impl MockTonApi {
+    /// Starts server on a random local port.
+    pub async fn start() -> AnyResult<Self> {
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let service_state = state.clone();
+        let server = hyper::Server::try_bind(&([127, 0, 0, 1], 0).into())?.serve(make_service_fn(
+            move |_| {
+                let state = service_state.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |req| handle_request(state.clone(), req)))
+                }
+            },
+        ));
+        let url = Url::parse(&format!("http://{}/", server.local_addr()))?;
+        let (shutdown, stop) = tokio::sync::oneshot::channel::<()>();
+        tokio::spawn(server.with_graceful_shutdown(async {
+            stop.await.ok();
+        }));
+        Ok(Self {
+            state,
+            url,
+            _shutdown: shutdown,
+        })
+    }
+
+    pub fn url(&self) -> Url {
+        self.url.clone()
+    }
+
+    pub fn client(&self) -> AnyResult<TonClient> {
+        TonClient::new(self.url(), None)
+    }
+
+    /// Sets result of get method `method` of the `account`.
+    pub fn set_get_method(
+        &self,
+        account: MsgAddress,
+        method: &str,
+        exit_code: i64,
+        stack: Vec<I256>,
+    ) {
+        self.state
+            .lock()
+            .expect("poisoned lock")
+            .get_methods
+            .insert(
+                (account.to_string(), method.to_string()),
+                (exit_code, stack),
+            );
+    }
+
+    /// Adds transaction of the `account` which sends message with given
+    /// body, external if `destination` is `None`. Returns logical time of the
+    /// transaction.
+    pub fn push_message(
+        &self,
+        account: MsgAddress,
+        destination: Option<MsgAddress>,
+        body: Vec<u8>,
+    ) -> i64 {
+        let mut state = self.state.lock().expect("poisoned lock");
+        state.lt += 1;
+        let lt = state.lt;
+        let hash = sp_core::hashing::sha2_256(&lt.to_be_bytes());
+        let transaction = Transaction {
+            address: AccountAddress {
+                account_address: account,
+            },
+            utime: lt,
+            data: vec![],
+            transaction_id: TransactionId { lt, hash },
+            fee: 0,
+            storage_fee: 0,
+            other_fee: 0,
+            in_msg: None,
+            out_msgs: vec![Message {
+                source: account,
+                destination,
+                value: 0,
+                fwd_fee: 0,
+                ihr_fee: 0,
+                created_lt: lt,
+                body_hash: sp_core::hashing::sha2_256(&body).to_vec(),
+                msg_data: MessageData::Raw {
+                    body,
+                    init_state: vec![],
+                },
+                message: None,
+            }],
+        };
+        state
+            .transactions
+            .entry(account.to_string())
+            .or_default()
+            .insert(0, transaction);
+        lt
+    }
+
+    /// Emits `OutboundMessage` with the given nonce from the `channel` and
+    /// sets `outboundNonce` get method result to it.
+    pub fn push_outbound_message(
+        &self,
+        channel: MsgAddress,
+        source: MsgAddress,
+        nonce: u64,
+        message: Cell,
+    ) -> AnyResult<i64> {
+        let body = to_boc(&OutboundMessage {
+            nonce,
+            message,
+            source,
+        })?;
+        let lt = self.push_message(channel, None, body);
+        self.set_get_method(channel, "outboundNonce", 0, vec![I256::from(nonce)]);
+        Ok(lt)
+    }
+
+    /// BoCs received by `sendBocReturnHash`.
+    pub fn sent_bocs(&self) -> Vec<Vec<u8>> {
+        self.state.lock().expect("poisoned lock").sent_bocs.clone()
+    }
+
+    /// Message bodies received by `estimateFee` for the `account`.
+    pub fn estimated_bodies(&self, account: MsgAddress) -> Vec<Vec<u8>> {
+        self.state
+            .lock()
+            .expect("poisoned lock")
+            .estimated
+            .iter()
+            .filter(|(address, _)| *address == account.to_string())
+            .map(|(_, body)| body.clone())
+            .collect()
+    }
+}
+
+/// Serializes cell to BoC in the same way as the wallet does.
+pub fn to_boc(value: &impl CellSerialize) -> AnyResult<Vec<u8>> {
+    let boc = BagOfCells::from_root(value.to_cell()?);
+    Ok(toner::tlb::bits::ser::pack_with(
+        boc,
+        BagOfCellsArgs {
+            has_crc32c: true,
+            has_idx: false,
+        },
+    )?
+    .as_raw_slice()
+    .to_vec())
+}
+
+fn ok<T: Serialize>(result: T) -> Response<Body> {
+    json_response(
+        StatusCode::OK,
+        &TonApiResult {
+            code: None,
+            error: None,
+            ok: true,
+            result: Some(result),
+        },
+    )
+}
+
+fn error_response(status: StatusCode, error: impl ToString) -> Response<Body> {
+    json_response(
+        status,
+        &TonApiResult::<()> {
+            code: Some(status.as_u16() as i64),
+            error: Some(error.to_string()),
+            ok: false,
+            result: None,
+        },
+    )
+}
+
+fn json_response<T: Serialize>(status: StatusCode, body: &T) -> Response<Body> {
+    let body = serde_json::to_vec(body).expect("response is serializable");
+    Response::builder()
+        .status(status)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .expect("response is valid")
+}
+
+fn empty_block_id() -> BlockIdExt {
+    BlockIdExt {
+        workchain: -1,
+        shard: 0,
+        seqno: 0,
+        root_hash: vec![0; 32],
+        file_hash: vec![0; 32],
+    }
+}
+
+async fn parse_body<T: DeserializeOwned>(req: Request<Body>) -> AnyResult<T> {
+    let body = hyper::body::to_bytes(req.into_body()).await?;
+    Ok(serde_json::from_slice(&body)?)
+}
+
+fn get_transactions(state: &MockState, query: &str) -> AnyResult<Response<Body>> {
+    let query: HashMap<String, String> = url::form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+    let address = query.get("address").ok_or(anyhow!("address is required"))?;
+    let limit = match query.get("limit") {
+        Some(limit) => limit.parse()?,
+        None => DEFAULT_TRANSACTIONS_LIMIT,
+    };
+    let from_lt = query.get("lt").map(|lt| lt.parse::<i64>()).transpose()?;
+    let to_lt = query.get("to_lt").map(|lt| lt.parse::<i64>()).transpose()?;
+    let transactions = state
+        .transactions
+        .get(address)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter(|tx| from_lt.map_or(true, |lt| tx.transaction_id.lt <= lt))
+        .filter(|tx| to_lt.map_or(true, |lt| tx.transaction_id.lt > lt))
+        .take(limit)
+        .collect::<Vec<_>>();
+    Ok(ok(transactions))
+}
+
+fn run_get_method(state: &MockState, request: RunGetMethod) -> Response<Body> {
+    let key = (request.address.to_string(), request.method);
+    let (exit_code, stack) = state
+        .get_methods
+        .get(&key)
+        .cloned()
+        .unwrap_or((UNKNOWN_METHOD_EXIT_CODE, vec![]));
+    let last_transaction_id = state
+        .transactions
+        .get(&key.0)
+        .and_then(|txs| txs.first())
+        .map(|tx| TransactionId {
+            lt: tx.transaction_id.lt,
+            hash: tx.transaction_id.hash,
+        })
+        .unwrap_or(TransactionId {
+            lt: 0,
+            hash: [0; 32],
+        });
+    ok(RunResult {
+        gas_used: 0,
+        stack: stack.into_iter().map(StackEntry::Int).collect(),
+        exit_code,
+        block_id: empty_block_id(),
+        last_transaction_id,
+    })
+}
+
+async fn route(state: &Mutex<MockState>, req: Request<Body>) -> AnyResult<Response<Body>> {
+    let method = req
+        .uri()
+        .path()
+        .strip_prefix("/api/v2/")
+        .unwrap_or_default()
+        .to_string();
+    let res = match method.as_str() {
+        "getTransactions" => {
+            let query = req.uri().query().unwrap_or_default().to_string();
+            get_transactions(&state.lock().expect("poisoned lock"), &query)?
+        }
+        "runGetMethod" => {
+            let request = parse_body(req).await?;
+            run_get_method(&state.lock().expect("poisoned lock"), request)
+        }
+        "sendBocReturnHash" => {
+            let request: SendBoc = parse_body(req).await?;
+            let hash = sp_core::hashing::sha2_256(&request.boc);
+            state
+                .lock()
+                .expect("poisoned lock")
+                .sent_bocs
+                .push(request.boc);
+            ok(SendBocResultHash { hash })
+        }
+        "estimateFee" => {
+            let request: EstimateFee = parse_body(req).await?;
+            state
+                .lock()
+                .expect("poisoned lock")
+                .estimated
+                .push((request.address.to_string(), request.body));
+            ok(QueryFees {
+                source_fees: Fees {
+                    in_fwd_fee: 0,
+                    storage_fee: 0,
+                    gas_fee: 0,
+                    fwd_fee: 0,
+                },
+            })
+        }
+        "getMasterchainInfo" => ok(MasterchainInfo {
+            last: empty_block_id(),
+        }),
+        _ => error_response(StatusCode::NOT_FOUND, format!("Unknown method {method}")),
+    };
+    Ok(res)
+}
+
+async fn handle_request(
+    state: Arc<Mutex<MockState>>,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    Ok(route(&state, req)
+        .await
+        .unwrap_or_else(|err| error_response(StatusCode::BAD_REQUEST, err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCOUNT: MsgAddress = MsgAddress {
+        workchain_id: 0,
+        address: [1; 32],
+    };
+
+    #[tokio::test]
+    async fn serves_scripted_state() {
+        let api = MockTonApi::start().await.unwrap();
+        let client = api.client().unwrap();
+        for body in 0..3u8 {
+            api.push_message(ACCOUNT, None, vec![body]);
+        }
+        api.set_get_method(ACCOUNT, "seqno", 0, vec![7u64.into()]);
+
+        let transactions = client
+            .get_transactions(ACCOUNT, Some(2), None, None, None)
+            .await
+            .unwrap();
+        let lts = transactions
+            .iter()
+            .map(|tx| tx.transaction_id.lt)
+            .collect::<Vec<_>>();
+        assert_eq!(lts, vec![3, 2]);
+        let transactions = client
+            .get_transactions(ACCOUNT, None, None, Some(1), None)
+            .await
+            .unwrap();
+        assert_eq!(transactions.len(), 2);
+
+        let res = client
+            .run_get_method(ACCOUNT, "seqno", vec![], None)
+            .await
+            .unwrap();
+        assert_eq!(res.exit_code, 0);
+        assert!(matches!(res.stack.first(), Some(StackEntry::Int(seqno)) if seqno.as_u32() == 7));
+        let res = client
+            .run_get_method(ACCOUNT, "unknown", vec![], None)
+            .await
+            .unwrap();
+        assert_eq!(res.exit_code, UNKNOWN_METHOD_EXIT_CODE);
+
+        let res = client.send_boc_return_hash(vec![1, 2, 3]).await.unwrap();
+        assert_eq!(res.hash, sp_core::hashing::sha2_256(&[1, 2, 3]));
+        assert_eq!(api.sent_bocs(), vec![vec![1, 2, 3]]);
+    }
+
+    #[tokio::test]
+    async fn estimates_fee_without_sending() {
+        let api = MockTonApi::start().await.unwrap();
+        let client = api.client().unwrap();
+        let fees = client.estimate_fee(ACCOUNT, vec![4, 5]).await.unwrap();
+        assert_eq!(fees.source_fees.gas_fee, 0);
+        assert_eq!(fees.source_fees.fwd_fee, 0);
+        assert_eq!(api.estimated_bodies(ACCOUNT), vec![vec![4, 5]]);
+        assert!(api.sent_bocs().is_empty());
+    }
+}
@@ -29,9 +29,14 @@
 // USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
 pub mod contracts;
+#[cfg(test)]
+pub mod mock;
 pub mod types;
 pub mod wallet;
 
+use std::sync::Arc;
+
+use crate::endpoints::{spawn_health_check, Endpoints, EndpointsHealth};
 use crate::prelude::*;
 use num_bigint::BigUint;
 use serde::de::DeserializeOwned;
@@ -53,23 +58,61 @@ use url::Url;
 use wallet::*;
 
 #[derive(Clone)]
-pub struct TonClient {
+pub struct TonClient(Arc<TonClientInner>);
+
+struct TonClientInner {
     client: reqwest::Client,
-    base: Url,
+    endpoints: Endpoints<Url>,
 }
 
 impl TonClient {
     pub fn new(base: Url, api_key: Option<String>) -> AnyResult<Self> {
+        Self::with_endpoints(vec![base], api_key)
+    }
+
+    /// Creates client which fails over between redundant endpoints.
+    pub fn with_endpoints(bases: Vec<Url>, api_key: Option<String>) -> AnyResult<Self> {
         let mut headers = http::HeaderMap::new();
         if let Some(api_key) = api_key {
             headers.insert("X-API-Key", http::HeaderValue::from_str(&api_key)?);
         }
-        Ok(Self {
-            base: base.join("api/v2/")?,
+        let bases = bases
+            .into_iter()
+            .map(|base| base.join("api/v2/"))
+            .collect::<Result<Vec<_>, _>>()?;
+        let inner = Arc::new(TonClientInner {
+            endpoints: Endpoints::new(bases)?,
             client: reqwest::Client::builder()
                 .default_headers(headers)
                 .build()?,
-        })
+        });
+        spawn_health_check(&inner);
+        Ok(Self(inner))
+    }
+
+    /// Sends request to the active endpoint and returns response body,
+    /// failing over to the next endpoint if the request could not be served.
+    async fn send<F>(&self, method: &str, build: F) -> AnyResult<String>
+    where
+        F: Fn(&reqwest::Client, Url) -> reqwest::RequestBuilder,
+    {
+        let mut last_err = anyhow!("No endpoints provided");
+        for index in self.0.endpoints.failover_order() {
+            match self.0.send_to(index, method, &build).await {
+                Ok(body) => {
+                    self.0.endpoints.set_active(index);
+                    return Ok(body);
+                }
+                Err(err) => {
+                    warn!(
+                        "Request '{method}' to {} failed: {err:?}",
+                        self.0.endpoints.url(index)
+                    );
+                    last_err = err;
+                }
+            }
+        }
+        Err(last_err)
     }
 
     pub async fn post_request<T: DeserializeOwned, B: Serialize>(
@@ -78,18 +121,9 @@ impl TonClient {
         body: &B,
     ) -> AnyResult<T> {
         trace!("Send {} => {}", method, serde_json::to_string(body)?);
-        let res = self
-            .client
-            .post(self.base.join(method)?)
-            .json(body)
-            .send()
+        let pretty = self
+            .send(method, |client, url| client.post(url).json(body))
             .await?;
-        let bytes = res.bytes().await?;
-        let value: serde_json::Value = serde_json::from_slice(&bytes)?;
-        let pretty = serde_json::to_string_pretty(&value)?;
-        for (i, line) in pretty.lines().enumerate() {
-            trace!("{i}: {line}");
-        }
         let body: TonApiResult<T> = serde_json::from_str(&pretty)?;
         if body.ok {
             Ok(body
@@ -107,19 +141,16 @@ impl TonClient {
         method: &str,
         query: &[(String, String)],
     ) -> AnyResult<T> {
-        let builder = self.client.get(self.base.join(method)?);
-        let builder = if query.is_empty() {
-            builder
-        } else {
-            builder.query(query)
-        };
-        let res = builder.send().await?;
-        let bytes = res.bytes().await?;
-        let value: serde_json::Value = serde_json::from_slice(&bytes)?;
-        let pretty = serde_json::to_string_pretty(&value)?;
-        for (i, line) in pretty.lines().enumerate() {
-            trace!("{i}: {line}");
-        }
+        let pretty = self
+            .send(method, |client, url| {
+                let builder = client.get(url);
+                if query.is_empty() {
+                    builder
+                } else {
+                    builder.query(query)
+                }
+            })
+            .await?;
         let body: TonApiResult<T> = serde_json::from_str(&pretty)?;
         if body.ok {
             Ok(body.result.ok_or(anyhow!(
@@ -181,12 +212,75 @@ impl TonClient {
         .await
     }
 
+    /// Estimates fees of the external message to the given address.
+    pub async fn estimate_fee(&self, address: MsgAddress, body: Vec<u8>) -> AnyResult<QueryFees> {
+        self.post_request(
+            "estimateFee",
+            &EstimateFee {
+                address,
+                body,
+                ignore_chksig: false,
+            },
+        )
+        .await
+    }
+
     pub async fn send_boc_return_hash(&self, boc: Vec<u8>) -> AnyResult<SendBocResultHash> {
         self.post_request("sendBocReturnHash", &SendBoc { boc })
             .await
     }
 }
 
+impl TonClientInner {
+    async fn send_to<F>(&self, index: usize, method: &str, build: &F) -> AnyResult<String>
+    where
+        F: Fn(&reqwest::Client, Url) -> reqwest::RequestBuilder,
+    {
+        let url = self.endpoints.url(index).join(method)?;
+        let res = build(&self.client, url).send().await?;
+        let status = res.status();
+        if status == http::StatusCode::TOO_MANY_REQUESTS
+            || status == http::StatusCode::BAD_GATEWAY
+            || status == http::StatusCode::SERVICE_UNAVAILABLE
+            || status == http::StatusCode::GATEWAY_TIMEOUT
+        {
+            return Err(anyhow!("Endpoint responded with {status}"));
+        }
+        let bytes = res.bytes().await?;
+        let value: serde_json::Value = serde_json::from_slice(&bytes)?;
+        let pretty = serde_json::to_string_pretty(&value)?;
+        for (i, line) in pretty.lines().enumerate() {
+            trace!("{i}: {line}");
+        }
+        Ok(pretty)
+    }
+}
+
+#[async_trait::async_trait]
+impl EndpointsHealth for TonClientInner {
+    type Url = Url;
+
+    fn endpoints(&self) -> &Endpoints<Url> {
+        &self.endpoints
+    }
+
+    async fn finalized_height(&self, index: usize) -> AnyResult<u64> {
+        let body = self
+            .send_to(
+                index,
+                "getMasterchainInfo",
+                &|client: &reqwest::Client, url| client.get(url),
+            )
+            .await?;
+        let body: TonApiResult<MasterchainInfo> = serde_json::from_str(&body)?;
+        let info = body.result.ok_or(anyhow!(
+            "Request 'getMasterchainInfo' failed: {:?}",
+            body.error
+        ))?;
+        Ok(info.last.seqno as u64)
+    }
+}
+
 pub struct SignedTonClient {
     client: TonClient,
     wallet: TonWallet,
@@ -234,18 +328,38 @@ impl SignedTonClient {
         let msg = self
             .wallet
             .create_external_message(expire_at, seqno, msgs, false)?;
-        let msg = msg.to_cell()?;
-        let boc = BagOfCells::from_root(msg);
-        let msg = toner::tlb::bits::ser::pack_with(
-            boc,
-            BagOfCellsArgs {
-                has_crc32c: true,
-                has_idx: false,
-            },
-        )?
-        .as_raw_slice()
-        .to_vec();
+        if crate::dry_run::is_enabled() {
+            let fees = self
+                .client
+                .estimate_fee(self.wallet.address(), pack_boc(msg.body.to_cell()?)?)
+                .await?;
+            let msg = msg.to_cell()?;
+            let hash = H256(msg.hash());
+            info!(
+                "Dry run: message {:?} to {} with value {} is not sent, fees {:?}: {}",
+                hash,
+                dst,
+                value,
+                fees.source_fees,
+                hex::encode(pack_boc(msg)?)
+            );
+            return Ok(hash);
+        }
+        let msg = pack_boc(msg.to_cell()?)?;
         let res = self.client.send_boc_return_hash(msg).await?;
         Ok(res.hash.into())
     }
 }
+
+fn pack_boc(cell: Cell) -> AnyResult<Vec<u8>> {
+    let boc = BagOfCells::from_root(cell);
+    Ok(toner::tlb::bits::ser::pack_with(
+        boc,
+        BagOfCellsArgs {
+            has_crc32c: true,
+            has_idx: false,
+        },
+    )?
+    .as_raw_slice()
+    .to_vec())
+}
@@ -57,6 +57,11 @@ pub struct BlockIdExt {
     pub file_hash: Vec<u8>,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MasterchainInfo {
+    pub last: BlockIdExt,
+}
+
 #[serde_as]
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AccountAddress {
@@ -162,6 +167,29 @@ pub struct SendBoc {
     pub boc: Vec<u8>,
 }
 
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EstimateFee {
+    #[serde_as(as = "DisplayFromStr")]
+    pub address: MsgAddress,
+    #[serde_as(as = "Base64")]
+    pub body: Vec<u8>,
+    pub ignore_chksig: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct QueryFees {
+    pub source_fees: Fees,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Fees {
+    pub in_fwd_fee: i64,
+    pub storage_fee: i64,
+    pub gas_fee: i64,
+    pub fwd_fee: i64,
+}
+
 #[serde_as]
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SendBocResultHash {
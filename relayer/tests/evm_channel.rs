@@ -0,0 +1,415 @@
+// This file is part of the SORA network and Polkaswap app.
+
+// Copyright (c) 2020, 2021, Polka Biome Ltd. All rights reserved.
+// SPDX-License-Identifier: BSD-4-Clause
+
+// Redistribution and use in source and binary forms, with or without modification,
+// are permitted provided that the following conditions are met:
+
+// Redistributions of source code must retain the above copyright notice, this list
+// of conditions and the following disclaimer.
+// Redistributions in binary form must reproduce the above copyright notice, this
+// list of conditions and the following disclaimer in the documentation and/or other
+// materials provided with the distribution.
+//
+// All advertising materials mentioning features or use of this software must display
+// the following acknowledgement: This product includes software developed by Polka Biome
+// Ltd., SORA, and Polkaswap.
+//
+// Neither the name of the Polka Biome Ltd. nor the names of its contributors may be used
+// to endorse or promote products derived from this software without specific prior written permission.
+
+// THIS SOFTWARE IS PROVIDED BY Polka Biome Ltd. AS IS AND ANY EXPRESS OR IMPLIED WARRANTIES,
+// INCLUDING, BUT NOT LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL Polka Biome Ltd. BE LIABLE FOR ANY
+// DIRECT, INDIRECT, INCIDENTAL, SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING,
+// BUT NOT LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE, DATA, OR PROFITS;
+// OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY THEORY OF LIABILITY, WHETHER IN CONTRACT,
+// STRICT LIABILITY, OR TORT (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE
+// USE OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Integration tests of the EVM channel flow against a local anvil node.
+//!
+//! Tests deploy `TestChannelHandler`, `FAApp` and `TestToken`. The ABI in
+//! `ethereum-gen/abi` has no bytecode, so tests need compiled contracts, e.g.
+//! hardhat artifacts of the bridge contracts:
+//!
+//! ```sh
+//! BRIDGE_CONTRACTS_ARTIFACTS=../sora2-evm-contracts/artifacts cargo test --test evm_channel -- --ignored
+//! ```
+//!
+//! Tests are ignored by default and fail if `anvil` is not installed or
+//! artifacts are not provided.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result as AnyResult};
+use bridge_types::evm::OutboundCommitment;
+use bridge_types::{EVMChainId, GenericNetworkId, SubNetworkId};
+use ethereum_gen::channel_handler::BatchDispatchedFilter;
+use ethereum_gen::{ChannelHandler, FAApp, TestToken};
+use ethers::abi::{Abi, Tokenize};
+use ethers::prelude::*;
+use ethers::utils::{Anvil, AnvilInstance};
+use sp_core::{ecdsa, Pair};
+
+#[path = "../src/relay/evm/batch.rs"]
+mod batch;
+
+use batch::MaxU32;
+
+type Client = SignerMiddleware<Provider<Http>, LocalWallet>;
+
+const ARTIFACTS_ENV: &str = "BRIDGE_CONTRACTS_ARTIFACTS";
+const PEER_SEEDS: [u8; 3] = [1, 2, 3];
+const RECIPIENT: [u8; 32] = [5; 32];
+const ASSET_ID: &str = "0x0200070000000000000000000000000000000000000000000000000000000000";
+
+struct TestEnv {
+    anvil: AnvilInstance,
+    key: String,
+    client: Arc<Client>,
+    channel: ChannelHandler<Client>,
+    app: FAApp<Client>,
+    token: TestToken<Client>,
+}
+
+fn peer(seed: u8) -> ecdsa::Pair {
+    ecdsa::Pair::from_seed(&[seed; 32])
+}
+
+fn peer_address(seed: u8) -> Address {
+    LocalWallet::from_bytes(&[seed; 32]).unwrap().address()
+}
+
+fn find_artifact(dir: &Path, name: &str) -> AnyResult<PathBuf> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if let Ok(path) = find_artifact(&path, name) {
+                return Ok(path);
+            }
+        } else if path.file_name() == Some(format!("{name}.json").as_ref()) {
+            return Ok(path);
+        }
+    }
+    Err(anyhow!("Artifact {name} not found in {dir:?}"))
+}
+
+async fn deploy(
+    client: &Arc<Client>,
+    artifacts: &Path,
+    name: &str,
+    args: impl Tokenize,
+) -> AnyResult<Address> {
+    let artifact: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(find_artifact(artifacts, name)?)?)?;
+    let abi: Abi = serde_json::from_value(artifact["abi"].clone())?;
+    let bytecode: Bytes = artifact["bytecode"]
+        .as_str()
+        .ok_or(anyhow!("Artifact {name} has no bytecode"))?
+        .parse()?;
+    let contract = ContractFactory::new(abi, bytecode, client.clone())
+        .deploy(args)?
+        .send()
+        .await?;
+    Ok(contract.address())
+}
+
+/// Starts anvil and deploys contracts.
+async fn setup() -> TestEnv {
+    let artifacts = std::env::var_os(ARTIFACTS_ENV)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| panic!("{ARTIFACTS_ENV} is not set"));
+    assert!(
+        std::process::Command::new("anvil")
+            .arg("--version")
+            .output()
+            .is_ok(),
+        "anvil is not installed"
+    );
+    let anvil = Anvil::new().spawn();
+    let wallet = LocalWallet::from(anvil.keys()[0].clone()).with_chain_id(anvil.chain_id());
+    let key = hex::encode(anvil.keys()[0].to_bytes());
+    let provider = Provider::<Http>::try_from(anvil.endpoint()).unwrap();
+    let client = Arc::new(SignerMiddleware::new(provider, wallet));
+
+    let channel = deploy(&client, &artifacts, "TestChannelHandler", ())
+        .await
+        .unwrap();
+    let token = deploy(
+        &client,
+        &artifacts,
+        "TestToken",
+        ("Test".to_string(), "TEST".to_string()),
+    )
+    .await
+    .unwrap();
+    let app = deploy(
+        &client,
+        &artifacts,
+        "FAApp",
+        (channel, vec![token], Vec::<Address>::new()),
+    )
+    .await
+    .unwrap();
+    TestEnv {
+        key,
+        channel: ChannelHandler::new(channel, client.clone()),
+        app: FAApp::new(app, client.clone()),
+        token: TestToken::new(token, client.clone()),
+        client,
+        anvil,
+    }
+}
+
+impl TestEnv {
+    /// Runs relayer command connected to the anvil node.
+    async fn relayer(&self, args: &[&str]) {
+        let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_bridge-relayer"))
+            .args(["--evm-url", &self.anvil.endpoint(), "--evm-key", &self.key])
+            .args(args)
+            .output()
+            .await
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "relayer {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    async fn initialize_channel(&self) {
+        let peers = PEER_SEEDS
+            .iter()
+            .map(|seed| format!("0x{}", hex::encode(peer(*seed).public().0)))
+            .collect::<Vec<_>>();
+        let channel = format!("{:?}", self.channel.address());
+        let mut args = vec![
+            "bridge",
+            "register",
+            "evm",
+            "initialize-channels",
+            "--channel-address",
+            &channel,
+        ];
+        for peer in peers.iter() {
+            args.extend(["--peers", peer]);
+        }
+        self.relayer(&args).await;
+        self.channel
+            .register_app(self.app.address())
+            .send()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+    }
+
+    async fn transfer_to_sora(&self, amount: u128) {
+        let owner = self.client.address();
+        self.token
+            .mint(owner, amount.into())
+            .send()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        self.token
+            .approve(self.app.address(), amount.into())
+            .send()
+            .await
+            .unwrap()
+            .await
+            .unwrap();
+        let account_id = format!("0x{}", hex::encode(RECIPIENT));
+        let amount = amount.to_string();
+        let app = format!("{:?}", self.app.address());
+        let token = format!("{:?}", self.token.address());
+        self.relayer(&[
+            "bridge",
+            "transfer",
+            "evm",
+            "sora",
+            "--account-id",
+            &account_id,
+            "--asset-id",
+            ASSET_ID,
+            "--amount",
+            &amount,
+            "--app-address",
+            &app,
+            "--token-address",
+            &token,
+        ])
+        .await;
+    }
+
+    /// Batch which unlocks tokens to the recipient.
+    fn unlock_batch(
+        &self,
+        nonce: u64,
+        recipient: Address,
+        amount: u128,
+    ) -> OutboundCommitment<MaxU32, MaxU32> {
+        let payload = self
+            .app
+            .unlock(self.token.address(), RECIPIENT, recipient, amount.into())
+            .calldata()
+            .unwrap();
+        OutboundCommitment {
+            nonce,
+            total_max_gas: 200000.into(),
+            messages: vec![bridge_types::evm::Message {
+                target: self.app.address().0.into(),
+                max_gas: 200000.into(),
+                payload: payload.to_vec().try_into().unwrap(),
+            }]
+            .try_into()
+            .unwrap(),
+        }
+    }
+
+    /// Approvals of the batch by peers, as collected on SORA for the relay.
+    fn approvals(
+        &self,
+        commitment: &OutboundCommitment<MaxU32, MaxU32>,
+        seeds: &[u8],
+    ) -> Vec<ecdsa::Signature> {
+        let message = batch::message_to_sign(
+            GenericNetworkId::Sub(SubNetworkId::Mainnet),
+            GenericNetworkId::EVM(EVMChainId::from(self.anvil.chain_id())),
+            commitment,
+        );
+        seeds
+            .iter()
+            .map(|seed| peer(*seed).sign_prehashed(&message.0))
+            .collect()
+    }
+
+    /// Submits the batch with the call built by SORA to EVM relay.
+    async fn submit(
+        &self,
+        commitment: OutboundCommitment<MaxU32, MaxU32>,
+        seeds: &[u8],
+    ) -> AnyResult<TransactionReceipt> {
+        let approvals = self.approvals(&commitment, seeds);
+        let call = batch::submit_call(&self.channel, &commitment, approvals).gas(1_000_000);
+        call.call().await?;
+        let receipt = call
+            .send()
+            .await?
+            .await?
+            .ok_or(anyhow!("Transaction dropped"))?;
+        Ok(receipt)
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs anvil and BRIDGE_CONTRACTS_ARTIFACTS"]
+async fn initialize_channel_registers_peers() {
+    let env = setup().await;
+    env.initialize_channel().await;
+    assert_eq!(
+        env.channel.peers_count().call().await.unwrap(),
+        PEER_SEEDS.len().into()
+    );
+    for seed in PEER_SEEDS {
+        assert!(env
+            .channel
+            .is_peer(peer_address(seed))
+            .call()
+            .await
+            .unwrap());
+    }
+    assert_eq!(env.channel.batch_nonce().call().await.unwrap(), 0.into());
+    assert_eq!(env.channel.message_nonce().call().await.unwrap(), 0.into());
+}
+
+#[tokio::test]
+#[ignore = "needs anvil and BRIDGE_CONTRACTS_ARTIFACTS"]
+async fn transfer_to_sora_dispatches_message() {
+    let env = setup().await;
+    env.initialize_channel().await;
+    env.transfer_to_sora(1000).await;
+    env.transfer_to_sora(500).await;
+
+    assert_eq!(env.channel.message_nonce().call().await.unwrap(), 2.into());
+    assert_eq!(
+        env.token
+            .balance_of(env.app.address())
+            .call()
+            .await
+            .unwrap(),
+        1500.into()
+    );
+    let events = env
+        .channel
+        .message_dispatched_filter()
+        .from_block(0u64)
+        .query()
+        .await
+        .unwrap();
+    let nonces = events
+        .iter()
+        .map(|event| event.nonce.as_u64())
+        .collect::<Vec<_>>();
+    assert_eq!(nonces, vec![1, 2]);
+    assert!(events.iter().all(|event| event.source == env.app.address()));
+}
+
+#[tokio::test]
+#[ignore = "needs anvil and BRIDGE_CONTRACTS_ARTIFACTS"]
+async fn submit_dispatches_batch_approved_by_peers() {
+    let env = setup().await;
+    env.initialize_channel().await;
+    env.transfer_to_sora(1000).await;
+    let recipient = peer_address(9);
+
+    let receipt = env
+        .submit(env.unlock_batch(1, recipient, 400), &PEER_SEEDS)
+        .await
+        .unwrap();
+    let dispatched = receipt
+        .logs
+        .into_iter()
+        .find_map(|log| ethers::contract::parse_log::<BatchDispatchedFilter>(log).ok())
+        .expect("BatchDispatched is emitted");
+    assert_eq!(dispatched.batch_nonce, 1.into());
+    assert_eq!(dispatched.relayer, env.client.address());
+    assert_eq!(dispatched.results_length, 1.into());
+    assert!(dispatched.results.bit(0));
+    assert_eq!(env.channel.batch_nonce().call().await.unwrap(), 1.into());
+    assert_eq!(
+        env.token.balance_of(recipient).call().await.unwrap(),
+        400.into()
+    );
+
+    // Batch can be submitted only once
+    assert!(env
+        .submit(env.unlock_batch(1, recipient, 400), &PEER_SEEDS)
+        .await
+        .is_err());
+    // Next batch needs approvals of enough peers
+    assert!(env
+        .submit(env.unlock_batch(2, recipient, 100), &PEER_SEEDS[..1])
+        .await
+        .is_err());
+    // Batches are accepted only in nonce order
+    assert!(env
+        .submit(env.unlock_batch(3, recipient, 100), &PEER_SEEDS)
+        .await
+        .is_err());
+    assert_eq!(env.channel.batch_nonce().call().await.unwrap(), 1.into());
+
+    env.submit(env.unlock_batch(2, recipient, 100), &PEER_SEEDS)
+        .await
+        .unwrap();
+    assert_eq!(env.channel.batch_nonce().call().await.unwrap(), 2.into());
+    assert_eq!(
+        env.token.balance_of(recipient).call().await.unwrap(),
+        500.into()
+    );
+}